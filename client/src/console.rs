@@ -0,0 +1,81 @@
+//! 从标准输入读取的管理命令
+use std::io::{self,BufRead};
+
+use crate::network_layer::arp::cache_table::ARP_CACHE_TABLE;
+use crate::network_layer::arp::monitor::ARP_MONITOR;
+use crate::network_layer::ip::route::{parse_destination,ROUTING_TABLE};
+use crate::tools::address::{parse_ip,parse_mac};
+
+/// 命令的用法，无法识别命令时打印
+const USAGE:&str="可用的命令：
+  arp                        打印ARP缓存表与ARP告警次数
+  arp add <IP地址> <MAC地址>  添加静态ARP表项
+  arp del <IP地址>            删除静态ARP表项
+  route                      打印路由表
+  route add <目的网络/前缀长度|default> <网关> [度量值]  添加静态路由
+  route del <目的网络/前缀长度|default>  删除路由";
+
+/// ### 功能
+/// 从标准输入逐行读取管理命令并执行，标准输入关闭时返回
+pub fn read_commands(){
+    for line in io::stdin().lock().lines(){
+        let line=match line {
+            Ok(line)=>line,
+            Err(_)=>return,
+        };
+        let fields:Vec<&str>=line.split_whitespace().collect();
+        if fields.is_empty(){
+            continue;
+        }
+        if !run_command(&fields){
+            println!("无法识别的命令：{}",line);
+            println!("{}",USAGE);
+        }
+    }
+}
+
+/// ### 功能
+/// 执行一条命令
+/// ### 返回值
+/// 命令是否合法
+fn run_command(fields:&[&str])->bool{
+    match fields {
+        ["arp"]=>{
+            ARP_CACHE_TABLE.lock().unwrap().print();
+            println!("ARP告警{}次",ARP_MONITOR.lock().unwrap().alert_count());
+        }
+        ["arp","add",ip,mac]=>{
+            let (ip,mac)=match parse_ip(ip).zip(parse_mac(mac)) {
+                Some(entry)=>entry,
+                None=>return false,
+            };
+            ARP_CACHE_TABLE.lock().unwrap().add_static_entry(ip,mac);
+        }
+        ["arp","del",ip]=>{
+            let ip=match parse_ip(ip) {
+                Some(ip)=>ip,
+                None=>return false,
+            };
+            if !ARP_CACHE_TABLE.lock().unwrap().delete_static_entry(ip){
+                println!("{}没有静态ARP表项",fields[2]);
+            }
+        }
+        ["route"]=>{
+            ROUTING_TABLE.lock().unwrap().print();
+        }
+        ["route","add",route @ ..]=>{
+            return ROUTING_TABLE.lock().unwrap().add_route_from_fields(route);
+        }
+        ["route","del",destination]=>{
+            let (destination,netmask)=match parse_destination(destination) {
+                Some(destination)=>destination,
+                None=>return false,
+            };
+            if !ROUTING_TABLE.lock().unwrap().delete_route(destination,netmask){
+                println!("没有到{}的路由",fields[2]);
+            }
+        }
+        _=>return false,
+    }
+    true
+}
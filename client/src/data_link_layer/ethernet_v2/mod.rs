@@ -1,2 +1,5 @@
 pub mod send;
 pub mod receive;
+/// 与服务器共用同一份优先级分类与调度的实现
+#[path="../../../../server/src/data_link_layer/ethernet_v2/priority.rs"]
+pub mod priority;
@@ -3,8 +3,9 @@ use std::sync::Mutex;
 use pcap::*;
 use crate::tools::crc32::*;
 use crate::network_layer::arp::receive::ArpReceiveQueue;
+use crate::network_layer::rarp::receive::RarpReceiveQueue;
 use crate::tools::global_variables::*;
-pub fn receive(shared_arp_receive_queue:Arc<Mutex<ArpReceiveQueue>>){
+pub fn receive(shared_arp_receive_queue:Arc<Mutex<ArpReceiveQueue>>,shared_rarp_receive_queue:Arc<Mutex<RarpReceiveQueue>>){
 
     //获取并打印所有网络适配器
     let devices=Device::list().unwrap();
@@ -67,7 +68,7 @@ pub fn receive(shared_arp_receive_queue:Arc<Mutex<ArpReceiveQueue>>){
 
         println!();
         //校验数据
-        if crc32_code==calculate_crc32(&packet.data[14..packet.header.caplen as usize-4].to_vec(), packet.header.caplen as i32-18 ){ 
+        if crc32_code==calculate_crc32(&packet.data[14..packet.header.caplen as usize-4], packet.header.caplen as i32-18 ){ 
             println!("CRC32校验通过！");
             //if packet.header.len-18>(46) && packet.header.caplen-18<1500{
             //    println!("数据长度检验通过！");
@@ -90,6 +91,12 @@ pub fn receive(shared_arp_receive_queue:Arc<Mutex<ArpReceiveQueue>>){
                         data.copy_from_slice(&packet.data[14..42]);
                         shared_arp_receive_queue.lock().unwrap().add_data(data);
                     }
+                    else if packet.data[12]==0x80 && packet.data[13]==0x35{
+                        //rarp协议，帧格式与arp相同
+                        let mut data:[u8;28]=[0;28];
+                        data.copy_from_slice(&packet.data[14..42]);
+                        shared_rarp_receive_queue.lock().unwrap().add_data(data);
+                    }
 
                 }
                 else {
@@ -101,7 +108,7 @@ pub fn receive(shared_arp_receive_queue:Arc<Mutex<ArpReceiveQueue>>){
             //}
         }
         else{
-            println!("CRC32校验失败！应为{:#X}！",calculate_crc32(&packet.data[14..packet.header.caplen as usize-4].to_vec(), packet.header.caplen as i32-18 ));
+            println!("CRC32校验失败！应为{:#X}！",calculate_crc32(&packet.data[14..packet.header.caplen as usize-4], packet.header.caplen as i32-18 ));
         }  
     }
 
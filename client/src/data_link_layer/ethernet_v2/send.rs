@@ -1,17 +1,21 @@
 use std::{sync::{Arc, Mutex}, thread::yield_now};
 use pcap::*;
 use std::collections::VecDeque;
+use std::time::{Duration,Instant};
 use lazy_static::*;
 
 use crate::tools::crc32::calculate_crc32;
 use crate::tools::global_variables::*;
-
+use super::priority::*;
 
 lazy_static!{
     ///静态变量--ethernet_v2的发送队列
     pub static ref ETHERNET_V2_SEND_QUEUE:Arc<Mutex<Eth2SendQueue>> = Arc::new(Mutex::new(Eth2SendQueue::new()));
 }
 
+/// 发送队列清空后打印各类别计数的最小间隔
+const STATISTICS_INTERVAL:Duration=Duration::from_secs(5);
+
 
 ///帧头
 pub struct EthernetHeader{
@@ -29,42 +33,89 @@ pub struct Eth2QueueElement{
     ///类型
     ethernet_type   :u16, 
     ///数据
-    data:Vec<u8>
+    data:Vec<u8>,
+    ///进入发送队列的时间，用于统计等待时间
+    enqueued_at     :Instant,
+}
+/// ## Ethernet v2的发送队列
+/// 每个优先级类别一个先进先出的队列（类别见priority::classify），取出时按调度方式选择类别，
+/// 使ARP、ICMP等控制报文不会排在大量分片之后
+pub struct Eth2SendQueue{
+    queues:[VecDeque<Eth2QueueElement>;PRIORITY_CLASSES],
+    scheduling:Scheduling,
+    round_robin:RoundRobin,
+    statistics:[ClassStatistics;PRIORITY_CLASSES],
 }
-pub struct Eth2SendQueue(
-    VecDeque<Eth2QueueElement>
-);
 
 impl Eth2SendQueue{
-    ///生成发送队列
+    ///生成发送队列，调度方式由全局常量确定
     pub fn new() -> Self{
-        let new_send_queue=VecDeque::new();
-        Eth2SendQueue(new_send_queue)
+        let scheduling=if WEIGHTED_ROUND_ROBIN {
+            Scheduling::WeightedRoundRobin(TRANSMIT_CLASS_WEIGHTS)
+        }
+        else{
+            Scheduling::StrictPriority
+        };
+        Eth2SendQueue{
+            queues:Default::default(),
+            scheduling,
+            round_robin:RoundRobin::new(),
+            statistics:[ClassStatistics::default();PRIORITY_CLASSES],
+        }
     }
     /// netwrok向其中写入数据。
     /// 注意分片的工作由network层负责。
     /// newwork层保证数据长度在46与1500之间，该函数中不再检查。
-    pub fn add_data(&mut self,dest_mac:[u8;6],ethernet_v2_type:u16,buffer: &Vec<u8>) -> bool{
+    pub fn add_data(&mut self,dest_mac:[u8;6],ethernet_v2_type:u16,buffer: &[u8]) -> bool{
         // if buffer.len()>1500 || buffer.len()<46{
         //     return false;
         // }
-        self.0.push_back(
+        let class=classify(ethernet_v2_type,buffer);
+        self.queues[class].push_back(
             Eth2QueueElement{
                 dest_mac_addr   :dest_mac,
                 ethernet_type   :ethernet_v2_type, 
-                data:buffer.clone()
+                data:buffer.to_vec(),
+                enqueued_at     :Instant::now(),
             }
         );
+        let statistics=&mut self.statistics[class];
+        statistics.enqueued_packets+=1;
+        statistics.enqueued_bytes+=buffer.len() as u64;
+        statistics.max_depth=statistics.max_depth.max(self.queues[class].len());
         true
     }
-    /// 获取队列数据
+    /// 按调度方式获取队列数据
     pub fn get_data(&mut self)-> Option<Eth2QueueElement>{
-        self.0.pop_front()
+        let class=match self.scheduling {
+            Scheduling::StrictPriority=>self.queues.iter().position(|queue|!queue.is_empty())?,
+            Scheduling::WeightedRoundRobin(weights)=>{
+                let non_empty=std::array::from_fn(|class|!self.queues[class].is_empty());
+                self.round_robin.next_class(&weights,&non_empty)?
+            }
+        };
+        let element=self.queues[class].pop_front()?;
+        let wait=element.enqueued_at.elapsed();
+        let statistics=&mut self.statistics[class];
+        statistics.sent_packets+=1;
+        statistics.sent_bytes+=element.data.len() as u64;
+        statistics.total_wait+=wait;
+        statistics.max_wait=statistics.max_wait.max(wait);
+        Some(element)
     }
 
     /// 队列是否为空
     pub fn is_empty(&self)->bool{
-        self.0.is_empty()
+        self.queues.iter().all(|queue|queue.is_empty())
+    }
+    /// 打印各类别的计数
+    pub fn print_statistics(&self){
+        println!("发送队列（{:?}）：",self.scheduling);
+        for (name,statistics) in CLASS_NAMES.iter().zip(self.statistics.iter()){
+            println!("  {}：入队{}帧{}字节，发送{}帧{}字节，最大队长{}，平均等待{:?}，最长等待{:?}",
+                name,statistics.enqueued_packets,statistics.enqueued_bytes,statistics.sent_packets,statistics.sent_bytes,
+                statistics.max_depth,statistics.average_wait(),statistics.max_wait);
+        }
     }
 }
 
@@ -89,17 +140,17 @@ pub fn load_ethernet_header( buffer: &mut Vec<u8>,element:&Eth2QueueElement){
 pub fn load_ethernet_data_from_network_layer(buffer: &mut Vec<u8>,element:&Eth2QueueElement) -> (bool,usize){
     let data=&element.data;
     //计算CRC32校验码
-    let crc32:u32=calculate_crc32(&data, data.len() as i32);
+    let crc32:u32=calculate_crc32(data, data.len() as i32);
 
     //拼接帧,注意要在帧头（14B）之后
-    buffer.extend_from_slice(&data);
+    buffer.extend_from_slice(data);
     for i in 14..data.len() as i32+14{
         buffer[i as usize]=data[i as usize-14];
     }
     buffer.extend_from_slice(&crc32.to_be_bytes());
 
     //返回值
-    (true,14+data.len() as usize+crc32.to_be_bytes().len())
+    (true,14+data.len()+crc32.to_be_bytes().len())
 }
 
 pub fn send(shared_ethernet_v2_send_queue:Arc<Mutex<Eth2SendQueue>>) {
@@ -145,14 +196,24 @@ pub fn send(shared_ethernet_v2_send_queue:Arc<Mutex<Eth2SendQueue>>) {
         panic!("您选择的设备不支持以太网");
     }
     
+    //上一次打印计数的时间，以及之后是否发送过帧
+    let mut last_report=Instant::now();
+    let mut sent_since_report=false;
     //轮询发送队列，队列为空则直接continue
     loop{
         if shared_ethernet_v2_send_queue.lock().unwrap().is_empty(){
+            //队列清空时打印各类别的计数
+            if sent_since_report && last_report.elapsed()>=STATISTICS_INTERVAL{
+                shared_ethernet_v2_send_queue.lock().unwrap().print_statistics();
+                last_report=Instant::now();
+                sent_since_report=false;
+            }
             yield_now();
             continue;
         }
         else {
             let element=shared_ethernet_v2_send_queue.lock().unwrap().get_data().unwrap();
+            sent_since_report=true;
 
             //本次发送中的数据帧
             let mut buffer:Vec<u8>=Vec::new();
@@ -1,24 +1,56 @@
+mod console;
 mod data_link_layer;
 mod network_layer;
 mod tools;
 
+use std::env;
 use std::sync::Arc;
 use std::thread;
 
 use crate::data_link_layer::ethernet_v2::send::ETHERNET_V2_SEND_QUEUE;
 
 use crate::network_layer::arp::cache_table::ARP_CACHE_TABLE;
+use crate::network_layer::arp::monitor::{ArpMonitorPolicy, ARP_MONITOR};
+use crate::network_layer::arp::arping::{arping, ArpingOptions};
+use crate::network_layer::arp::scan::{scan, ArpScanOptions};
 use crate::network_layer::arp::receive::ARP_RECEIVE_QUEUE;
 use crate::network_layer::arp::send::{ARP_SEND_REPLY_QUEUE, ARP_SEND_REQUEST_QUEUE};
 use crate::network_layer::icmp::receive::ICMP_RECEIVE_QUEUE;
 use crate::network_layer::icmp::send::ICMP_SEND_QUEUE;
+use crate::network_layer::rarp::receive::RARP_RECEIVE_QUEUE;
 
 use crate::network_layer::ip::send::IP_SEND_QUEUE;
+use crate::network_layer::ip::route::ROUTING_TABLE;
+
+use crate::tools::global_variables::{ARP_STATIC_TABLE_PATH, ARP_MONITOR_POLICY, ARP_MONITOR_GATEWAY_POLICY, USE_RARP, ROUTE_TABLE_PATH, NETMASK, GATEWAY_IP, local_ip};
 
 //测试icmp
 use crate::network_layer::icmp::send::test_icmp;
 
 fn main() {
+    //加载静态ARP表项
+    let count=ARP_CACHE_TABLE.lock().unwrap().load_static_entries(ARP_STATIC_TABLE_PATH);
+    println!("已加载{}条静态ARP表项",count);
+
+    //ARP欺骗检测策略，配置无效时普通主机只告警，网关固定旧映射
+    let policy=ArpMonitorPolicy::from_name(ARP_MONITOR_POLICY).unwrap_or_else(||{
+        println!("ARP检测策略{}无效，使用log",ARP_MONITOR_POLICY);
+        ArpMonitorPolicy::Log
+    });
+    let gateway_policy=ArpMonitorPolicy::from_name(ARP_MONITOR_GATEWAY_POLICY).unwrap_or_else(||{
+        println!("网关的ARP检测策略{}无效，使用pin",ARP_MONITOR_GATEWAY_POLICY);
+        ArpMonitorPolicy::Pin
+    });
+    ARP_MONITOR.lock().unwrap().set_policy(policy);
+    ARP_MONITOR.lock().unwrap().set_gateway_policy(gateway_policy);
+
+    //带参数运行时，执行诊断工具后退出
+    let args:Vec<String>=env::args().collect();
+    if args.len()>1{
+        run_tool(&args[1..]);
+        return;
+    }
+
     //测试
     test_icmp(Arc::clone(&ICMP_SEND_QUEUE));
 
@@ -35,16 +67,34 @@ fn main() {
     let eth2_receive_handle = thread::spawn(move || {
         //EthernetV2协议-接收
         data_link_layer::ethernet_v2::receive::receive(
-            Arc::clone(&ARP_RECEIVE_QUEUE));
+            Arc::clone(&ARP_RECEIVE_QUEUE),
+            Arc::clone(&RARP_RECEIVE_QUEUE));
     });
 
+    //通过RARP获取本机IP地址，需要在网络层开始工作之前完成
+    if USE_RARP{
+        network_layer::rarp::send::request_local_ip(
+            Arc::clone(&ETHERNET_V2_SEND_QUEUE),
+            Arc::clone(&RARP_RECEIVE_QUEUE));
+    }
+
+    //初始化路由表：直连路由、默认路由与配置文件中的静态路由
+    {
+        let mut routing_table=ROUTING_TABLE.lock().unwrap();
+        routing_table.add_connected_route(local_ip(),NETMASK);
+        routing_table.set_default_route(GATEWAY_IP);
+        routing_table.load_static_routes(ROUTE_TABLE_PATH);
+        routing_table.print();
+    }
+
     //运行网络层
     let ip_send_handle = thread::spawn(move || {
         //ip协议-发送
         network_layer::ip::send::send(
             Arc::clone(&ARP_CACHE_TABLE),
             Arc::clone(&ETHERNET_V2_SEND_QUEUE),
-            Arc::clone(&IP_SEND_QUEUE));
+            Arc::clone(&IP_SEND_QUEUE),
+            Arc::clone(&ICMP_RECEIVE_QUEUE));
     });
 
     let arp_send_handle = thread::spawn(move || {
@@ -72,9 +122,15 @@ fn main() {
     let icmp_receive_handle = thread::spawn(move || {
         //icmp协议-接收
         network_layer::icmp::receive::receive(
-            Arc::clone(&ICMP_RECEIVE_QUEUE));
+            Arc::clone(&ICMP_RECEIVE_QUEUE),
+            Arc::clone(&IP_SEND_QUEUE));
     });
     
+    let console_handle = thread::spawn(move || {
+        //从标准输入读取管理命令
+        console::read_commands();
+    });
+
     eth2_send_handle.join().unwrap();
     eth2_receive_handle.join().unwrap();
     ip_send_handle.join().unwrap();
@@ -82,4 +138,51 @@ fn main() {
     arp_receive_handle.join().unwrap();
     icmp_send_handle.join().unwrap();
     icmp_receive_handle.join().unwrap();
+    console_handle.join().unwrap();
+}
+
+/// ### 功能
+/// 运行诊断工具。只启动数据链路层与ARP接收，工具结束后进程退出
+fn run_tool(args:&[String]){
+    thread::spawn(move || {
+        data_link_layer::ethernet_v2::send::send(
+            Arc::clone(&ETHERNET_V2_SEND_QUEUE));
+    });
+    thread::spawn(move || {
+        data_link_layer::ethernet_v2::receive::receive(
+            Arc::clone(&ARP_RECEIVE_QUEUE),
+            Arc::clone(&RARP_RECEIVE_QUEUE));
+    });
+    thread::spawn(move || {
+        network_layer::arp::receive::receive(
+            Arc::clone(&ARP_CACHE_TABLE),
+            Arc::clone(&ARP_RECEIVE_QUEUE));
+    });
+
+    match args[0].as_str() {
+        "arping"=>{
+            match ArpingOptions::from_args(&args[1..]) {
+                Some(options)=>{
+                    arping(Arc::clone(&ETHERNET_V2_SEND_QUEUE),&options);
+                }
+                None=>{
+                    println!("用法：client arping <IP地址> [-c 次数] [-i 间隔毫秒]");
+                }
+            }
+        }
+        "scan"=>{
+            match ArpScanOptions::from_args(&args[1..]) {
+                Some(options)=>{
+                    scan(Arc::clone(&ETHERNET_V2_SEND_QUEUE),&options);
+                }
+                None=>{
+                    println!("用法：client scan [-r 每秒请求数] [-w 等待毫秒]");
+                }
+            }
+        }
+        _=>{
+            println!("未知的命令：{}",args[0]);
+            println!("可用的命令：arping、scan");
+        }
+    }
 }
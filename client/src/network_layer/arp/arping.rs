@@ -0,0 +1,139 @@
+use std::sync::{Arc,Mutex};
+use std::thread::sleep;
+use std::time::{Duration,Instant};
+
+use crate::data_link_layer::ethernet_v2::send::Eth2SendQueue;
+use crate::tools::address::{format_ip,format_mac,parse_ip};
+use crate::tools::global_variables::*;
+
+use super::receive::{register_reply_listener,unregister_reply_listener};
+use super::send::send_request;
+
+/// ## arping的参数
+pub struct ArpingOptions{
+    /// 目的IP地址
+    pub target_ip:[u8;4],
+    /// 请求次数
+    pub count:usize,
+    /// 两次请求的间隔，也是每次请求等待应答的时间
+    pub interval:Duration,
+}
+
+impl ArpingOptions{
+    /// ### 功能
+    /// 从命令行参数解析：arping <IP地址> [-c 次数] [-i 间隔毫秒]
+    /// ### 返回值
+    /// Option，参数错误则返回None
+    pub fn from_args(args:&[String])->Option<ArpingOptions>{
+        let mut options=ArpingOptions{
+            target_ip:[0;4],
+            count:4,
+            interval:Duration::from_millis(1000),
+        };
+        let mut target_ip=None;
+        let mut iter=args.iter();
+        while let Some(arg)=iter.next(){
+            match arg.as_str() {
+                "-c"=>options.count=iter.next()?.parse().ok()?,
+                "-i"=>options.interval=Duration::from_millis(iter.next()?.parse().ok()?),
+                _=>target_ip=Some(parse_ip(arg)?),
+            }
+        }
+        options.target_ip=target_ip?;
+        Some(options)
+    }
+}
+
+/// ## arping的统计结果
+pub struct ArpingStatistics{
+    /// 发出的请求数
+    pub sent:usize,
+    /// 收到应答的请求数
+    pub received:usize,
+    /// 同一请求收到的多余应答数
+    pub duplicates:usize,
+    /// 未收到应答的请求数
+    pub timeouts:usize,
+    /// 所有应答过的MAC地址
+    pub macs:Vec<[u8;6]>,
+    /// 每次请求首个应答的时延
+    pub latencies:Vec<Duration>,
+}
+
+/// ### 功能
+/// 仿照iputils的arping，按给定的次数与间隔向目的IP发送ARP请求，
+/// 报告每个应答的MAC与时延，以及重复应答与超时
+/// ### 返回值
+/// 统计结果
+pub fn arping(shared_ethernet_v2_send_queue:Arc<Mutex<Eth2SendQueue>>,options:&ArpingOptions)->ArpingStatistics{
+    let mut statistics=ArpingStatistics{
+        sent:0,
+        received:0,
+        duplicates:0,
+        timeouts:0,
+        macs:Vec::new(),
+        latencies:Vec::new(),
+    };
+    let listener=register_reply_listener();
+    println!("ARPING {} 源地址 {}",format_ip(options.target_ip),format_ip(local_ip()));
+
+    for seq in 0..options.count{
+        let send_time=Instant::now();
+        send_request(&shared_ethernet_v2_send_queue,options.target_ip);
+        statistics.sent+=1;
+
+        //在间隔时间内收集应答
+        let mut replies=0;
+        while send_time.elapsed()<options.interval{
+            let record=listener.lock().unwrap().get_data();
+            let record=match record {
+                Some(record)=>record,
+                None=>{
+                    sleep(Duration::from_millis(1));
+                    continue;
+                }
+            };
+            if record.ip!=options.target_ip{
+                continue;
+            }
+            let latency=record.time.saturating_duration_since(send_time);
+            if !statistics.macs.contains(&record.mac){
+                statistics.macs.push(record.mac);
+            }
+            replies+=1;
+            if replies==1{
+                statistics.received+=1;
+                statistics.latencies.push(latency);
+                println!("来自 {} [{}] 的应答  序号={}  {:.3}ms",
+                    format_ip(record.ip),format_mac(record.mac),seq,latency.as_secs_f64()*1000.0);
+            }
+            else{
+                statistics.duplicates+=1;
+                println!("来自 {} [{}] 的应答  序号={}  {:.3}ms  (重复)",
+                    format_ip(record.ip),format_mac(record.mac),seq,latency.as_secs_f64()*1000.0);
+            }
+        }
+        if replies==0{
+            statistics.timeouts+=1;
+            println!("序号={} 请求超时",seq);
+        }
+    }
+    unregister_reply_listener(&listener);
+
+    println!("共发送{}个请求，收到{}个应答，{}个重复，{}个超时",
+        statistics.sent,statistics.received,statistics.duplicates,statistics.timeouts);
+    if !statistics.latencies.is_empty(){
+        let min=statistics.latencies.iter().min().unwrap();
+        let max=statistics.latencies.iter().max().unwrap();
+        let avg=statistics.latencies.iter().sum::<Duration>()/statistics.latencies.len() as u32;
+        println!("时延 最小/平均/最大 = {:.3}/{:.3}/{:.3} ms",
+            min.as_secs_f64()*1000.0,avg.as_secs_f64()*1000.0,max.as_secs_f64()*1000.0);
+    }
+    if statistics.macs.len()>1{
+        println!("警告：{}个不同的MAC地址应答了该IP：",statistics.macs.len());
+        for mac in &statistics.macs{
+            println!("  {}",format_mac(*mac));
+        }
+    }
+    statistics
+}
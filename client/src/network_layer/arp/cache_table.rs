@@ -1,6 +1,9 @@
 use lazy_static::*;
+use std::fs::File;
+use std::io::{BufRead,BufReader};
 use std::sync::{Arc,Mutex};
 
+use crate::tools::address::{format_ip,format_mac,parse_ip,parse_mac};
 use super::send::ARP_SEND_REQUEST_QUEUE;
 
 lazy_static!{
@@ -39,17 +42,83 @@ impl ArpCacheTable{
     /// 新建一个缓存表
     pub fn new()->ArpCacheTable{
         let v:Vec<ArpCacheEntry>=Vec::new();
-        let mut return_value=ArpCacheTable{
+        ArpCacheTable{
             inner:v
+        }
+    }
+    /// ### 功能
+    /// 从ethers格式的文件中加载静态表项。
+    /// 每行一个表项，格式为"MAC地址 IP地址"（两者顺序可以互换），以#开头的内容为注释
+    /// ### 返回值
+    /// 成功加载的表项数
+    pub fn load_static_entries(&mut self,file_path:&str)->usize{
+        let file=match File::open(file_path) {
+            Ok(file)=>file,
+            Err(err)=>{
+                println!("读取静态ARP表项失败！{file_path}:{err}");
+                return 0;
+            }
         };
-        // 初始化时插入一条静态变量
-        let element=ArpCacheEntry{
-            ip:[ 10, 10, 10, 3 ],
-            mac:[ 0x14, 0x5A, 0xFC, 0x15, 0x1A, 0x9D ],
-            state:1
-        };
-        return_value.insert_entry(element);
-        return_value
+        let mut count=0;
+        for (line_number,line) in BufReader::new(file).lines().enumerate(){
+            let line=match line {
+                Ok(line)=>line,
+                Err(_)=>break,
+            };
+            //去掉注释
+            let line=line.split('#').next().unwrap_or("");
+            let fields:Vec<&str>=line.split_whitespace().collect();
+            if fields.is_empty(){
+                continue;
+            }
+            if fields.len()!=2{
+                println!("静态ARP表项格式错误！{file_path}:{}",line_number+1);
+                continue;
+            }
+            //ethers文件为MAC在前，也接受IP在前的写法
+            let parsed=match (parse_mac(fields[0]),parse_ip(fields[1])) {
+                (Some(mac),Some(ip))=>Some((ip,mac)),
+                _=>parse_ip(fields[0]).zip(parse_mac(fields[1])),
+            };
+            match parsed {
+                Some((ip,mac))=>{
+                    self.add_static_entry(ip,mac);
+                    count+=1;
+                }
+                None=>{
+                    println!("静态ARP表项格式错误！{file_path}:{}",line_number+1);
+                }
+            }
+        }
+        count
+    }
+    /// ### 功能
+    /// 添加一个静态（永久）表项。若该IP已有表项，则覆盖并改为静态
+    /// ### 返回值
+    /// 是否为新插入的表项
+    pub fn add_static_entry(&mut self,ip:[u8;4],mac:[u8;6])->bool{
+        for old in &mut self.inner{
+            if old.ip==ip{
+                old.mac=mac;
+                old.state=1;
+                return false;
+            }
+        }
+        self.inner.push(ArpCacheEntry::new(ip,mac,1));
+        true
+    }
+    /// ### 功能
+    /// 删除某个IP地址对应的静态表项，动态表项不受影响
+    /// ### 返回值
+    /// 是否删除成功
+    pub fn delete_static_entry(&mut self,ip:[u8;4])->bool{
+        match self.inner.iter().position(|old|old.ip==ip && old.state==1) {
+            Some(index)=>{
+                self.inner.remove(index);
+                true
+            }
+            None=>false
+        }
     }
     /// ### 功能
     /// 插入一个表项，必须保证不存在该ip地址对应表项
@@ -70,16 +139,15 @@ impl ArpCacheTable{
     /// 删除某一个表项
     /// ### 返回值
     /// 是否删除成功
+    #[allow(dead_code)]
     pub fn delete_entry(&mut self,element:ArpCacheEntry)-> bool{
-        let mut index=0;
-        for old in &mut self.inner{
-            if old.ip==element.ip && old.mac==element.mac{
+        match self.inner.iter().position(|old|old.ip==element.ip && old.mac==element.mac) {
+            Some(index)=>{
                 self.inner.remove(index);
-                return true;
+                true
             }
-            index+=1;
+            None=>false,
         }
-        false
     }
     /// ### 功能
     /// 缓存表中是否存在此IP地址
@@ -97,9 +165,14 @@ impl ArpCacheTable{
     /// 更新某个IP地址对应表项的MAC地址与状态
     /// ### 返回值
     /// 是否更新成功（不存在则无法更新）
+    /// ### 备注
+    /// 静态表项不会被动态表项覆盖
     pub fn update_entry(&mut self,element:ArpCacheEntry)->bool{
         for old in &mut self.inner{
             if old.ip==element.ip{
+                if old.state==1 && element.state!=1{
+                    return false;
+                }
                 old.mac=element.mac;
                 old.state=element.state;
                 return true;
@@ -108,6 +181,13 @@ impl ArpCacheTable{
         false
     }
     /// ### 功能
+    /// 根据IP查询表中的MAC地址，找不到时不会发送ARP请求
+    /// ### 返回值
+    /// Option，成功找到则返回mac地址
+    pub fn get_mac(&self,ip:[u8;4])->Option<[u8;6]>{
+        self.inner.iter().find(|element|element.ip==ip).map(|element|element.mac)
+    }
+    /// ### 功能
     /// 根据IP寻找MAC地址
     /// ### 返回值
     /// Option，成功找到则返回mac地址
@@ -122,4 +202,49 @@ impl ArpCacheTable{
         ARP_SEND_REQUEST_QUEUE.lock().unwrap().add_data(ip);
         None
     }
-}
\ No newline at end of file
+    /// ### 功能
+    /// 打印缓存表
+    pub fn print(&self){
+        println!("{:<16}{:<20}类型","IP地址","MAC地址");
+        for entry in &self.inner{
+            let state=match entry.state {
+                1=>"静态",
+                2=>"动态",
+                _=>"其他",
+            };
+            println!("{:<16}{:<20}{}",format_ip(entry.ip),format_mac(entry.mac),state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn static_entries_can_be_added_and_deleted(){
+        let mut table=ArpCacheTable::new();
+        assert!(table.add_static_entry([192,168,1,1],[2,0,0,0,0,1]));
+        assert!(!table.add_static_entry([192,168,1,1],[2,0,0,0,0,2]));
+        assert_eq!(table.get_mac([192,168,1,1]),Some([2,0,0,0,0,2]));
+        assert!(table.delete_static_entry([192,168,1,1]));
+        assert!(!table.delete_static_entry([192,168,1,1]));
+        assert_eq!(table.get_mac([192,168,1,1]),None);
+    }
+
+    #[test]
+    fn dynamic_entries_are_not_deleted_as_static(){
+        let mut table=ArpCacheTable::new();
+        table.insert_entry(ArpCacheEntry::new([192,168,1,2],[2,0,0,0,0,3],2));
+        assert!(!table.delete_static_entry([192,168,1,2]));
+        assert_eq!(table.get_mac([192,168,1,2]),Some([2,0,0,0,0,3]));
+    }
+
+    #[test]
+    fn static_entries_are_not_overwritten_by_dynamic_ones(){
+        let mut table=ArpCacheTable::new();
+        table.add_static_entry([192,168,1,1],[2,0,0,0,0,1]);
+        assert!(!table.update_entry(ArpCacheEntry::new([192,168,1,1],[2,0,0,0,0,9],2)));
+        assert_eq!(table.get_mac([192,168,1,1]),Some([2,0,0,0,0,1]));
+    }
+}
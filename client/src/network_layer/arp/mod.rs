@@ -1,3 +1,6 @@
 pub mod send;
 pub mod receive;
-pub mod cache_table;
\ No newline at end of file
+pub mod cache_table;
+pub mod monitor;
+pub mod arping;
+pub mod scan;
\ No newline at end of file
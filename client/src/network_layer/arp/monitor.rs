@@ -0,0 +1,209 @@
+use std::collections::VecDeque;
+use std::sync::{Arc,Mutex};
+use std::time::{Duration,Instant};
+use lazy_static::*;
+
+use crate::tools::address::{format_ip,format_mac};
+use crate::tools::global_variables::*;
+
+lazy_static!{
+    ///静态变量--ARP欺骗检测器
+    pub static ref ARP_MONITOR:Arc<Mutex<ArpMonitor>> = Arc::new(Mutex::new(ArpMonitor::new()));
+}
+
+/// 发出的ARP请求在多长时间内收到应答算作"已请求"
+const ARP_REQUEST_TIMEOUT:Duration=Duration::from_secs(3);
+/// 统计MAC抖动的时间窗口
+const ARP_FLAP_WINDOW:Duration=Duration::from_secs(10);
+/// 时间窗口内MAC变化达到该次数即视为抖动
+const ARP_FLAP_THRESHOLD:usize=3;
+
+/// 检测到IP→MAC映射变化时的处理策略
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum ArpMonitorPolicy{
+    /// 只记录告警，照常更新缓存表
+    Log,
+    /// 记录告警，并忽略这次变化
+    Ignore,
+    /// 记录告警，并把旧的映射固定为静态表项
+    Pin,
+}
+
+impl ArpMonitorPolicy{
+    /// ### 功能
+    /// 由配置中的名称解析处理策略
+    pub fn from_name(name:&str)->Option<ArpMonitorPolicy>{
+        match name.to_ascii_lowercase().as_str() {
+            "log"=>Some(ArpMonitorPolicy::Log),
+            "ignore"=>Some(ArpMonitorPolicy::Ignore),
+            "pin"=>Some(ArpMonitorPolicy::Pin),
+            _=>None,
+        }
+    }
+}
+
+/// 检测器对一个ARP应答的裁决
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum ArpVerdict{
+    /// 正常写入缓存表
+    Update,
+    /// 丢弃，不修改缓存表
+    Ignore,
+    /// 丢弃，并把所给的旧MAC固定为静态表项
+    Pin([u8;6]),
+}
+
+/// 某个IP地址的MAC历史
+struct ArpHistory{
+    ip:[u8;4],
+    mac:[u8;6],
+    /// 最近若干次MAC变化的时间
+    changes:VecDeque<Instant>,
+}
+
+/// ## ARP欺骗与MAC抖动检测器
+/// 检查每个收到的ARP应答，发现以下情况时告警：
+/// - 某个IP对应的MAC发生变化（网关尤甚）
+/// - 短时间内MAC反复变化
+/// - 收到了本机从未请求过的应答
+pub struct ArpMonitor{
+    /// 普通主机的处理策略
+    policy:ArpMonitorPolicy,
+    /// 网关的处理策略
+    gateway_policy:ArpMonitorPolicy,
+    history:Vec<ArpHistory>,
+    /// 尚未得到应答的请求：(目的IP，发送时间)
+    pending_requests:Vec<([u8;4],Instant)>,
+    /// 告警次数
+    alert_count:u64,
+}
+
+impl ArpMonitor{
+    /// ### 功能
+    /// 新建一个检测器，默认策略均为只记录
+    pub fn new()->ArpMonitor{
+        ArpMonitor{
+            policy:ArpMonitorPolicy::Log,
+            gateway_policy:ArpMonitorPolicy::Log,
+            history:Vec::new(),
+            pending_requests:Vec::new(),
+            alert_count:0,
+        }
+    }
+    /// ### 功能
+    /// 设置普通主机的处理策略
+    pub fn set_policy(&mut self,policy:ArpMonitorPolicy){
+        self.policy=policy;
+    }
+    /// ### 功能
+    /// 设置网关的处理策略
+    pub fn set_gateway_policy(&mut self,policy:ArpMonitorPolicy){
+        self.gateway_policy=policy;
+    }
+    /// ### 功能
+    /// 获取告警次数
+    pub fn alert_count(&self)->u64{
+        self.alert_count
+    }
+    /// ### 功能
+    /// 记录一次发出的ARP请求，由arp发送端调用
+    pub fn record_request(&mut self,ip:[u8;4]){
+        self.pending_requests.retain(|(old_ip,_)|*old_ip!=ip);
+        self.pending_requests.push((ip,Instant::now()));
+    }
+    /// ### 功能
+    /// 检查一个收到的ARP应答
+    /// ### 参数
+    /// ip、mac为应答中的发送端地址，cached_mac为缓存表中该IP现有的MAC
+    /// ### 返回值
+    /// 对该应答的裁决
+    pub fn check_reply(&mut self,ip:[u8;4],mac:[u8;6],cached_mac:Option<[u8;6]>)->ArpVerdict{
+        let now=Instant::now();
+        let is_gateway=ip==GATEWAY_IP;
+        let policy=if is_gateway {self.gateway_policy} else {self.policy};
+        let mut suspicious=false;
+
+        //是否为本机请求过的应答
+        self.pending_requests.retain(|(_,time)|now.duration_since(*time)<ARP_REQUEST_TIMEOUT);
+        match self.pending_requests.iter().position(|(old_ip,_)|*old_ip==ip) {
+            Some(index)=>{
+                self.pending_requests.remove(index);
+            }
+            None=>{
+                self.alert(format!("收到未请求的ARP应答：{} 为 {}",format_ip(ip),format_mac(mac)));
+                suspicious=true;
+            }
+        }
+
+        //IP→MAC是否变化
+        let mut changed=false;
+        if let Some(old_mac)=cached_mac{
+            if old_mac!=mac{
+                changed=true;
+                if is_gateway{
+                    self.alert(format!("网关{}的MAC由{}变为{}，可能遭受ARP欺骗！",format_ip(ip),format_mac(old_mac),format_mac(mac)));
+                }
+                else{
+                    self.alert(format!("{}的MAC由{}变为{}",format_ip(ip),format_mac(old_mac),format_mac(mac)));
+                }
+            }
+        }
+
+        //维护历史并检测抖动
+        let flapping=match self.history.iter_mut().find(|h|h.ip==ip) {
+            Some(history)=>{
+                if history.mac!=mac{
+                    history.mac=mac;
+                    history.changes.push_back(now);
+                }
+                while history.changes.front().is_some_and(|time|now.duration_since(*time)>ARP_FLAP_WINDOW){
+                    history.changes.pop_front();
+                }
+                if history.changes.len()>=ARP_FLAP_THRESHOLD{
+                    Some(history.changes.len())
+                }
+                else{
+                    None
+                }
+            }
+            None=>{
+                self.history.push(ArpHistory{ip,mac,changes:VecDeque::new()});
+                None
+            }
+        };
+        if let Some(count)=flapping{
+            if changed{
+                self.alert(format!("{}的MAC在{}秒内变化了{}次，发生MAC抖动",format_ip(ip),ARP_FLAP_WINDOW.as_secs(),count));
+            }
+        }
+
+        //MAC未变化的应答（即使未请求过）只是刷新表项，无需处理
+        if !changed && (!suspicious || cached_mac.is_some()){
+            return ArpVerdict::Update;
+        }
+        match (policy,cached_mac) {
+            (ArpMonitorPolicy::Log,_)=>ArpVerdict::Update,
+            (ArpMonitorPolicy::Pin,Some(old_mac))=>ArpVerdict::Pin(old_mac),
+            _=>ArpVerdict::Ignore,
+        }
+    }
+    /// ### 功能
+    /// 输出一条告警并计数
+    fn alert(&mut self,message:String){
+        self.alert_count+=1;
+        println!("[ARP告警] {}",message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policy_from_name(){
+        assert_eq!(ArpMonitorPolicy::from_name("log"),Some(ArpMonitorPolicy::Log));
+        assert_eq!(ArpMonitorPolicy::from_name("Ignore"),Some(ArpMonitorPolicy::Ignore));
+        assert_eq!(ArpMonitorPolicy::from_name("PIN"),Some(ArpMonitorPolicy::Pin));
+        assert_eq!(ArpMonitorPolicy::from_name("drop"),None);
+    }
+}
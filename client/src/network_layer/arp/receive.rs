@@ -1,13 +1,56 @@
 use std::collections::VecDeque;
 use std::sync::{Arc,Mutex};
 use std::thread::yield_now;
+use std::time::Instant;
 
 use lazy_static::*;
 
 use super::cache_table::{ArpCacheEntry, ArpCacheTable};
+use super::monitor::{ArpVerdict,ARP_MONITOR};
 lazy_static!{
     ///静态变量--ARP的发送队列
     pub static ref ARP_RECEIVE_QUEUE:Arc<Mutex<ArpReceiveQueue>> = Arc::new(Mutex::new(ArpReceiveQueue::new()));
+    ///静态变量--ARP应答的监听者，arping等工具通过它获取收到的应答
+    static ref ARP_REPLY_LISTENERS:Mutex<Vec<Arc<Mutex<ArpReplyListener>>>> = Mutex::new(Vec::new());
+}
+
+/// 一条收到的ARP应答记录
+#[derive(Clone,Copy)]
+pub struct ArpReplyRecord{
+    /// 发送端IP地址
+    pub ip:[u8;4],
+    /// 发送端MAC地址
+    pub mac:[u8;6],
+    /// 收到的时间
+    pub time:Instant,
+}
+
+///ARP应答的监听队列
+pub struct ArpReplyListener(
+    VecDeque<ArpReplyRecord>
+);
+
+impl ArpReplyListener{
+    /// 获取队列数据
+    pub fn get_data(&mut self)-> Option<ArpReplyRecord>{
+        self.0.pop_front()
+    }
+}
+
+/// ### 功能
+/// 注册一个ARP应答的监听者，此后收到的每个ARP应答都会复制一份给它
+/// ### 返回值
+/// 监听队列
+pub fn register_reply_listener()->Arc<Mutex<ArpReplyListener>>{
+    let listener=Arc::new(Mutex::new(ArpReplyListener(VecDeque::new())));
+    ARP_REPLY_LISTENERS.lock().unwrap().push(Arc::clone(&listener));
+    listener
+}
+
+/// ### 功能
+/// 注销一个ARP应答的监听者
+pub fn unregister_reply_listener(listener:&Arc<Mutex<ArpReplyListener>>){
+    ARP_REPLY_LISTENERS.lock().unwrap().retain(|old|!Arc::ptr_eq(old,listener));
 }
 
 ///ARP的接收队列
@@ -27,7 +70,7 @@ impl ArpReceiveQueue{
         //if buffer.len()>1500 || buffer.len()<46{
         //    return false;
         //}
-        self.0.push_back(buffer.clone());
+        self.0.push_back(buffer);
         true
     }
     /// 获取队列数据
@@ -58,22 +101,43 @@ pub fn receive(shared_arp_cache_table:Arc<Mutex<ArpCacheTable>>,shared_arp_recei
             continue;
         }
 
+        let sender_ip:[u8;4]=arp_frame[14..18].try_into().unwrap();
+        let sender_mac:[u8;6]=arp_frame[8..14].try_into().unwrap();
+
+        //通知监听者
+        let record=ArpReplyRecord{ip:sender_ip,mac:sender_mac,time:Instant::now()};
+        for listener in ARP_REPLY_LISTENERS.lock().unwrap().iter(){
+            listener.lock().unwrap().0.push_back(record);
+        }
+
+        //交给检测器检查是否存在ARP欺骗
+        let mut arp_cache_table=shared_arp_cache_table.lock().unwrap();
+        let cached_mac=arp_cache_table.get_mac(sender_ip);
+        match ARP_MONITOR.lock().unwrap().check_reply(sender_ip,sender_mac,cached_mac) {
+            ArpVerdict::Update=>{}
+            ArpVerdict::Ignore=>{
+                continue;
+            }
+            ArpVerdict::Pin(old_mac)=>{
+                arp_cache_table.add_static_entry(sender_ip,old_mac);
+                continue;
+            }
+        }
+
         let arp_cache_entry=ArpCacheEntry::new(
-            arp_frame[14..18].try_into().unwrap(),
-            arp_frame[8..14].try_into().unwrap(),
+            sender_ip,
+            sender_mac,
             // 1:静态 2:动态 3: log
             2
         );
 
-        if shared_arp_cache_table.lock().unwrap().is_existed_ip(arp_frame[14..18].try_into().unwrap()){
+        if arp_cache_table.is_existed_ip(sender_ip){
             //如果存在则更新
-            shared_arp_cache_table.lock().unwrap().update_entry(arp_cache_entry);
+            arp_cache_table.update_entry(arp_cache_entry);
         }
         else{
             //否则插入
-            shared_arp_cache_table.lock().unwrap().insert_entry(arp_cache_entry);
+            arp_cache_table.insert_entry(arp_cache_entry);
         }
-        
-
     }
 }
\ No newline at end of file
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::sync::{Arc,Mutex};
+use std::thread::sleep;
+use std::time::{Duration,Instant};
+
+use crate::data_link_layer::ethernet_v2::send::Eth2SendQueue;
+use crate::tools::address::{format_ip,format_mac};
+use crate::tools::global_variables::*;
+
+use super::receive::{register_reply_listener,unregister_reply_listener,ArpReplyListener};
+use super::send::send_request;
+
+/// ## 子网扫描的参数
+pub struct ArpScanOptions{
+    /// 每秒最多发送的请求数
+    pub rate:u32,
+    /// 全部请求发出后继续等待应答的时间
+    pub wait:Duration,
+}
+
+impl ArpScanOptions{
+    /// ### 功能
+    /// 从命令行参数解析：scan [-r 每秒请求数] [-w 等待毫秒]
+    /// ### 返回值
+    /// Option，参数错误则返回None
+    pub fn from_args(args:&[String])->Option<ArpScanOptions>{
+        let mut options=ArpScanOptions{
+            rate:100,
+            wait:Duration::from_millis(2000),
+        };
+        let mut iter=args.iter();
+        while let Some(arg)=iter.next(){
+            match arg.as_str() {
+                "-r"=>options.rate=iter.next()?.parse().ok().filter(|rate|*rate>0)?,
+                "-w"=>options.wait=Duration::from_millis(iter.next()?.parse().ok()?),
+                _=>return None,
+            }
+        }
+        Some(options)
+    }
+}
+
+/// 扫描发现的一台主机
+pub struct ArpScanHost{
+    pub ip:[u8;4],
+    pub mac:[u8;6],
+    /// 从发出请求到收到应答的时间
+    pub response_time:Duration,
+}
+
+/// ### 功能
+/// 扫描本机所在子网（由本机IP与NETMASK确定）的所有主机地址。
+/// 按限定的速率逐个发送ARP请求，应答由ARP接收线程写入缓存表，
+/// 同时在此收集并打印主机清单
+/// ### 返回值
+/// 按IP排序的主机清单
+pub fn scan(shared_ethernet_v2_send_queue:Arc<Mutex<Eth2SendQueue>>,options:&ArpScanOptions)->Vec<ArpScanHost>{
+    let netmask=u32::from_be_bytes(NETMASK);
+    let network=u32::from_be_bytes(local_ip()) & netmask;
+    let broadcast=network | !netmask;
+    let interval=Duration::from_secs(1)/options.rate;
+
+    println!("扫描子网 {}/{}，共{}个地址",format_ip(network.to_be_bytes()),netmask.count_ones(),broadcast.saturating_sub(network+1));
+
+    let listener=register_reply_listener();
+    let mut send_times:HashMap<[u8;4],Instant>=HashMap::new();
+    let mut hosts:Vec<ArpScanHost>=Vec::new();
+
+    //逐个发送请求（跳过网络地址、广播地址与本机）
+    for host in network+1..broadcast{
+        let ip=host.to_be_bytes();
+        if ip==local_ip(){
+            continue;
+        }
+        send_times.insert(ip,Instant::now());
+        send_request(&shared_ethernet_v2_send_queue,ip);
+        collect_replies(&listener,&send_times,&mut hosts);
+        sleep(interval);
+    }
+
+    //等待剩余的应答
+    let start=Instant::now();
+    while start.elapsed()<options.wait{
+        collect_replies(&listener,&send_times,&mut hosts);
+        sleep(Duration::from_millis(10));
+    }
+    unregister_reply_listener(&listener);
+
+    hosts.sort_by_key(|host|u32::from_be_bytes(host.ip));
+    println!("{:<16}{:<20}响应时间","IP地址","MAC地址");
+    for host in &hosts{
+        println!("{:<16}{:<20}{:.3}ms",format_ip(host.ip),format_mac(host.mac),host.response_time.as_secs_f64()*1000.0);
+    }
+    println!("共发现{}台主机",hosts.len());
+    hosts
+}
+
+/// ### 功能
+/// 取出监听队列中的应答，记录每个被扫描地址的首个应答
+fn collect_replies(
+    listener:&Arc<Mutex<ArpReplyListener>>,
+    send_times:&HashMap<[u8;4],Instant>,
+    hosts:&mut Vec<ArpScanHost>
+){
+    let mut listener=listener.lock().unwrap();
+    while let Some(record)=listener.get_data(){
+        let send_time=match send_times.get(&record.ip) {
+            Some(send_time)=>*send_time,
+            //不是本次扫描请求的地址
+            None=>continue,
+        };
+        if hosts.iter().any(|host|host.ip==record.ip){
+            continue;
+        }
+        hosts.push(ArpScanHost{
+            ip:record.ip,
+            mac:record.mac,
+            response_time:record.time.saturating_duration_since(send_time),
+        });
+    }
+}
@@ -1,12 +1,12 @@
 use std::collections::VecDeque;
 use std::sync::{Arc,Mutex};
-use std::thread::yield_now;
 use lazy_static::*;
 use crate::tools::global_variables::*;
 
 use crate::data_link_layer::ethernet_v2::send::Eth2SendQueue;
 
 use super::cache_table::ArpCacheTable;
+use super::monitor::ARP_MONITOR;
 lazy_static!{
     ///静态变量--ARP应答报文的发送队列
     pub static ref ARP_SEND_REPLY_QUEUE:Arc<Mutex<ArpSendReplyQueue>> = Arc::new(Mutex::new(ArpSendReplyQueue::new()));
@@ -26,8 +26,9 @@ impl ArpSendReplyQueue{
         ArpSendReplyQueue(new_send_queue)
     }
     /// 由receive控制，向其中加入封装好的arp应答帧
+    #[allow(dead_code)]
     pub fn add_data(&mut self,arp_frame: [u8;28]) -> bool{
-        self.0.push_back(arp_frame.clone());
+        self.0.push_back(arp_frame);
         true
     }
     /// 获取应答报文队列数据
@@ -54,7 +55,7 @@ impl ArpSendRequestQueue{
     }
     /// 由ip控制，向其中加入ipv4地址
     pub fn add_data(&mut self,ip: [u8;4]) -> bool{
-        self.0.push_back(ip.clone());
+        self.0.push_back(ip);
         true
     }
     /// 获取队列数据
@@ -67,6 +68,53 @@ impl ArpSendRequestQueue{
         self.0.is_empty()
     }
 }
+/// ### 功能
+/// 按ARP报文格式（以太网+IPv4）封装一个28字节的帧，RARP也使用同样的格式
+/// ### 参数
+/// operation为操作字段：1为ARP请求，2为ARP应答，3为RARP请求，4为RARP应答
+pub fn build_arp_frame(
+    operation:u16,
+    sender_mac:[u8;6],
+    sender_ip:[u8;4],
+    target_mac:[u8;6],
+    target_ip:[u8;4]
+)->[u8;28]{
+    let mut arp_frame:[u8;28]=[0;28];
+    //硬件类型
+    arp_frame[0..2].copy_from_slice(&[0x00,0x01]);
+    //协议类型
+    arp_frame[2..4].copy_from_slice(&[0x08,0x00]);
+    //硬件地址长度
+    arp_frame[4]=6;
+    //协议地址长度
+    arp_frame[5]=4;
+    //操作字段
+    arp_frame[6..8].copy_from_slice(&operation.to_be_bytes());
+    //发送端mac地址
+    arp_frame[8..14].copy_from_slice(&sender_mac);
+    //发送端ip地址
+    arp_frame[14..18].copy_from_slice(&sender_ip);
+    //目的mac地址
+    arp_frame[18..24].copy_from_slice(&target_mac);
+    //目的ip地址
+    arp_frame[24..28].copy_from_slice(&target_ip);
+    arp_frame
+}
+
+/// ### 功能
+/// 立即广播一个询问dest_ip的ARP请求，不查询缓存表。
+/// arping、子网扫描等工具也通过它发送请求
+pub fn send_request(shared_ethernet_v2_send_queue:&Arc<Mutex<Eth2SendQueue>>,dest_ip:[u8;4]){
+    //封装为帧。op=1代表为ARP请求，目的mac地址全0
+    let arp_frame=build_arp_frame(1,LOCAL_MAC,local_ip(),[0;6],dest_ip);
+
+    //记录本次请求，供检测器识别未请求的应答
+    ARP_MONITOR.lock().unwrap().record_request(dest_ip);
+
+    //发送--写入到Ethernet-v2的发送队列里
+    shared_ethernet_v2_send_queue.lock().unwrap().add_data(BROADCAST_MAC,0x0806,&Vec::from(arp_frame));
+}
+
 ///### 功能
 /// 考虑到为server端，目前仅支持发送arp应答报文。arp请求报文发送在client端。
 pub fn send(
@@ -94,46 +142,15 @@ pub fn send(
         else if !shared_arp_send_request_queue.lock().unwrap().is_empty(){
             let mut sendqueue=shared_arp_send_request_queue.lock().unwrap();
             //队列不为空，则封装为帧，并发送
-            let mut dest_ip=sendqueue.get_data().unwrap();
+            //请求的是下一跳的地址，由ip层通过路由表确定，这里不再替换为网关
+            let dest_ip=sendqueue.get_data().unwrap();
 
             //为了防止重复发送
             if shared_arp_cache_table.lock().unwrap().is_existed_ip(dest_ip){
                 continue;
             }
-            
-            //如果为同一子网，为目的ip
-            dest_ip= if (u32::from_be_bytes(dest_ip) & u32::from_be_bytes(NETMASK))==(u32::from_be_bytes(LOCAL_IP) & u32::from_be_bytes(NETMASK)){
-                dest_ip
-            }
-            else{
-                //否则为网关的ip
-                GATEWAY_IP
-            };
-            
 
-            //封装为帧
-            let mut arp_frame:[u8;28]=[0;28];
-            //硬件类型
-            arp_frame[0..2].copy_from_slice(&[0x00,0x01]);
-            //协议类型
-            arp_frame[2..4].copy_from_slice(&[0x08,0x00]);
-            //硬件地址长度
-            arp_frame[4]=6;
-            //协议地址长度
-            arp_frame[5]=4;
-            //操作字段。op=1代表为ARP请求
-            arp_frame[6..8].copy_from_slice(&[0x00,0x01]);
-            //发送端mac地址
-            arp_frame[8..14].copy_from_slice(&LOCAL_MAC);
-            //发送端ip地址
-            arp_frame[14..18].copy_from_slice(&LOCAL_IP);
-            //目的mac地址。全0
-            arp_frame[18..24].copy_from_slice(&[0;6]);
-            //目的ip地址
-            arp_frame[24..28].copy_from_slice(&dest_ip);
-
-            //发送--写入到Ethernet-v2的发送队列里
-            shared_ethernet_v2_send_queue.lock().unwrap().add_data(BROADCAST_MAC,0x0806,&Vec::from(arp_frame));
+            send_request(&shared_ethernet_v2_send_queue,dest_ip);
         }
     }
 }
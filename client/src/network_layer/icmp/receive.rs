@@ -3,6 +3,9 @@ use std::sync::{Arc,Mutex};
 use std::thread::yield_now;
 use lazy_static::*;
 
+use crate::network_layer::ip::send::{IPSendQueue,ICMPV4_PROTOCOL};
+use crate::tools::checksum::internet_checksum;
+
 
 lazy_static!{
     ///静态变量--ARP的发送队列
@@ -10,8 +13,9 @@ lazy_static!{
 }
 
 ///ARP的接收队列
+/// 元素为(源地址，ICMP报文)，源地址用于回答回送请求
 pub struct IcmpReceiveQueue(
-    VecDeque<Vec<u8>>
+    VecDeque<([u8;4],Vec<u8>)>
 );
 
 impl IcmpReceiveQueue{
@@ -21,19 +25,14 @@ impl IcmpReceiveQueue{
             IcmpReceiveQueue(new_send_queue)
         }
         /// 由ipv4协议写入
-        pub fn add_data(&mut self,data: Vec<u8>) -> bool{
-            self.0.push_back(data.clone());
+        pub fn add_data(&mut self,source_ip:[u8;4],data: Vec<u8>) -> bool{
+            self.0.push_back((source_ip,data));
             true
         }
         /// 获取队列数据
-        pub fn get_data(&mut self)-> Option<Vec<u8>>{
+        pub fn get_data(&mut self)-> Option<([u8;4],Vec<u8>)>{
             self.0.pop_front()
         }
-    
-        /// 队列是否为空
-        pub fn is_empty(&self)->bool{
-            self.0.is_empty()
-        }
 }
 
 pub struct IcmpHeader{
@@ -46,13 +45,13 @@ pub struct IcmpHeader{
 impl IcmpHeader {
     /// ### 功能
     /// 计算首部校验和时使用，把首部转化为2字节的数组（30*2）
-    pub fn into_u16_array(&self) -> Vec<u16> {
-        let mut result:Vec<u16>=Vec::new();
-        result.push((self.icmp_type[0] as u16) << 8 | self.code[0] as u16);
-        result.push((self.check_sum[0] as u16) << 8 | self.check_sum[1] as u16);
-        result.push((self.other[0] as u16)<<8|(self.other[1] as u16));
-        result.push((self.other[2] as u16)<<8|(self.other[3] as u16));
-        result
+    pub fn to_u16_array(&self) -> Vec<u16> {
+        vec![
+            (self.icmp_type[0] as u16) << 8 | self.code[0] as u16,
+            (self.check_sum[0] as u16) << 8 | self.check_sum[1] as u16,
+            (self.other[0] as u16)<<8|(self.other[1] as u16),
+            (self.other[2] as u16)<<8|(self.other[3] as u16),
+        ]
     }
     /// ### 功能
     /// 根据所给值生成头部，并自动计算首部校验和
@@ -95,19 +94,19 @@ impl IcmpHeader {
     pub fn calculate_check_sum(&self)-> u16{
         let mut sum:u32=0;
         let len:usize=8;
-        let hdr=self.into_u16_array();
+        let hdr=self.to_u16_array();
     
-        for i in 0..hdr.len()-1{
-            sum=sum+hdr[i] as u32;
+        for value in &hdr[..hdr.len()-1]{
+            sum+=*value as u32;
         }
     
         //如果最后剩了一字节
         if len%2==1{
             //那么只需要加最后一个[u16]的高8位即可
-            sum=sum+ ( (hdr[hdr.len()-1]>>8) & 0x00ff )as u32;
+            sum+=( (hdr[hdr.len()-1]>>8) & 0x00ff )as u32;
         }
         else{
-            sum=sum+hdr[hdr.len()-1]as u32;
+            sum+=hdr[hdr.len()-1]as u32;
         }
         
         //压缩32位到16位
@@ -121,27 +120,45 @@ impl IcmpHeader {
 }
 
 
-pub fn receive(shared_icmp_receive_queue:Arc<Mutex<IcmpReceiveQueue>>){
+/// ### 功能
+/// 根据回送请求生成回送回答：类型改为0，标识符、序号与数据不变，重新计算整个报文的校验和
+fn echo_reply(request:&[u8])->Vec<u8>{
+    let mut reply=request.to_vec();
+    reply[0]=0;
+    reply[2..4].copy_from_slice(&[0,0]);
+    let check_sum=internet_checksum(&reply);
+    reply[2..4].copy_from_slice(&check_sum.to_be_bytes());
+    reply
+}
+
+pub fn receive(
+    shared_icmp_receive_queue:Arc<Mutex<IcmpReceiveQueue>>,
+    shared_ip_send_queue:Arc<Mutex<IPSendQueue>>)
+{
     loop{
-        let mut receive_queue=shared_icmp_receive_queue.lock().unwrap();
-        if receive_queue.is_empty(){
-            yield_now();
+        let element=shared_icmp_receive_queue.lock().unwrap().get_data();
+        let (source_ip,data)=match element {
+            Some(element)=>element,
+            None=>{
+                yield_now();
+                continue;
+            }
+        };
+        //不足8字节的首部或校验和错误的报文丢弃
+        if data.len()<8 || internet_checksum(&data)!=0{
             continue;
         }
 
-        let data=receive_queue.get_data().unwrap();
-
-        let hdr=IcmpHeader::from_vec_u8(data);
-
-        if hdr.icmp_type[0]==11{
+        if data[0]==11{
             println!("接收到ICMP超时报文！");
         }
-        else if hdr.icmp_type[0]==8{
+        else if data[0]==8{
             println!("接收到ICMP回送请求报文！");
+            //经IP发送回送回答，目的地址为请求的源地址
+            shared_ip_send_queue.lock().unwrap().add_data_to(source_ip,echo_reply(&data),ICMPV4_PROTOCOL);
         }
-        else if  hdr.icmp_type[0]==0 {
+        else if data[0]==0 {
             println!("接收到ICMP回送回答报文！");
         }
-        
     }
-}
\ No newline at end of file
+}
@@ -56,29 +56,29 @@ pub fn send(
             //加载头部
             let hdr=IcmpHeader::from_vec_u8(data.clone());
             let mut buffer:Vec<u8>=Vec::new();
-            for i in hdr.into_u16_array(){
+            for i in hdr.to_u16_array(){
                 buffer.push(((i>>8)&0x00_ff )as u8);
                 buffer.push((i&0x00_ff )as u8);
             }
             //加载数据
             buffer.append(&mut data[8..data.len()].to_vec());
     
-            shared_ip_send_queue.lock().unwrap().add_data(buffer.into(),ICMPV4_PROTOCOL);
+            shared_ip_send_queue.lock().unwrap().add_data(buffer,ICMPV4_PROTOCOL);
         }
     }
 }
 
 
+#[allow(dead_code)]
 fn test_ip_send_queue(shared_ip_send_queue:Arc<Mutex<IPSendQueue>>)  {
     let file_path=String::from("data.txt");
     let mut tmp=Vec::new();
     match File::open(file_path) {
         Ok(mut file)=>{
             //读到tmp里
-            let size_of_data;
-            size_of_data=file.read_to_end( &mut tmp).unwrap()as i32;
+            file.read_to_end( &mut tmp).unwrap();
         }
-        Err(err)=> {
+        Err(_)=> {
             println!("读取文件失败！");
         }
     } 
@@ -88,7 +88,7 @@ fn test_ip_send_queue(shared_ip_send_queue:Arc<Mutex<IPSendQueue>>)  {
 pub fn test_icmp(shared_icmp_send_queue:Arc<Mutex<IcmpSendQueue>>){
     let mut data:Vec<u8>=Vec::new();
     let hdr=IcmpHeader::new(11, 0, 0);
-    for i in hdr.into_u16_array(){
+    for i in hdr.to_u16_array(){
         data.push(((i>>8)&0x00_ff )as u8);
         data.push((i&0x00_ff )as u8);
     }
@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher,Hasher};
+
+/// 最多为多少个目的地址分别维护标识，超过后清空重新开始
+const MAX_DESTINATIONS:usize=1024;
+
+/// ## IP数据报标识的生成器
+/// 为每个目的地址维护一个从随机值开始递增的标识，
+/// 使发往同一目的地址的相邻数据报标识不同，不同目的地址之间也不易重复
+pub struct IdGenerator{
+    counters:HashMap<[u8;4],u16>
+}
+
+impl IdGenerator{
+    /// ### 功能
+    /// 新建生成器
+    pub fn new()->IdGenerator{
+        IdGenerator{
+            counters:HashMap::new()
+        }
+    }
+    /// ### 功能
+    /// 为发往destination_ip的下一个数据报生成标识，同一数据报的所有分片使用同一标识
+    pub fn next_id(&mut self,destination_ip:[u8;4])->u16{
+        if self.counters.len()>=MAX_DESTINATIONS && !self.counters.contains_key(&destination_ip){
+            self.counters.clear();
+        }
+        let counter=self.counters.entry(destination_ip).or_insert_with(random_u16);
+        *counter=counter.wrapping_add(1);
+        *counter
+    }
+}
+
+/// ### 功能
+/// 生成一个随机的16位数，作为每个目的地址的初始标识
+fn random_u16()->u16{
+    //RandomState每次创建时使用不同的随机种子
+    RandomState::new().build_hasher().finish() as u16
+}
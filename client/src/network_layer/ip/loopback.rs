@@ -0,0 +1,31 @@
+use std::sync::{Arc,Mutex};
+
+use crate::network_layer::icmp::receive::IcmpReceiveQueue;
+use crate::tools::address::format_ip;
+
+use super::send::ICMPV4_PROTOCOL;
+
+/// 回环接口的地址
+pub const LOOPBACK_IP:[u8;4]=[127,0,0,1];
+/// 回环接口的MTU，本机之间的数据报不需要分片
+pub const LOOPBACK_MTU:usize=65535;
+
+/// ### 功能
+/// IP地址是否属于回环网络127.0.0.0/8
+pub fn is_loopback_ip(ip:[u8;4])->bool{
+    ip[0]==127
+}
+
+/// ### 功能
+/// 回环接口：把发给本机的分组直接交给本机的上层协议，不经过ARP与以太网。
+/// 客户端只接收ICMP，其他协议的分组被丢弃
+pub fn deliver_local(packet:&[u8],shared_icmp_receive_queue:&Arc<Mutex<IcmpReceiveQueue>>){
+    let header_len=((packet[0]&0x0f) as usize)*4;
+    let source_ip:[u8;4]=packet[12..16].try_into().unwrap();
+    if packet[9]==ICMPV4_PROTOCOL{
+        shared_icmp_receive_queue.lock().unwrap().add_data(source_ip,packet[header_len..].to_vec());
+    }
+    else{
+        println!("回环接口没有上层协议{}，丢弃来自{}的分组",packet[9],format_ip(source_ip));
+    }
+}
@@ -1 +1,8 @@
-pub mod send;
\ No newline at end of file
+pub mod send;
+pub mod route;
+/// 与服务器共用同一份选项的解析与编码。客户端没有IP接收，只用到编码部分
+#[path="../../../../server/src/network_layer/ip/option.rs"]
+#[allow(dead_code)]
+pub mod option;
+pub mod id;
+pub mod loopback;
\ No newline at end of file
@@ -0,0 +1,294 @@
+use lazy_static::*;
+use std::fs::File;
+use std::io::{BufRead,BufReader};
+use std::sync::{Arc,Mutex};
+
+use crate::tools::address::{format_ip,parse_ip};
+
+lazy_static!{
+    //静态变量--路由表
+    pub static ref ROUTING_TABLE:Arc<Mutex<RoutingTable>> = Arc::new(Mutex::new(RoutingTable::new()));
+}
+
+/// 路由的来源
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum RouteType{
+    /// 直连路由，目的主机与本机在同一子网
+    Connected,
+    /// 静态路由
+    Static,
+    /// 默认路由，即0.0.0.0/0
+    Default,
+}
+
+/// 路由表的表项
+#[derive(Clone,Copy)]
+pub struct RouteEntry{
+    /// 目的网络
+    pub destination:[u8;4],
+    /// 子网掩码
+    pub netmask:[u8;4],
+    /// 下一跳网关，直连路由没有网关
+    pub gateway:Option<[u8;4]>,
+    pub route_type:RouteType,
+    /// 度量值，前缀长度相同时优先选择较小者
+    pub metric:u32,
+}
+
+impl RouteEntry{
+    /// ### 功能
+    /// 前缀长度
+    pub fn prefix_len(&self)->u32{
+        u32::from_be_bytes(self.netmask).count_ones()
+    }
+    /// ### 功能
+    /// 目的地址是否属于该表项的网络
+    pub fn matches(&self,ip:[u8;4])->bool{
+        let netmask=u32::from_be_bytes(self.netmask);
+        (u32::from_be_bytes(ip) & netmask)==(u32::from_be_bytes(self.destination) & netmask)
+    }
+}
+
+/// ## 路由表
+/// 按最长前缀匹配查找路由，并给出下一跳地址
+pub struct RoutingTable{
+    inner:Vec<RouteEntry>
+}
+
+impl RoutingTable{
+    /// ### 功能
+    /// 新建一个空的路由表
+    pub fn new()->RoutingTable{
+        RoutingTable{
+            inner:Vec::new()
+        }
+    }
+    /// ### 功能
+    /// 添加一条路由。目的网络与掩码相同的旧路由会被替换
+    pub fn add_route(&mut self,entry:RouteEntry){
+        let mut entry=entry;
+        //规范化目的网络
+        entry.destination=(u32::from_be_bytes(entry.destination) & u32::from_be_bytes(entry.netmask)).to_be_bytes();
+        self.inner.retain(|old|!(old.destination==entry.destination && old.netmask==entry.netmask));
+        self.inner.push(entry);
+    }
+    /// ### 功能
+    /// 根据本机地址与掩码添加直连路由
+    pub fn add_connected_route(&mut self,local_ip:[u8;4],netmask:[u8;4]){
+        self.add_route(RouteEntry{
+            destination:local_ip,
+            netmask,
+            gateway:None,
+            route_type:RouteType::Connected,
+            metric:0,
+        });
+    }
+    /// ### 功能
+    /// 添加一条经由gateway到达destination/netmask的静态路由
+    pub fn add_static_route(&mut self,destination:[u8;4],netmask:[u8;4],gateway:[u8;4],metric:u32){
+        self.add_route(RouteEntry{
+            destination,
+            netmask,
+            gateway:Some(gateway),
+            route_type:RouteType::Static,
+            metric,
+        });
+    }
+    /// ### 功能
+    /// 设置默认网关
+    pub fn set_default_route(&mut self,gateway:[u8;4]){
+        self.add_route(RouteEntry{
+            destination:[0;4],
+            netmask:[0;4],
+            gateway:Some(gateway),
+            route_type:RouteType::Default,
+            metric:0,
+        });
+    }
+    /// ### 功能
+    /// 删除目的网络与掩码对应的路由
+    /// ### 返回值
+    /// 是否删除成功
+    pub fn delete_route(&mut self,destination:[u8;4],netmask:[u8;4])->bool{
+        let destination=(u32::from_be_bytes(destination) & u32::from_be_bytes(netmask)).to_be_bytes();
+        let len=self.inner.len();
+        self.inner.retain(|old|!(old.destination==destination && old.netmask==netmask));
+        self.inner.len()!=len
+    }
+    /// ### 功能
+    /// 最长前缀匹配查找目的地址的路由
+    /// ### 返回值
+    /// Option，找到则返回路由表项
+    pub fn lookup(&self,destination:[u8;4])->Option<RouteEntry>{
+        self.inner.iter()
+            .filter(|entry|entry.matches(destination))
+            .max_by(|a,b|a.prefix_len().cmp(&b.prefix_len()).then(b.metric.cmp(&a.metric)))
+            .copied()
+    }
+    /// ### 功能
+    /// 查找目的地址的下一跳：直连路由的下一跳为目的地址本身，否则为网关
+    /// ### 返回值
+    /// Option，没有路由则返回None
+    pub fn next_hop(&self,destination:[u8;4])->Option<[u8;4]>{
+        self.lookup(destination).map(|entry|entry.gateway.unwrap_or(destination))
+    }
+    /// ### 功能
+    /// 从文件中加载静态路由。每行一条，格式为"目的网络/前缀长度 网关 [度量值]"，
+    /// 目的网络写作default时为默认路由，以#开头的内容为注释
+    /// ### 返回值
+    /// 成功加载的路由数
+    pub fn load_static_routes(&mut self,file_path:&str)->usize{
+        let file=match File::open(file_path) {
+            Ok(file)=>file,
+            Err(err)=>{
+                println!("读取静态路由失败！{file_path}:{err}");
+                return 0;
+            }
+        };
+        let mut count=0;
+        for (line_number,line) in BufReader::new(file).lines().enumerate(){
+            let line=match line {
+                Ok(line)=>line,
+                Err(_)=>break,
+            };
+            let line=line.split('#').next().unwrap_or("");
+            let fields:Vec<&str>=line.split_whitespace().collect();
+            if fields.is_empty(){
+                continue;
+            }
+            if self.add_route_from_fields(&fields){
+                count+=1;
+            }
+            else{
+                println!("静态路由格式错误！{file_path}:{}",line_number+1);
+            }
+        }
+        count
+    }
+    /// ### 功能
+    /// 按静态路由配置的格式添加一条路由，目的网络为default时设置默认网关
+    /// ### 返回值
+    /// 是否添加成功（格式错误则失败）
+    pub fn add_route_from_fields(&mut self,fields:&[&str])->bool{
+        let (destination,netmask,gateway,metric)=match parse_route(fields) {
+            Some(route)=>route,
+            None=>return false,
+        };
+        if netmask==[0;4]{
+            self.set_default_route(gateway);
+        }
+        else{
+            self.add_static_route(destination,netmask,gateway,metric);
+        }
+        true
+    }
+    /// ### 功能
+    /// 打印路由表
+    pub fn print(&self){
+        println!("{:<20}{:<16}{:<10}度量值","目的网络","网关","类型");
+        for entry in &self.inner{
+            let gateway=match entry.gateway {
+                Some(gateway)=>format_ip(gateway),
+                None=>String::from("直连"),
+            };
+            println!("{:<20}{:<16}{:<10}{}",
+                format!("{}/{}",format_ip(entry.destination),entry.prefix_len()),
+                gateway,format!("{:?}",entry.route_type),entry.metric);
+        }
+    }
+}
+
+/// ### 功能
+/// 由前缀长度生成子网掩码
+pub fn netmask_from_prefix_len(prefix_len:u32)->[u8;4]{
+    if prefix_len==0{
+        return [0;4];
+    }
+    (u32::MAX << (32-prefix_len.min(32))).to_be_bytes()
+}
+
+/// ### 功能
+/// 解析"目的网络/前缀长度"，default表示默认路由
+/// ### 返回值
+/// Option，(目的网络，掩码)
+pub fn parse_destination(field:&str)->Option<([u8;4],[u8;4])>{
+    if field=="default"{
+        return Some(([0;4],[0;4]));
+    }
+    let (network,prefix_len)=field.split_once('/')?;
+    let prefix_len:u32=prefix_len.parse().ok().filter(|len|*len<=32)?;
+    Some((parse_ip(network)?,netmask_from_prefix_len(prefix_len)))
+}
+
+/// 一行静态路由：(目的网络，掩码，网关，度量值)
+type StaticRoute=([u8;4],[u8;4],[u8;4],u32);
+
+/// ### 功能
+/// 解析一行静态路由
+/// ### 返回值
+/// Option，格式错误时返回None
+fn parse_route(fields:&[&str])->Option<StaticRoute>{
+    if fields.len()<2 || fields.len()>3{
+        return None;
+    }
+    let (destination,netmask)=parse_destination(fields[0])?;
+    let gateway=parse_ip(fields[1])?;
+    let metric=match fields.get(2) {
+        Some(metric)=>metric.parse().ok()?,
+        None=>0,
+    };
+    Some((destination,netmask,gateway,metric))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table()->RoutingTable{
+        let mut table=RoutingTable::new();
+        table.add_connected_route([192,168,1,10],[255,255,255,0]);
+        table.set_default_route([192,168,1,1]);
+        table
+    }
+
+    #[test]
+    fn longest_prefix_wins(){
+        let mut table=table();
+        assert!(table.add_route_from_fields(&["10.0.0.0/8","192.168.1.2"]));
+        assert!(table.add_route_from_fields(&["10.1.0.0/16","192.168.1.3"]));
+        assert_eq!(table.next_hop([10,1,2,3]),Some([192,168,1,3]));
+        assert_eq!(table.next_hop([10,2,2,3]),Some([192,168,1,2]));
+        assert_eq!(table.next_hop([192,168,1,20]),Some([192,168,1,20]));
+        assert_eq!(table.next_hop([8,8,8,8]),Some([192,168,1,1]));
+    }
+
+    #[test]
+    fn smaller_metric_wins_for_equal_prefixes(){
+        let mut table=RoutingTable::new();
+        table.add_route(RouteEntry{destination:[10,0,0,0],netmask:[255,0,0,0],gateway:Some([192,168,1,2]),route_type:RouteType::Static,metric:10});
+        table.add_route(RouteEntry{destination:[10,0,0,0],netmask:[255,255,0,0],gateway:Some([192,168,1,3]),route_type:RouteType::Static,metric:1});
+        assert_eq!(table.next_hop([10,0,1,1]),Some([192,168,1,3]));
+        assert_eq!(table.next_hop([10,1,1,1]),Some([192,168,1,2]));
+    }
+
+    #[test]
+    fn deleted_routes_are_no_longer_used(){
+        let mut table=table();
+        table.add_route_from_fields(&["10.0.0.0/8","192.168.1.2"]);
+        assert!(table.delete_route([10,1,2,3],[255,0,0,0]));
+        assert!(!table.delete_route([10,0,0,0],[255,0,0,0]));
+        assert_eq!(table.next_hop([10,1,2,3]),Some([192,168,1,1]));
+        assert!(table.delete_route([0;4],[0;4]));
+        assert_eq!(table.next_hop([10,1,2,3]),None);
+    }
+
+    #[test]
+    fn malformed_routes_are_rejected(){
+        let mut table=RoutingTable::new();
+        assert!(!table.add_route_from_fields(&["10.0.0.0","192.168.1.2"]));
+        assert!(!table.add_route_from_fields(&["10.0.0.0/33","192.168.1.2"]));
+        assert!(!table.add_route_from_fields(&["10.0.0.0/8","192.168.1"]));
+        assert!(!table.add_route_from_fields(&["10.0.0.0/8","192.168.1.2","x"]));
+        assert_eq!(parse_destination("default"),Some(([0;4],[0;4])));
+    }
+}
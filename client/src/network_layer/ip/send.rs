@@ -1,36 +1,115 @@
 use std::collections::VecDeque;
 use lazy_static::*;
 use std::sync::{Arc,Mutex};
+use std::sync::mpsc::Sender;
 use std::thread::yield_now;
 use crate::data_link_layer::ethernet_v2::send::Eth2SendQueue;
 use crate::network_layer::arp::cache_table::ArpCacheTable;
+use crate::network_layer::icmp::receive::IcmpReceiveQueue;
+use crate::tools::address::format_ip;
+use crate::tools::checksum::internet_checksum;
 use crate::tools::global_variables::*;
 
+use super::id::IdGenerator;
+use super::loopback::{deliver_local,is_loopback_ip,LOOPBACK_IP,LOOPBACK_MTU};
+use super::option::{copied_options,encode_options,IpOption};
+use super::route::ROUTING_TABLE;
+
 lazy_static!{
     //静态变量--IP发送队列
     pub static ref IP_SEND_QUEUE:Arc<Mutex<IPSendQueue>> = Arc::new(Mutex::new(IPSendQueue::new()));
 }
 
 
-///最大分片长度
-pub const DATA_SLICE_LENTH:usize=1400;
+/// 标志位-DF，不分片
+pub const FLAG_DF:u16=1<<14;
+/// 标志位-MF，还有更多分片
+pub const FLAG_MF:u16=1<<13;
 /// 上层协议字段-TCP
+#[allow(dead_code)]
 pub const TCP_PROTOCOL:u8 = 6;
 /// 上层协议字段-UDP
 pub const UDP_PROTOCOL :u8= 17;
 /// 上层协议字段-ICMPV4
 pub const ICMPV4_PROTOCOL:u8=1;
 /// 上层协议字段-IGMPV4
+#[allow(dead_code)]
 pub const IGMPV4_PROTOCOL:u8=2;
+/// 默认的生存时间
+pub const DEFAULT_TIME_TO_LIVE:u8=64;
 
 ///ARP应答报文的发送队列
 pub struct IPSendQueue(
     VecDeque<IPSendQueueElement>
 );
 
+/// IP发送队列的元素。由new生成默认值，上层协议可以再修改其中的字段
 pub struct IPSendQueueElement{
-    protocol_type:u8,
-    data:Vec<u8>
+    /// 目的IP地址
+    pub destination_ip:[u8;4],
+    /// 源地址提示，为None时使用本机地址
+    pub source_ip:Option<[u8;4]>,
+    /// 生存时间，traceroute等探测可以设置较小的值
+    pub time_to_live:u8,
+    /// 服务类型，高6位为DSCP，低2位为ECN
+    pub type_of_service:u8,
+    /// 是否禁止分片（DF），置位时超过MTU的数据报发送失败
+    pub dont_fragment:bool,
+    /// 上层协议
+    pub protocol_type:u8,
+    /// 上层数据
+    pub data:Vec<u8>,
+    /// IP首部的选项
+    pub options:Vec<IpOption>,
+    /// 发送结果的通知通道：交给数据链路层后通知Ok，失败时通知错误原因
+    pub result_sender:Option<Sender<Result<(),IpSendError>>>,
+}
+
+impl IPSendQueueElement{
+    /// ### 功能
+    /// 生成发往destination_ip的数据报，其余字段取默认值：
+    /// 源地址为本机地址，TTL为64，服务类型为0，允许分片，不带选项，不通知结果
+    pub fn new(destination_ip:[u8;4],protocol_type:u8,data:Vec<u8>)->Self{
+        IPSendQueueElement{
+            destination_ip,
+            source_ip:None,
+            time_to_live:DEFAULT_TIME_TO_LIVE,
+            type_of_service:0,
+            dont_fragment:false,
+            protocol_type,
+            data,
+            options:Vec::new(),
+            result_sender:None,
+        }
+    }
+    /// ### 功能
+    /// 通过通知通道报告发送结果，上层已不再等待时忽略
+    fn report(&self,result:Result<(),IpSendError>){
+        if let Some(sender)=&self.result_sender{
+            let _=sender.send(result);
+        }
+    }
+}
+
+/// IP发送失败的原因
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum IpSendError{
+    /// 没有到目的地址的路由
+    NoRoute,
+    /// 数据报超过MTU，但DF置位不允许分片
+    FragmentationNeeded{mtu:usize},
+    /// 选项超过40字节
+    OptionsTooLong,
+}
+
+impl std::fmt::Display for IpSendError{
+    fn fmt(&self,f:&mut std::fmt::Formatter<'_>)->std::fmt::Result{
+        match self {
+            IpSendError::NoRoute=>write!(f,"没有路由"),
+            IpSendError::FragmentationNeeded{mtu}=>write!(f,"数据报超过MTU {}，且不允许分片",mtu),
+            IpSendError::OptionsTooLong=>write!(f,"IP选项超过40字节"),
+        }
+    }
 }
 
 impl IPSendQueue{
@@ -39,12 +118,16 @@ impl IPSendQueue{
         let new_send_queue=VecDeque::new();
         IPSendQueue(new_send_queue)
     }
-    /// 由上层协议写入
+    /// 由上层协议写入，发往默认的目的地址DEST_IP
     pub fn add_data(&mut self,data: Vec<u8>,protocol_type:u8) -> bool{
-        let element=IPSendQueueElement{
-            protocol_type,
-            data
-        };
+        self.add_data_to(DEST_IP,data,protocol_type)
+    }
+    /// 由上层协议写入，发往destination_ip，其余字段取默认值
+    pub fn add_data_to(&mut self,destination_ip:[u8;4],data: Vec<u8>,protocol_type:u8) -> bool{
+        self.add_element(IPSendQueueElement::new(destination_ip,protocol_type,data))
+    }
+    /// 由上层协议写入一个设置好的元素（目的地址、TTL、服务类型、DF、选项、结果通知等）
+    pub fn add_element(&mut self,element:IPSendQueueElement) -> bool{
         self.0.push_back(element);
         true
     }
@@ -52,15 +135,10 @@ impl IPSendQueue{
     pub fn get_data(&mut self)-> Option<IPSendQueueElement>{
         self.0.pop_front()
     }
-
-    /// 队列是否为空
-    pub fn is_empty(&self)->bool{
-        self.0.is_empty()
-    }
 }
 
 struct IpHeader{
-    /// 默认IP版本：IPV4，头部长度：单位为4字节，最长60字节
+    /// IP版本：IPV4，头部长度：单位为4字节，20~60字节，由选项的长度决定
     version_and_hdrlen:u8,
     /// 服务类型
 	type_of_service:u8,
@@ -80,36 +158,37 @@ struct IpHeader{
 	source_ip:[u8;4],   
     /// 目的IP地址
     destination_ip:[u8;4],
-    /// 40字节的可选部分
-	optional:[u8;40],
+    /// 选项，已补齐到4字节的整数倍，最长40字节
+	options:Vec<u8>,
 }
 
 impl IpHeader {
     /// ### 功能
-    /// 计算首部校验和时使用，把首部转化为2字节的数组（30*2）
-    pub fn into_u16_array(&self) -> Vec<u16> {
-        let mut result:Vec<u16>=Vec::new();
-        result.push((self.version_and_hdrlen as u16) << 8 | self.type_of_service as u16);
-        result.push(self.total_length);
-        result.push(self.id);
-        result.push(self.flags_and_fragment_offset);
-        result.push((self.time_to_live as u16) << 8 | self.upper_protocol_type as u16);
-        result.push(self.check_sum);
-        result.push((self.source_ip[3] as u16)<<8|(self.source_ip[2] as u16));
-        result.push((self.source_ip[1] as u16)<<8|(self.source_ip[0] as u16));
-        result.push((self.destination_ip[3] as u16)<<8|(self.destination_ip[2] as u16));
-        result.push((self.destination_ip[1] as u16)<<8|(self.destination_ip[0] as u16));
-        for i in 0..20{
-            result.push((self.optional[2*i] as u16) << 8 | self.optional[2*i+1] as u16);
+    /// 计算首部校验和时使用，把首部按网络字节序转化为2字节的数组
+    pub fn to_u16_array(&self) -> Vec<u16> {
+        let mut result:Vec<u16>=vec![
+            (self.version_and_hdrlen as u16) << 8 | self.type_of_service as u16,
+            self.total_length,
+            self.id,
+            self.flags_and_fragment_offset,
+            (self.time_to_live as u16) << 8 | self.upper_protocol_type as u16,
+            self.check_sum,
+            (self.source_ip[0] as u16)<<8|(self.source_ip[1] as u16),
+            (self.source_ip[2] as u16)<<8|(self.source_ip[3] as u16),
+            (self.destination_ip[0] as u16)<<8|(self.destination_ip[1] as u16),
+            (self.destination_ip[2] as u16)<<8|(self.destination_ip[3] as u16),
+        ];
+        for i in 0..self.options.len()/2{
+            result.push((self.options[2*i] as u16) << 8 | self.options[2*i+1] as u16);
         }
         result
     }
     /// ### 功能
-    /// 根据所给值生成头部，并自动计算首部校验和
+    /// 根据所给值生成头部，首部长度由选项的长度决定，并自动计算首部校验和
     /// ### 返回值
     /// 计算过首部校验和的头部
+    #[allow(clippy::too_many_arguments)]
     pub fn new (
-        in_version_and_hdrlen:u8,
         in_type_of_service:u8,
         in_total_length:u16,
         in_id:u16,			
@@ -118,10 +197,10 @@ impl IpHeader {
         in_upper_protocol_type:u8,
         in_source_ip:[u8;4],   
         in_destination_ip:[u8;4],
-        in_optional:[u8;40],
+        in_options:Vec<u8>,
     )-> IpHeader{
         let mut hdr=IpHeader{
-            version_and_hdrlen:in_version_and_hdrlen,
+            version_and_hdrlen:0x40 | (5+in_options.len()/4) as u8,
             type_of_service:in_type_of_service,
             total_length:in_total_length,
             id:in_id,			
@@ -131,7 +210,7 @@ impl IpHeader {
             check_sum:0x0000,
             source_ip:in_source_ip,   
             destination_ip:in_destination_ip,
-            optional:in_optional,
+            options:in_options,
         };
         hdr.check_sum=calculate_check_sum(&hdr);
         hdr
@@ -143,30 +222,7 @@ impl IpHeader {
 /// ### 返回值 
 /// 16bit的校验和
 fn calculate_check_sum(ip_hdr:&IpHeader)-> u16{
-    let mut sum:u32=0;
-    let len:usize=(ip_hdr.version_and_hdrlen&0x000f)as usize;
-	let hdr=ip_hdr.into_u16_array();
-
-	for i in 0..hdr.len()-1{
-        sum=sum+hdr[i] as u32;
-    }
-
-    //如果最后剩了一字节
-    if len%2==1{
-        //那么只需要加最后一个[u16]的高8位即可
-        sum=sum+ ( (hdr[hdr.len()-1]>>8) & 0x00ff )as u32;
-    }
-    else{
-        sum=sum+hdr[hdr.len()-1]as u32;
-    }
-	
-	//压缩32位到16位
-	while sum>>16 >0
-	{
-		sum=(sum & 0xffff)+(sum>>16);
-	}
-
-    (sum & 0xffff) as u16
+    internet_checksum(&u8_from_u16(&ip_hdr.to_u16_array()))
 }
 
 
@@ -182,122 +238,144 @@ pub fn u8_from_u16 (u16_array:& Vec<u16>)-> Vec<u8>{
 }
 
 
-pub fn send(
-    shared_arp_cache_table:Arc<Mutex<ArpCacheTable>>,
-    shared_ethernet_v2_send_queue:Arc<Mutex<Eth2SendQueue>>,
-    shared_ip_send_queue:Arc<Mutex<IPSendQueue>>
-) {
+/// ### 功能
+/// 通过ARP解析下一跳的MAC地址，并把IP分组交给数据链路层发送。
+/// 下一跳由路由表给出：直连时为目的地址，否则为网关
+fn send_to_next_hop(
+    next_hop:[u8;4],
+    buffer:&[u8],
+    shared_arp_cache_table:&Arc<Mutex<ArpCacheTable>>,
+    shared_ethernet_v2_send_queue:&Arc<Mutex<Eth2SendQueue>>
+){
     loop{
-        //分片数
-        let mut sendqueue=shared_ip_send_queue.lock().unwrap();
-        if sendqueue.is_empty(){
+        let dest_mac=shared_arp_cache_table.lock().unwrap().find_mac_from_ip(next_hop);
+        if let Some(dest_mac)=dest_mac{
+            let mut sendqueue=shared_ethernet_v2_send_queue.lock().unwrap();
+            sendqueue.add_data(dest_mac,0x0800,buffer);
+            break;
+        }
+        else {
             yield_now();
-            continue;
         }
-        let element=sendqueue.get_data().unwrap();
-        let len_of_data=element.data.len();
+    }
+}
 
+/// ### 功能
+/// 为一个数据报封装首部，并按MTU分片：每个分片的数据按8字节对齐，
+/// 第一个分片携带全部选项，其余分片只携带需要复制的选项
+/// ### 返回值
+/// Result，成功时为依次发送的分组；DF置位而又需要分片时返回错误
+fn build_packets(element:&IPSendQueueElement,id:u16,mtu:usize)->Result<Vec<Vec<u8>>,IpSendError>{
+    let options=encode_options(&element.options).ok_or(IpSendError::OptionsTooLong)?;
+    let later_options=copied_options(&options);
+    let data=&element.data;
 
-        let number_of_slice=(len_of_data as usize /DATA_SLICE_LENTH)+1;
+    if element.dont_fragment && 20+options.len()+data.len()>mtu{
+        return Err(IpSendError::FragmentationNeeded{mtu});
+    }
+    let mut packets=Vec::new();
+    let mut start=0;
+    loop{
+        let options=if start==0 {options.clone()} else {later_options.clone()};
+        //每个分片最多携带的数据，除最后一个分片外必须是8的倍数
+        let max_data_len=mtu.saturating_sub(20+options.len()) & !7;
+        if max_data_len==0{
+            return Err(IpSendError::FragmentationNeeded{mtu});
+        }
+        let end=(start+max_data_len).min(data.len());
+        let last=end==data.len();
+        let mut flags_and_fragment_offset=(start/8) as u16;
+        if element.dont_fragment{
+            flags_and_fragment_offset|=FLAG_DF;
+        }
+        if !last{
+            flags_and_fragment_offset|=FLAG_MF;
+        }
+        let hdr:IpHeader=IpHeader::new (
+            element.type_of_service,
+            (20+options.len()+end-start) as u16,
+            id,
+            flags_and_fragment_offset,
+            element.time_to_live,
+            element.protocol_type,
+            element.source_ip.unwrap_or_else(local_ip),
+            element.destination_ip,
+            options,
+        );
+        let mut buffer:Vec<u8>=u8_from_u16(&(hdr.to_u16_array()));
+        buffer.extend_from_slice(&data[start..end]);
+        packets.push(buffer);
+        if last{
+            break;
+        }
+        start=end;
+    }
+    Ok(packets)
+}
 
-        //如果不需要分片
-        if number_of_slice==1{
-            let mut buffer:Vec<u8>=Vec::new();
-            let hdr:IpHeader=IpHeader::new (
-                0x4f,//60
-                0xfe,
-                60+len_of_data as u16,
-                2023,			
-                0b0100_0000_0000_0000,//DF=1,offset=0
-                64,
-                element.protocol_type,
-                LOCAL_IP,   
-                DEST_IP,
-                [0;40],
-            );
+pub fn send(
+    shared_arp_cache_table:Arc<Mutex<ArpCacheTable>>,
+    shared_ethernet_v2_send_queue:Arc<Mutex<Eth2SendQueue>>,
+    shared_ip_send_queue:Arc<Mutex<IPSendQueue>>,
+    shared_icmp_receive_queue:Arc<Mutex<IcmpReceiveQueue>>
+) {
+    //数据报的标识，按目的地址分别生成
+    let mut id_generator=IdGenerator::new();
+    loop{
+        let element=shared_ip_send_queue.lock().unwrap().get_data();
+        let element=match element {
+            Some(element)=>element,
+            None=>{
+                yield_now();
+                continue;
+            }
+        };
 
-            buffer.extend(u8_from_u16(&(hdr.into_u16_array())));
-            buffer.extend(element.data);
-            
-            loop{
-                let dest_mac=shared_arp_cache_table.lock().unwrap().find_mac_from_ip(DEST_IP);
-                if dest_mac.is_some(){
-                    let mut sendqueue=shared_ethernet_v2_send_queue.lock().unwrap();
-                    sendqueue.add_data(dest_mac.unwrap(),0x0800,&buffer);
-                    break;
-                }
-                else {
-                    yield_now()
-                }
+        //目的地址是本机或回环网络：经回环接口直接交给本机，不查找路由
+        if is_loopback_ip(element.destination_ip) || element.destination_ip==local_ip(){
+            let mut element=element;
+            if element.source_ip.is_none(){
+                element.source_ip=Some(if is_loopback_ip(element.destination_ip) {LOOPBACK_IP} else {local_ip()});
             }
-        }
-        else{
-            //如果需要分片
-            //对于每一个分片
-            //首先取出数据
-            //然后计算头部并加载
-            //之后加载数据并把整个片添加到发送队列里
-            for i in 0..number_of_slice{
-                if i==number_of_slice-1 {//最后一个分片
-                    let mut buffer:Vec<u8>=Vec::new();
-                    let len_of_data=element.data.len()-i*DATA_SLICE_LENTH;
-                    let hdr:IpHeader=IpHeader::new (
-                        0x4f,
-                        0xfe,
-                        60+len_of_data as u16,
-                        2023,			
-                        (i* DATA_SLICE_LENTH / 8)as u16,//DF=0,MF=0
-                        64,
-                        element.protocol_type,
-                        LOCAL_IP,   
-                        DEST_IP,
-                        [0;40],
-                    );
-                    buffer.extend(u8_from_u16(&(hdr.into_u16_array())));
-                    buffer.extend(element.data[i*DATA_SLICE_LENTH..element.data.len()].to_vec());
-                    loop{
-                        let dest_mac=shared_arp_cache_table.lock().unwrap().find_mac_from_ip(DEST_IP);
-                        if dest_mac.is_some(){
-                            let mut sendqueue=shared_ethernet_v2_send_queue.lock().unwrap();
-                            sendqueue.add_data(dest_mac.unwrap(),0x0800,&buffer);
-                            break;
-                        }
-                        else {
-                            yield_now()
-                        }
+            let id=id_generator.next_id(element.destination_ip);
+            match build_packets(&element,id,LOOPBACK_MTU) {
+                Ok(packets)=>{
+                    for packet in packets{
+                        deliver_local(&packet,&shared_icmp_receive_queue);
                     }
+                    element.report(Ok(()));
                 }
-                else{
-                    let mut buffer:Vec<u8>=Vec::new();
-                    let len_of_data=DATA_SLICE_LENTH;
-                    let hdr:IpHeader=IpHeader::new (
-                        0x4f,
-                        0xfe,
-                        60+len_of_data as u16,
-                        2023,			
-                        1<<13 as u16/*MF*/ | (i* DATA_SLICE_LENTH/8)as u16,//MF=1,DF=0
-                        64,
-                        element.protocol_type,
-                        LOCAL_IP,   
-                        DEST_IP,
-                        [0;40],
-                    );
-                    buffer.extend(u8_from_u16(&(hdr.into_u16_array())));
-                    buffer.extend(element.data[i*DATA_SLICE_LENTH..(i+1)*DATA_SLICE_LENTH].to_vec());
-                    
-                    loop{
-                        let dest_mac=shared_arp_cache_table.lock().unwrap().find_mac_from_ip(DEST_IP);
-                        if dest_mac.is_some(){
-                            let mut sendqueue=shared_ethernet_v2_send_queue.lock().unwrap();
-                            sendqueue.add_data(dest_mac.unwrap(),0x0800,&buffer);
-                            break;
-                        }
-                        else {
-                            yield_now();
-                        }
-                    }
+                Err(err)=>{
+                    println!("发送到{}的数据报失败：{}",format_ip(element.destination_ip),err);
+                    element.report(Err(err));
                 }
-            }//end for
+            }
+            continue;
         }
+
+        //查找路由，确定下一跳
+        let next_hop=match ROUTING_TABLE.lock().unwrap().next_hop(element.destination_ip) {
+            Some(next_hop)=>next_hop,
+            None=>{
+                println!("没有到{}的路由，丢弃数据报",format_ip(element.destination_ip));
+                element.report(Err(IpSendError::NoRoute));
+                continue;
+            }
+        };
+
+        //同一数据报的所有分片使用同一标识
+        let id=id_generator.next_id(element.destination_ip);
+        let packets=match build_packets(&element,id,MTU) {
+            Ok(packets)=>packets,
+            Err(err)=>{
+                println!("发送到{}的数据报失败：{}",format_ip(element.destination_ip),err);
+                element.report(Err(err));
+                continue;
+            }
+        };
+        for packet in packets{
+            send_to_next_hop(next_hop,&packet,&shared_arp_cache_table,&shared_ethernet_v2_send_queue);
+        }
+        element.report(Ok(()));
     }
-}
\ No newline at end of file
+}
@@ -1,3 +1,4 @@
 pub mod ip;
 pub mod arp;
-pub mod icmp;
\ No newline at end of file
+pub mod icmp;
+pub mod rarp;
\ No newline at end of file
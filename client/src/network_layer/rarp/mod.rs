@@ -0,0 +1,2 @@
+pub mod send;
+pub mod receive;
\ No newline at end of file
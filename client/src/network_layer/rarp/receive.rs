@@ -0,0 +1,63 @@
+use std::collections::VecDeque;
+use std::sync::{Arc,Mutex};
+use lazy_static::*;
+
+use crate::tools::global_variables::*;
+
+lazy_static!{
+    ///静态变量--RARP的接收队列
+    pub static ref RARP_RECEIVE_QUEUE:Arc<Mutex<RarpReceiveQueue>> = Arc::new(Mutex::new(RarpReceiveQueue::new()));
+}
+
+///RARP的接收队列，帧格式与ARP相同。只在有RARP请求等待应答时接收，其余时候收到的帧被丢弃
+pub struct RarpReceiveQueue{
+    queue:VecDeque<[u8;28]>,
+    /// 是否有RARP请求在等待应答
+    pending:bool,
+}
+
+impl RarpReceiveQueue{
+    ///生成接收队列
+    pub fn new() -> Self{
+        RarpReceiveQueue{
+            queue:VecDeque::new(),
+            pending:false,
+        }
+    }
+    /// datalink向其中写入数据。没有请求在等待应答时丢弃
+    pub fn add_data(&mut self,buffer: [u8;28]) -> bool{
+        if !self.pending{
+            return false;
+        }
+        self.queue.push_back(buffer);
+        true
+    }
+    /// 获取队列数据
+    pub fn get_data(&mut self)-> Option<[u8;28]>{
+        self.queue.pop_front()
+    }
+    /// ### 功能
+    /// 开始或结束等待RARP应答。结束时清空队列中剩余的帧
+    pub fn set_pending(&mut self,pending:bool){
+        self.pending=pending;
+        if !pending{
+            self.queue.clear();
+        }
+    }
+}
+
+/// ### 功能
+/// 解析一个RARP帧，如果是发给本机的RARP应答，则取出分配给本机的IP地址
+/// ### 返回值
+/// Option，是发给本机的应答则返回IP地址
+pub fn parse_reply(rarp_frame:&[u8;28])->Option<[u8;4]>{
+    //op=4代表为RARP应答
+    if rarp_frame[6..8]!=[0x00,0x04]{
+        return None;
+    }
+    //目的mac地址应为本机
+    if rarp_frame[18..24]!=LOCAL_MAC{
+        return None;
+    }
+    Some(rarp_frame[24..28].try_into().unwrap())
+}
@@ -0,0 +1,71 @@
+use std::sync::{Arc,Mutex};
+use std::thread::{sleep,yield_now};
+use std::time::{Duration,Instant};
+
+use crate::data_link_layer::ethernet_v2::send::Eth2SendQueue;
+use crate::network_layer::arp::send::build_arp_frame;
+use crate::tools::address::format_ip;
+use crate::tools::global_variables::*;
+
+use super::receive::{parse_reply,RarpReceiveQueue};
+
+/// 每次RARP请求等待应答的时间
+const RARP_TIMEOUT:Duration=Duration::from_secs(2);
+/// RARP请求的最大发送次数
+const RARP_MAX_RETRY:usize=3;
+
+/// ### 功能
+/// 广播一个RARP请求，询问本机MAC对应的IP地址
+pub fn send_request(shared_ethernet_v2_send_queue:&Arc<Mutex<Eth2SendQueue>>){
+    //op=3代表为RARP请求。发送端与目的mac地址均为本机，ip地址均未知
+    let rarp_frame=build_arp_frame(3,LOCAL_MAC,[0;4],LOCAL_MAC,[0;4]);
+    //发送--写入到Ethernet-v2的发送队列里
+    shared_ethernet_v2_send_queue.lock().unwrap().add_data(BROADCAST_MAC,0x8035,&Vec::from(rarp_frame));
+}
+
+/// ### 功能
+/// 启动时通过RARP获取本机的IP地址。超时则重发，重发次数用尽后放弃
+/// ### 返回值
+/// Option，成功获取则返回IP地址（同时已设置为本机IP）
+pub fn request_local_ip(
+    shared_ethernet_v2_send_queue:Arc<Mutex<Eth2SendQueue>>,
+    shared_rarp_receive_queue:Arc<Mutex<RarpReceiveQueue>>
+)->Option<[u8;4]>{
+    //只在等待应答期间接收RARP帧
+    shared_rarp_receive_queue.lock().unwrap().set_pending(true);
+    let ip=wait_for_reply(&shared_ethernet_v2_send_queue,&shared_rarp_receive_queue);
+    shared_rarp_receive_queue.lock().unwrap().set_pending(false);
+    if ip.is_none(){
+        println!("RARP请求超时，使用默认IP地址：{}",format_ip(local_ip()));
+    }
+    ip
+}
+
+/// ### 功能
+/// 发送RARP请求并等待应答，超时则重发
+fn wait_for_reply(
+    shared_ethernet_v2_send_queue:&Arc<Mutex<Eth2SendQueue>>,
+    shared_rarp_receive_queue:&Arc<Mutex<RarpReceiveQueue>>
+)->Option<[u8;4]>{
+    for _ in 0..RARP_MAX_RETRY{
+        send_request(shared_ethernet_v2_send_queue);
+        let start=Instant::now();
+        while start.elapsed()<RARP_TIMEOUT{
+            let rarp_frame=shared_rarp_receive_queue.lock().unwrap().get_data();
+            match rarp_frame {
+                Some(rarp_frame)=>{
+                    if let Some(ip)=parse_reply(&rarp_frame){
+                        set_local_ip(ip);
+                        println!("通过RARP获取到本机IP地址：{}",format_ip(ip));
+                        return Some(ip);
+                    }
+                    yield_now();
+                }
+                None=>{
+                    sleep(Duration::from_millis(10));
+                }
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,53 @@
+//! 地址的解析与格式化工具
+
+/// ### 功能
+/// 将点分十进制字符串（如"10.10.10.3"）解析为IPv4地址
+/// ### 返回值
+/// Option，格式正确则返回地址
+pub fn parse_ip(s:&str)->Option<[u8;4]>{
+    let mut ip:[u8;4]=[0;4];
+    let mut count=0;
+    for part in s.trim().split('.'){
+        if count>=4{
+            return None;
+        }
+        ip[count]=part.parse::<u8>().ok()?;
+        count+=1;
+    }
+    if count!=4{
+        return None;
+    }
+    Some(ip)
+}
+
+/// ### 功能
+/// 将形如"14:5A:FC:15:1A:9D"或"14-5A-FC-15-1A-9D"的字符串解析为MAC地址
+/// ### 返回值
+/// Option，格式正确则返回地址
+pub fn parse_mac(s:&str)->Option<[u8;6]>{
+    let mut mac:[u8;6]=[0;6];
+    let mut count=0;
+    for part in s.trim().split([':','-']){
+        if count>=6 || part.is_empty() || part.len()>2{
+            return None;
+        }
+        mac[count]=u8::from_str_radix(part,16).ok()?;
+        count+=1;
+    }
+    if count!=6{
+        return None;
+    }
+    Some(mac)
+}
+
+/// ### 功能
+/// 将IPv4地址格式化为点分十进制
+pub fn format_ip(ip:[u8;4])->String{
+    format!("{}.{}.{}.{}",ip[0],ip[1],ip[2],ip[3])
+}
+
+/// ### 功能
+/// 将MAC地址格式化为冒号分隔的十六进制
+pub fn format_mac(mac:[u8;6])->String{
+    format!("{:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",mac[0],mac[1],mac[2],mac[3],mac[4],mac[5])
+}
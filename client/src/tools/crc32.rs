@@ -5,7 +5,7 @@ pub struct Crc32Table(
 impl Crc32Table{
     ///生成CRC32校验码辅助计算表
     pub fn new() -> Self{
-        let mut new_crc32_table:Crc32Table=Crc32Table(vec![0 as u32;256]);
+        let mut new_crc32_table:Crc32Table=Crc32Table(vec![0;256]);
         let mut crc:u32;
         for i in 0..256{
             crc=i;
@@ -25,12 +25,12 @@ impl Crc32Table{
 
 
 //根据crc32表计算crc32码
-pub fn calculate_crc32(buffer: &Vec<u8>,len: i32)-> u32{
+pub fn calculate_crc32(buffer: &[u8],len: i32)-> u32{
     let mut crc:u32=0xffff_ffff;
     let crc32_table=Crc32Table::new();
     for i in 0..len{
-        crc=(crc >> 8) ^ crc32_table.0[((crc & 0xFF) ^ buffer[i as usize]as u32) as usize] as u32;
+        crc=(crc >> 8) ^ crc32_table.0[((crc & 0xFF) ^ buffer[i as usize]as u32) as usize];
     }
-    crc = crc ^0xffff_ffff as u32;
+    crc ^= 0xffff_ffff;
     crc
 }
@@ -1,21 +1,59 @@
-/// 设置一些系统的常量
+//! 设置一些系统的常量
+use std::sync::Mutex;
+use lazy_static::*;
 
 /// 本机的MAC地址
 pub const LOCAL_MAC:[u8;6]=[ 0x14, 0x5A, 0xFC, 0x15, 0x1A, 0x8D ];
-/// 本机的IP地址
+/// 本机的IP地址（编译时的默认值，运行时请使用local_ip()）
 pub const LOCAL_IP:[u8;4]=[ 10, 10, 10, 4 ];
 /// 网关的IP地址
 pub const GATEWAY_IP:[u8;4]=[ 10, 10, 11, 1];
 /// 子网掩码
 pub const NETMASK:[u8;4]=[ 255, 255, 248, 0 ];
 /// DNS服务器的IP地址
+#[allow(dead_code)]
 pub const DNS_SERVER_IP:[u8;4]=[ 211, 137, 130, 3 ];
 /// DHCP服务器的IP地址
+#[allow(dead_code)]
 pub const DHCP_SERVER_IP:[u8;4]=[ 111, 20, 62, 57 ];
 /// 广播MAC地址，全1
 pub const BROADCAST_MAC:[u8;6] = [ 0xff, 0xff, 0xff, 0xff, 0xff, 0xff ];
+/// 静态ARP表项的配置文件（ethers格式）
+pub const ARP_STATIC_TABLE_PATH:&str="ethers";
+/// 普通主机的IP→MAC映射变化时的处理策略：log（只告警）、ignore（忽略变化）或pin（固定旧映射）
+pub const ARP_MONITOR_POLICY:&str="log";
+/// 网关的IP→MAC映射变化时的处理策略，取值同ARP_MONITOR_POLICY
+pub const ARP_MONITOR_GATEWAY_POLICY:&str="pin";
+/// 静态路由的配置文件
+pub const ROUTE_TABLE_PATH:&str="routes";
+/// 链路的MTU，超过的数据报需要分片
+pub const MTU:usize=1500;
+/// 启动时是否通过RARP获取本机的IP地址（否则使用LOCAL_IP）
+pub const USE_RARP:bool=false;
+/// 发送队列是否使用加权轮询，否则为严格优先级
+pub const WEIGHTED_ROUND_ROBIN:bool=false;
+/// 加权轮询时各优先级类别（网络控制、实时、保证转发、尽力而为）每轮最多发送的帧数
+pub const TRANSMIT_CLASS_WEIGHTS:[u32;4]=[8,4,2,1];
 
 
 
 ///要发送的ip
 pub const DEST_IP:[u8;4]=[ 10, 10, 10, 3 ];
+
+
+lazy_static!{
+    ///静态变量--本机当前使用的IP地址。启动时为LOCAL_IP，可以通过RARP获取
+    static ref HOST_IP:Mutex<[u8;4]>=Mutex::new(LOCAL_IP);
+}
+
+/// ### 功能
+/// 获取本机当前使用的IP地址
+pub fn local_ip()->[u8;4]{
+    *HOST_IP.lock().unwrap()
+}
+
+/// ### 功能
+/// 修改本机当前使用的IP地址
+pub fn set_local_ip(ip:[u8;4]){
+    *HOST_IP.lock().unwrap()=ip;
+}
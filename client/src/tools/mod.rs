@@ -1,2 +1,4 @@
 pub mod crc32;
-pub mod global_variables;
\ No newline at end of file
+pub mod global_variables;
+pub mod address;
+pub mod checksum;
\ No newline at end of file
@@ -0,0 +1,169 @@
+//! 从标准输入读取的管理命令
+use std::io::{self,BufRead};
+use std::process;
+use std::sync::atomic::{AtomicU16,Ordering};
+
+use crate::data_link_layer::interface::INTERFACE_TABLE;
+use crate::network_layer::filter::conntrack::CONNECTION_TABLE;
+use crate::network_layer::icmp::send::send_echo_request;
+use crate::network_layer::igmp::group::MULTICAST_GROUP_TABLE;
+use crate::network_layer::igmp::send::{request_membership,IGMP_MEMBERSHIP_QUEUE};
+use crate::network_layer::ip::option::{IpOption,TimestampFlag};
+use crate::network_layer::ip::pmtu::PATH_MTU_CACHE;
+use crate::network_layer::ip::protocol::{PROTOCOL_GRE,PROTOCOL_ICMP,PROTOCOL_IGMP,PROTOCOL_IPIP,PROTOCOL_REGISTRY,PROTOCOL_TCP,PROTOCOL_UDP};
+use crate::network_layer::ip::reassembly::REASSEMBLER;
+use crate::network_layer::ip::route::{parse_destination,ROUTING_TABLE};
+use crate::network_layer::ip::send::IP_SEND_QUEUE;
+use crate::network_layer::ip::validate::IP_RECEIVE_STATISTICS;
+use crate::tools::address::{format_ip,parse_ip};
+
+/// 命令的用法，无法识别命令时打印
+const USAGE:&str="可用的命令：
+  conntrack                  打印连接跟踪表
+  igmp                       打印已加入的组播组
+  join|leave <接口> <组播地址>  加入、离开组播组
+  ip                         打印IP接收与分片重组的统计
+  pmtu                       打印路径MTU缓存
+  protocol                   打印上层协议的注册情况
+  protocol del <协议名|协议号>  注销上层协议，之后收到的该协议的数据报回送协议不可达
+  route                      打印路由表
+  route add <目的网络/前缀长度|default> <网关> [度量值]  添加静态路由
+  route del <目的网络/前缀长度|default>  删除路由
+  route get <IP地址>          查找到某地址的下一跳与出接口
+  ping <IP地址> [rr|ts]       发送回送请求，rr带记录路由选项，ts带时间戳选项";
+
+/// 回送请求的选项最多能记录的地址或时间戳个数
+const OPTION_SLOTS:usize=9;
+
+/// 常见的上层协议：(名称，协议号)
+const PROTOCOLS:[(&str,u8);6]=[
+    ("icmp",PROTOCOL_ICMP),
+    ("igmp",PROTOCOL_IGMP),
+    ("ipip",PROTOCOL_IPIP),
+    ("tcp",PROTOCOL_TCP),
+    ("udp",PROTOCOL_UDP),
+    ("gre",PROTOCOL_GRE),
+];
+
+/// 回送请求的序号
+static PING_SEQUENCE_NUMBER:AtomicU16=AtomicU16::new(0);
+
+/// ### 功能
+/// 从标准输入逐行读取管理命令并执行，标准输入关闭时返回
+pub fn read_commands(){
+    for line in io::stdin().lock().lines(){
+        let line=match line {
+            Ok(line)=>line,
+            Err(_)=>return,
+        };
+        let fields:Vec<&str>=line.split_whitespace().collect();
+        if fields.is_empty(){
+            continue;
+        }
+        if !run_command(&fields){
+            println!("无法识别的命令：{}",line);
+            println!("{}",USAGE);
+        }
+    }
+}
+
+/// ### 功能
+/// 执行一条命令
+/// ### 返回值
+/// 命令是否合法
+fn run_command(fields:&[&str])->bool{
+    match fields {
+        ["conntrack"]=>{
+            CONNECTION_TABLE.lock().unwrap().print();
+        }
+        ["igmp"]=>{
+            MULTICAST_GROUP_TABLE.lock().unwrap().print();
+        }
+        ["join"|"leave",..]=>{
+            return request_membership(&IGMP_MEMBERSHIP_QUEUE,fields);
+        }
+        ["ip"]=>{
+            IP_RECEIVE_STATISTICS.lock().unwrap().print();
+            REASSEMBLER.lock().unwrap().print();
+        }
+        ["pmtu"]=>{
+            PATH_MTU_CACHE.lock().unwrap().print();
+        }
+        ["protocol"]=>{
+            let protocol_registry=PROTOCOL_REGISTRY.lock().unwrap();
+            for (name,protocol_type) in PROTOCOLS{
+                let state=if protocol_registry.is_registered(protocol_type) {"已注册"} else {"未注册"};
+                println!("{:<8}{:<6}{}",name,protocol_type,state);
+            }
+        }
+        ["protocol","del",protocol]=>{
+            let protocol_type=match parse_protocol(protocol) {
+                Some(protocol_type)=>protocol_type,
+                None=>return false,
+            };
+            if !PROTOCOL_REGISTRY.lock().unwrap().unregister(protocol_type){
+                println!("上层协议{}没有注册",protocol);
+            }
+        }
+        ["route"]=>{
+            ROUTING_TABLE.lock().unwrap().print();
+        }
+        ["route","add",route @ ..]=>{
+            match ROUTING_TABLE.lock().unwrap().add_route_from_fields(route) {
+                Some(true)=>{}
+                Some(false)=>println!("网关{}不可直连到达",route[1]),
+                None=>return false,
+            }
+        }
+        ["route","del",destination]=>{
+            let (destination,netmask)=match parse_destination(destination) {
+                Some(destination)=>destination,
+                None=>return false,
+            };
+            if !ROUTING_TABLE.lock().unwrap().delete_route(destination,netmask){
+                println!("没有到{}的路由",fields[2]);
+            }
+        }
+        ["route","get",ip]=>{
+            let ip=match parse_ip(ip) {
+                Some(ip)=>ip,
+                None=>return false,
+            };
+            let routing_table=ROUTING_TABLE.lock().unwrap();
+            match routing_table.lookup(ip).zip(routing_table.next_hop(ip)) {
+                Some((route,next_hop))=>{
+                    let source_ip=INTERFACE_TABLE.lock().unwrap().get(route.interface)
+                        .map(|interface|interface.select_source(ip,next_hop))
+                        .unwrap_or([0;4]);
+                    println!("{}经{}从接口{}发出，源地址{}",format_ip(ip),format_ip(next_hop),route.interface,format_ip(source_ip));
+                }
+                None=>println!("没有到{}的路由",format_ip(ip)),
+            }
+        }
+        ["ping",ip,option @ ..]=>{
+            let ip=match parse_ip(ip) {
+                Some(ip)=>ip,
+                None=>return false,
+            };
+            let options=match option {
+                []=>Vec::new(),
+                ["rr"]=>vec![IpOption::record_route(OPTION_SLOTS)],
+                ["ts"]=>vec![IpOption::timestamp(TimestampFlag::TimestampOnly,OPTION_SLOTS)],
+                _=>return false,
+            };
+            let sequence_number=PING_SEQUENCE_NUMBER.fetch_add(1,Ordering::Relaxed);
+            send_echo_request(&IP_SEND_QUEUE,ip,process::id() as u16,sequence_number,options);
+        }
+        _=>return false,
+    }
+    true
+}
+
+/// ### 功能
+/// 解析协议名（不区分大小写）或协议号
+fn parse_protocol(field:&str)->Option<u8>{
+    PROTOCOLS.iter()
+        .find(|(name,_)|name.eq_ignore_ascii_case(field))
+        .map(|(_,protocol_type)|*protocol_type)
+        .or_else(||field.parse().ok())
+}
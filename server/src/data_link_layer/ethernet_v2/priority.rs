@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+/// 发送优先级的类别数，0为最高
+pub const PRIORITY_CLASSES:usize=4;
+/// 各类别的名称，用于打印计数
+pub const CLASS_NAMES:[&str;PRIORITY_CLASSES]=["网络控制","实时","保证转发","尽力而为"];
+
+/// 发送队列的调度方式
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum Scheduling{
+    /// 严格优先级：总是先发送最高的非空类别
+    StrictPriority,
+    /// 加权轮询：每一轮中各类别最多发送权重个帧，低优先级的类别不会被饿死
+    WeightedRoundRobin([u32;PRIORITY_CLASSES]),
+}
+
+/// ### 功能
+/// 确定帧的优先级类别：
+/// - 非IP的帧（ARP、RARP）、ICMP与IGMP、以及DSCP为CS6、CS7的分组为网络控制
+/// - DSCP为CS4、CS5（含EF、AF4x）的分组为实时
+/// - DSCP为CS1至CS3（含AF1x至AF3x）的分组为保证转发
+/// - 其余（DSCP为0）为尽力而为
+pub fn classify(ethernet_type:u16,data:&[u8])->usize{
+    if ethernet_type!=0x0800 || data.len()<20{
+        return 0;
+    }
+    //ICMP、IGMP的每个分片都带有协议字段
+    if matches!(data[9],1|2){
+        return 0;
+    }
+    //DSCP的高3位为类选择器
+    match data[1]>>5 {
+        6|7=>0,
+        4|5=>1,
+        1..=3=>2,
+        _=>3,
+    }
+}
+
+/// 一个类别的计数
+#[derive(Clone,Copy,Default)]
+pub struct ClassStatistics{
+    /// 进入队列的帧数
+    pub enqueued_packets:u64,
+    /// 进入队列的字节数
+    pub enqueued_bytes:u64,
+    /// 已经取出发送的帧数
+    pub sent_packets:u64,
+    /// 已经取出发送的字节数
+    pub sent_bytes:u64,
+    /// 出现过的最大队列长度
+    pub max_depth:usize,
+    /// 帧在队列中等待的总时间
+    pub total_wait:Duration,
+    /// 帧在队列中等待的最长时间
+    pub max_wait:Duration,
+}
+
+impl ClassStatistics{
+    /// ### 功能
+    /// 平均等待时间
+    pub fn average_wait(&self)->Duration{
+        match self.sent_packets {
+            0=>Duration::ZERO,
+            packets=>self.total_wait/packets as u32,
+        }
+    }
+}
+
+/// ## 加权轮询的状态
+/// 当前轮到的类别，以及它在本轮剩余的帧数
+pub struct RoundRobin{
+    current:usize,
+    credit:u32,
+}
+
+impl RoundRobin{
+    /// ### 功能
+    /// 新建状态，第一轮从最高的类别开始
+    pub fn new()->RoundRobin{
+        RoundRobin{
+            current:PRIORITY_CLASSES-1,
+            credit:0,
+        }
+    }
+    /// ### 功能
+    /// 选择下一个发送的类别。权重为0的类别按1处理
+    /// ### 返回值
+    /// Option，所有类别都为空时为None
+    pub fn next_class(&mut self,weights:&[u32;PRIORITY_CLASSES],non_empty:&[bool;PRIORITY_CLASSES])->Option<usize>{
+        if !non_empty.contains(&true){
+            return None;
+        }
+        loop{
+            if self.credit>0 && non_empty[self.current]{
+                self.credit-=1;
+                return Some(self.current);
+            }
+            self.current=(self.current+1)%PRIORITY_CLASSES;
+            self.credit=weights[self.current].max(1);
+        }
+    }
+}
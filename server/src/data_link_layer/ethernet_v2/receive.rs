@@ -3,9 +3,29 @@ use std::sync::Mutex;
 use pcap::*;
 use crate::tools::crc32::*;
 use crate::network_layer::arp::receive::ArpReceiveQueue;
+use crate::network_layer::filter::rule::{FilterAction,FilterPacket,Hook};
+use crate::network_layer::filter::table::{reject,FilterTable};
+use crate::network_layer::igmp::group::MulticastGroupTable;
+use crate::network_layer::ip::send::IpSendQueue;
 use crate::network_layer::ip::receive::IpReceiveQueue;
+use crate::network_layer::rarp::receive::RarpReceiveQueue;
+use crate::data_link_layer::interface::Interface;
+use crate::data_link_layer::traffic_control::TrafficControl;
 use crate::tools::global_variables::*;
-pub fn receive(shared_arp_receive_queue:Arc<Mutex<ArpReceiveQueue>>,shared_ip_receive_queue:Arc<Mutex<IpReceiveQueue>>){
+
+/// ### 功能
+/// 在一个接口上接收帧，校验后按类型写入各协议的接收队列，并注明来自哪个接口
+#[allow(clippy::too_many_arguments)]
+pub fn receive(
+    interface:Interface,
+    shared_arp_receive_queue:Arc<Mutex<ArpReceiveQueue>>,
+    shared_ip_receive_queue:Arc<Mutex<IpReceiveQueue>>,
+    shared_rarp_receive_queue:Arc<Mutex<RarpReceiveQueue>>,
+    shared_multicast_group_table:Arc<Mutex<MulticastGroupTable>>,
+    shared_filter_table:Arc<Mutex<FilterTable>>,
+    shared_traffic_control:Arc<Mutex<TrafficControl>>,
+    shared_ip_send_queue:Arc<Mutex<IpSendQueue>>
+){
 
     //获取并打印所有网络适配器
     let devices=Device::list().unwrap();
@@ -34,7 +54,10 @@ pub fn receive(shared_arp_receive_queue:Arc<Mutex<ArpReceiveQueue>>,shared_ip_re
     // if used_device_number < 1 || used_device_number >devices.len(){
     //     panic!("不存在该设备！");
     // }
-    let used_device_number=1;
+    let used_device_number=interface.receive_device_number;
+    if used_device_number < 1 || used_device_number >devices.len(){
+        panic!("不存在该设备！");
+    }
     let mut cap=Capture::from_device(devices[used_device_number-1].clone()).unwrap()
         .immediate_mode(true)
         .open()
@@ -69,19 +92,51 @@ pub fn receive(shared_arp_receive_queue:Arc<Mutex<ArpReceiveQueue>>,shared_ip_re
 
         println!();
         //校验数据
-        if crc32_code==calculate_crc32(&packet.data[14..packet.header.caplen as usize-4].to_vec(), packet.header.caplen as i32-18 ){ 
+        if crc32_code==calculate_crc32(&packet.data[14..packet.header.caplen as usize-4], packet.header.caplen as i32-18 ){ 
             println!("CRC32校验通过！");
             //if packet.header.len-18>(46) && packet.header.caplen-18<1500{
             //    println!("数据长度检验通过！");
-                if (packet.data[0..6]==LOCAL_MAC) | (packet.data[0..6]==BROADCAST_MAC){
+                let destination_mac:[u8;6]=packet.data[0..6].try_into().unwrap();
+                //本接口的MAC、广播，或本接口加入的组播组对应的MAC
+                let accepted=destination_mac==interface.mac || destination_mac==BROADCAST_MAC
+                    || shared_multicast_group_table.lock().unwrap().accepts_mac(interface.index,destination_mac);
+                if accepted{
                     println!("MAC检验通过!");
 
+                    let ether_type=u16::from_be_bytes([packet.data[12],packet.data[13]]);
+                    let payload=&packet.data[14..packet.header.caplen as usize-4];
+
+                    //监管：按源地址限速，超过速率的帧在写入各协议的接收队列之前丢弃
+                    if !shared_traffic_control.lock().unwrap().ingress(interface.index,ether_type,payload){
+                        continue;
+                    }
+
+                    //过滤：ethernet挂载点
+                    let source_mac:[u8;6]=packet.data[6..12].try_into().unwrap();
+                    let ip_packet=if ether_type==0x0800 && payload.len()>=20 {Some(payload)} else {None};
+                    let action=shared_filter_table.lock().unwrap().check(Hook::Ethernet,&FilterPacket{
+                        in_interface:Some(interface.index),
+                        out_interface:None,
+                        frame_header:Some((source_mac,ether_type)),
+                        packet:ip_packet,
+                        state:None,
+                        length:packet.header.caplen as usize,
+                    });
+                    if action!=FilterAction::Accept{
+                        if let (FilterAction::Reject,Some(ip_packet))=(action,ip_packet){
+                            reject(&shared_ip_send_queue,ip_packet);
+                        }
+                        println!("帧被过滤规则{:?}",action);
+                        continue;
+                    }
+
                     //通过一系列校验之后，再写入到接收队列里
                     //写入队列
                     if packet.data[12]==0x08 && packet.data[13]==0x00{
                         //ipv4协议
                         shared_ip_receive_queue.lock().unwrap().add_data(
-                            &packet.data[14..packet.header.len as usize-4].to_vec()
+                            interface.index,
+                            &packet.data[14..packet.header.len as usize-4]
                         );
                     }
                     else if packet.data[12]==0x08 && packet.data[13]==0x06{
@@ -89,7 +144,13 @@ pub fn receive(shared_arp_receive_queue:Arc<Mutex<ArpReceiveQueue>>,shared_ip_re
                         //帧长一定为28
                         let mut data:[u8;28]=[0;28];
                         data.copy_from_slice(&packet.data[14..42]);
-                        shared_arp_receive_queue.lock().unwrap().add_data(data);
+                        shared_arp_receive_queue.lock().unwrap().add_data(interface.index,data);
+                    }
+                    else if packet.data[12]==0x80 && packet.data[13]==0x35{
+                        //rarp协议，帧格式与arp相同
+                        let mut data:[u8;28]=[0;28];
+                        data.copy_from_slice(&packet.data[14..42]);
+                        shared_rarp_receive_queue.lock().unwrap().add_data(interface.index,data);
                     }
 
                 }
@@ -102,7 +163,7 @@ pub fn receive(shared_arp_receive_queue:Arc<Mutex<ArpReceiveQueue>>,shared_ip_re
             //}
         }
         else{
-            println!("CRC32校验失败！应为{:#X}！",calculate_crc32(&packet.data[14..packet.header.caplen as usize-4].to_vec(), packet.header.caplen as i32-18 ));
+            println!("CRC32校验失败！应为{:#X}！",calculate_crc32(&packet.data[14..packet.header.caplen as usize-4], packet.header.caplen as i32-18 ));
         }  
         println!();
     }
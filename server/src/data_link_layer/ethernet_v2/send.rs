@@ -1,17 +1,23 @@
 use std::{sync::{Arc, Mutex}, thread::yield_now};
 use pcap::*;
 use std::collections::VecDeque;
+use std::time::{Duration,Instant};
 use lazy_static::*;
 
+use crate::data_link_layer::interface::InterfaceTable;
+use crate::data_link_layer::traffic_control::{ShapingVerdict,TrafficControl};
 use crate::tools::crc32::calculate_crc32;
-use crate::tools::global_variables::*;
-
+use crate::tools::global_variables::{TRANSMIT_CLASS_WEIGHTS,WEIGHTED_ROUND_ROBIN};
+use super::priority::*;
 
 lazy_static!{
     ///静态变量--ethernet_v2的发送队列
     pub static ref ETHERNET_V2_SEND_QUEUE:Arc<Mutex<Eth2SendQueue>> = Arc::new(Mutex::new(Eth2SendQueue::new()));
 }
 
+/// 发送队列清空后打印各类别计数的最小间隔
+const STATISTICS_INTERVAL:Duration=Duration::from_secs(5);
+
 
 ///帧头
 pub struct EthernetHeader{
@@ -24,56 +30,130 @@ pub struct EthernetHeader{
 }
 /// Ethernet v2的发送队列的元素
 pub struct Eth2QueueElement{
+    ///从哪个接口发出
+    interface       :usize,
     ///目的MAC地址
     dest_mac_addr   :[u8;6],
     ///类型
     ethernet_type   :u16, 
     ///数据
-    data:Vec<u8>
+    data:Vec<u8>,
+    ///进入发送队列的时间，用于统计等待时间
+    enqueued_at     :Instant,
+}
+/// ## Ethernet v2的发送队列
+/// 每个优先级类别一个先进先出的队列（类别见priority::classify），取出时按调度方式选择类别，
+/// 使ARP、ICMP等控制报文不会排在大量分片之后
+pub struct Eth2SendQueue{
+    queues:[VecDeque<Eth2QueueElement>;PRIORITY_CLASSES],
+    scheduling:Scheduling,
+    round_robin:RoundRobin,
+    statistics:[ClassStatistics;PRIORITY_CLASSES],
 }
-pub struct Eth2SendQueue(
-    VecDeque<Eth2QueueElement>
-);
 
 impl Eth2SendQueue{
-    ///生成发送队列
+    ///生成发送队列，调度方式由全局常量确定
     pub fn new() -> Self{
-        let new_send_queue=VecDeque::new();
-        Eth2SendQueue(new_send_queue)
+        let scheduling=if WEIGHTED_ROUND_ROBIN {
+            Scheduling::WeightedRoundRobin(TRANSMIT_CLASS_WEIGHTS)
+        }
+        else{
+            Scheduling::StrictPriority
+        };
+        Eth2SendQueue{
+            queues:Default::default(),
+            scheduling,
+            round_robin:RoundRobin::new(),
+            statistics:[ClassStatistics::default();PRIORITY_CLASSES],
+        }
     }
-    /// netwrok向其中写入数据。
+    /// netwrok向其中写入数据，指定从哪个接口发出。
     /// 注意分片的工作由network层负责。
     /// newwork层保证数据长度在46与1500之间，该函数中不再检查。
-    pub fn add_data(&mut self,dest_mac:[u8;6],ethernet_v2_type:u16,buffer: &Vec<u8>) -> bool{
+    pub fn add_data_to(&mut self,interface:usize,dest_mac:[u8;6],ethernet_v2_type:u16,buffer: &[u8]) -> bool{
         // if buffer.len()>1500 || buffer.len()<46{
         //     return false;
         // }
-        self.0.push_back(
+        let class=classify(ethernet_v2_type,buffer);
+        self.queues[class].push_back(
             Eth2QueueElement{
+                interface,
                 dest_mac_addr   :dest_mac,
                 ethernet_type   :ethernet_v2_type, 
-                data:buffer.clone()
+                data:buffer.to_vec(),
+                enqueued_at     :Instant::now(),
             }
         );
+        let statistics=&mut self.statistics[class];
+        statistics.enqueued_packets+=1;
+        statistics.enqueued_bytes+=buffer.len() as u64;
+        statistics.max_depth=statistics.max_depth.max(self.queues[class].len());
         true
     }
-    /// 获取队列数据
-    pub fn get_data(&mut self)-> Option<Eth2QueueElement>{
-        self.0.pop_front()
+    /// ### 功能
+    /// 按调度方式获取队列数据。admit检查选中的帧：Send时取出，Drop时丢弃后继续选择，
+    /// Delay时帧留在原来的队列中，本次不再从该帧的接口取帧（其他接口与其他类别照常调度）
+    /// ### 返回值
+    /// Option，没有可以立即发送的帧时为None
+    pub fn get_data(&mut self,mut admit:impl FnMut(&Eth2QueueElement)->ShapingVerdict)-> Option<Eth2QueueElement>{
+        //本次调用中需要等待的接口
+        let mut blocked:Vec<usize>=Vec::new();
+        loop{
+            //每个类别中第一个不属于等待接口的帧
+            let heads:[Option<usize>;PRIORITY_CLASSES]=std::array::from_fn(|class|{
+                self.queues[class].iter().position(|element|!blocked.contains(&element.interface))
+            });
+            let class=match self.scheduling {
+                Scheduling::StrictPriority=>heads.iter().position(|head|head.is_some())?,
+                Scheduling::WeightedRoundRobin(weights)=>{
+                    let non_empty=heads.map(|head|head.is_some());
+                    self.round_robin.next_class(&weights,&non_empty)?
+                }
+            };
+            let index=heads[class].unwrap();
+            match admit(&self.queues[class][index]) {
+                ShapingVerdict::Send=>{}
+                ShapingVerdict::Drop=>{
+                    self.queues[class].remove(index);
+                    continue;
+                }
+                ShapingVerdict::Delay(_)=>{
+                    blocked.push(self.queues[class][index].interface);
+                    continue;
+                }
+            }
+            let element=self.queues[class].remove(index)?;
+            let wait=element.enqueued_at.elapsed();
+            let statistics=&mut self.statistics[class];
+            statistics.sent_packets+=1;
+            statistics.sent_bytes+=element.data.len() as u64;
+            statistics.total_wait+=wait;
+            statistics.max_wait=statistics.max_wait.max(wait);
+            return Some(element);
+        }
     }
 
     /// 队列是否为空
     pub fn is_empty(&self)->bool{
-        self.0.is_empty()
+        self.queues.iter().all(|queue|queue.is_empty())
+    }
+    /// 打印各类别的计数
+    pub fn print_statistics(&self){
+        println!("发送队列（{:?}）：",self.scheduling);
+        for (name,statistics) in CLASS_NAMES.iter().zip(self.statistics.iter()){
+            println!("  {}：入队{}帧{}字节，发送{}帧{}字节，最大队长{}，平均等待{:?}，最长等待{:?}",
+                name,statistics.enqueued_packets,statistics.enqueued_bytes,statistics.sent_packets,statistics.sent_bytes,
+                statistics.max_depth,statistics.average_wait(),statistics.max_wait);
+        }
     }
 }
 
 
-///加载帧头
-pub fn load_ethernet_header( buffer: &mut Vec<u8>,element:&Eth2QueueElement){
+///加载帧头，源MAC地址为发出接口的MAC地址
+pub fn load_ethernet_header( buffer: &mut Vec<u8>,element:&Eth2QueueElement,src_mac:[u8;6]){
     let ethernet_header:EthernetHeader=EthernetHeader{
         dest_mac_addr:element.dest_mac_addr,
-        src_mac_addr:LOCAL_MAC,
+        src_mac_addr:src_mac,
         ethernet_type:element.ethernet_type,
     };
     buffer.extend_from_slice(&ethernet_header.dest_mac_addr);
@@ -89,20 +169,24 @@ pub fn load_ethernet_header( buffer: &mut Vec<u8>,element:&Eth2QueueElement){
 pub fn load_ethernet_data_from_network_layer(buffer: &mut Vec<u8>,element:&Eth2QueueElement) -> (bool,usize){
     let data=&element.data;
     //计算CRC32校验码
-    let crc32:u32=calculate_crc32(&data, data.len() as i32);
+    let crc32:u32=calculate_crc32(data, data.len() as i32);
 
     //拼接帧,注意要在帧头（14B）之后
-    buffer.extend_from_slice(&data);
+    buffer.extend_from_slice(data);
     for i in 14..data.len() as i32+14{
         buffer[i as usize]=data[i as usize-14];
     }
     buffer.extend_from_slice(&crc32.to_be_bytes());
 
     //返回值
-    (true,14+data.len() as usize+crc32.to_be_bytes().len())
+    (true,14+data.len()+crc32.to_be_bytes().len())
 }
 
-pub fn send(shared_ethernet_v2_send_queue:Arc<Mutex<Eth2SendQueue>>) {
+pub fn send(
+    shared_interface_table:Arc<Mutex<InterfaceTable>>,
+    shared_ethernet_v2_send_queue:Arc<Mutex<Eth2SendQueue>>,
+    shared_traffic_control:Arc<Mutex<TrafficControl>>
+) {
 
     //获取并打印所有网络适配器
     let devices=Device::list().unwrap();
@@ -121,55 +205,79 @@ pub fn send(shared_ethernet_v2_send_queue:Arc<Mutex<Eth2SendQueue>>) {
     //     };
     // }
 
-    // //由用户选择使用的网络适配器
-    // println!("选择发送端使用的网络适配器：1-{}",devices.len());
-    // io::stdout().flush().unwrap();//确保所有数据写入终端,此行可以删除
-    //     //读取用户输入
-    // let mut user_input = String::new();
-    // io::stdin().read_line(&mut user_input).unwrap();
-    // let used_device_number:usize=user_input.trim().parse().unwrap();
-    // if used_device_number < 1 || used_device_number >devices.len(){
-    //     panic!("不存在该设备！");
-    // }
-    let used_device_number=3;
-    //打开网络适配器
-    let mut cap=Capture::from_device(devices[used_device_number-1].clone()).unwrap()
-        .promisc(true)
-        .open()
-        .unwrap();
+    //每个物理接口打开一个网络适配器，适配器序号与MAC地址由接口表给出。
+    //隧道接口都在物理接口之后，适配器的下标仍与接口序号相同
+    let interfaces=shared_interface_table.lock().unwrap().interfaces().clone();
+    let mut caps=Vec::new();
+    for interface in interfaces.iter().filter(|interface|interface.tunnel.is_none()){
+        let used_device_number=interface.send_device_number;
+        if used_device_number < 1 || used_device_number >devices.len(){
+            panic!("不存在该设备！");
+        }
+        //打开网络适配器
+        let cap=Capture::from_device(devices[used_device_number-1].clone()).unwrap()
+            .promisc(true)
+            .open()
+            .unwrap();
 
-    println!();
-    // println!("您选择的网络适配器的datalink信息：");
-    // println!("  {},{}",cap.get_datalink().get_name().unwrap(),cap.get_datalink().get_description().unwrap());
-    if cap.get_datalink().get_description().unwrap() != "Ethernet"{
-        panic!("您选择的设备不支持以太网");
+        // println!("您选择的网络适配器的datalink信息：");
+        // println!("  {},{}",cap.get_datalink().get_name().unwrap(),cap.get_datalink().get_description().unwrap());
+        if cap.get_datalink().get_description().unwrap() != "Ethernet"{
+            panic!("您选择的设备不支持以太网");
+        }
+        caps.push(cap);
     }
+    println!();
     
+    //上一次打印计数的时间，以及之后是否发送过帧
+    let mut last_report=Instant::now();
+    let mut sent_since_report=false;
     //轮询发送队列，队列为空则直接continue
     loop{
-        if shared_ethernet_v2_send_queue.lock().unwrap().is_empty(){
-            yield_now();
+        //整形器超过速率的帧留在发送队列中，稍后再检查
+        let element={
+            let mut traffic_control=shared_traffic_control.lock().unwrap();
+            shared_ethernet_v2_send_queue.lock().unwrap().get_data(|element|{
+                traffic_control.egress(element.interface,element.ethernet_type,&element.data)
+            })
+        };
+        let element=match element {
+            Some(element)=>element,
+            None=>{
+                //队列清空时打印各类别的计数
+                if sent_since_report && last_report.elapsed()>=STATISTICS_INTERVAL
+                    && shared_ethernet_v2_send_queue.lock().unwrap().is_empty(){
+                    shared_ethernet_v2_send_queue.lock().unwrap().print_statistics();
+                    shared_traffic_control.lock().unwrap().print();
+                    last_report=Instant::now();
+                    sent_since_report=false;
+                }
+                yield_now();
+                continue;
+            }
+        };
+        sent_since_report=true;
+        if element.interface>=caps.len(){
+            println!("不存在接口{}，丢弃该帧",element.interface);
+            continue;
         }
-        else {
-            let element=shared_ethernet_v2_send_queue.lock().unwrap().get_data().unwrap();
 
-            //本次发送中的数据帧
-            let mut buffer:Vec<u8>=Vec::new();
+        //本次发送中的数据帧
+        let mut buffer:Vec<u8>=Vec::new();
 
-            //加载帧头
-            load_ethernet_header(&mut buffer,&element);
-        
-            //从网络层加载数据;
-            let(load_success,size_of_frame)=load_ethernet_data_from_network_layer(&mut buffer,&element);
-            if load_success{
-                println!("封装为帧成功，帧长: {} ,数据长度: {} ",size_of_frame,size_of_frame-18);
-            }
-            else{
-                continue;
-            }
-            
-            //发送数据
-            cap.sendpacket(buffer.clone()).unwrap();
+        //加载帧头
+        load_ethernet_header(&mut buffer,&element,interfaces[element.interface].mac);
+    
+        //从网络层加载数据;
+        let(load_success,size_of_frame)=load_ethernet_data_from_network_layer(&mut buffer,&element);
+        if load_success{
+            println!("封装为帧成功，帧长: {} ,数据长度: {} ",size_of_frame,size_of_frame-18);
         }
+        else{
+            continue;
+        }
+        
+        //发送数据
+        caps[element.interface].sendpacket(buffer.clone()).unwrap();
     }
 }
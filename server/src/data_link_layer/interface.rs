@@ -0,0 +1,271 @@
+use lazy_static::*;
+use std::fs::File;
+use std::io::{BufRead,BufReader};
+use std::sync::{Arc,Mutex};
+
+use crate::network_layer::ip::tunnel::{parse_tunnel,Tunnel};
+use crate::tools::address::*;
+use crate::tools::global_variables::*;
+
+lazy_static!{
+    //静态变量--网络接口表
+    pub static ref INTERFACE_TABLE:Arc<Mutex<InterfaceTable>> = Arc::new(Mutex::new(InterfaceTable::new()));
+}
+
+/// 一个网络接口
+#[derive(Clone)]
+pub struct Interface{
+    /// 接口序号，即在接口表中的下标
+    pub index:usize,
+    /// 发送使用的网络适配器序号（从1开始）
+    pub send_device_number:usize,
+    /// 接收使用的网络适配器序号（从1开始）
+    pub receive_device_number:usize,
+    /// 接口的MAC地址
+    pub mac:[u8;6],
+    /// 接口的主IP地址
+    pub ip:[u8;4],
+    /// 主地址的子网掩码
+    pub netmask:[u8;4],
+    /// 从地址及其子网掩码
+    pub secondary_addresses:Vec<([u8;4],[u8;4])>,
+    /// 最大传输单元
+    pub mtu:usize,
+    /// 隧道接口的参数，物理接口为None。隧道接口没有网络适配器，分组在IP层封装后从物理接口发出
+    pub tunnel:Option<Tunnel>,
+}
+
+impl Interface{
+    /// ### 功能
+    /// 接口的所有(地址，掩码)，主地址在前
+    pub fn addresses(&self)->impl Iterator<Item=([u8;4],[u8;4])>+'_{
+        std::iter::once((self.ip,self.netmask)).chain(self.secondary_addresses.iter().copied())
+    }
+    /// ### 功能
+    /// IP地址是否是接口的主地址或从地址
+    pub fn has_ip(&self,ip:[u8;4])->bool{
+        self.addresses().any(|(address,_)|address==ip)
+    }
+    /// ### 功能
+    /// 为从该接口发往destination、经由next_hop的分组选择源地址：
+    /// 优先选择与目的地址在同一子网的地址，其次是与下一跳在同一子网的地址，都没有时使用主地址
+    pub fn select_source(&self,destination:[u8;4],next_hop:[u8;4])->[u8;4]{
+        self.addresses().find(|(address,netmask)|is_same_subnet(destination,*address,*netmask))
+            .or_else(||self.addresses().find(|(address,netmask)|is_same_subnet(next_hop,*address,*netmask)))
+            .map_or(self.ip,|(address,_)|address)
+    }
+}
+
+/// ## 网络接口表
+/// 默认只有一个接口（发送用适配器3，接收用适配器1，地址为LOCAL_MAC、LOCAL_IP）。
+/// 路由器模式下可以从配置文件加载多个接口
+pub struct InterfaceTable{
+    inner:Vec<Interface>
+}
+
+impl InterfaceTable{
+    /// ### 功能
+    /// 新建接口表，包含由全局常量确定的默认接口
+    pub fn new()->InterfaceTable{
+        InterfaceTable{
+            inner:vec![Interface{
+                index:0,
+                send_device_number:3,
+                receive_device_number:1,
+                mac:LOCAL_MAC,
+                ip:LOCAL_IP,
+                netmask:NETMASK,
+                secondary_addresses:Vec::new(),
+                mtu:DEFAULT_MTU,
+                tunnel:None,
+            }]
+        }
+    }
+    /// ### 功能
+    /// 从文件加载接口，加载成功则替换默认接口。
+    /// 每行一个接口，格式为"适配器序号 MAC地址 IP地址/前缀长度 [从地址/前缀长度]... [MTU]"，以#开头的内容为注释。
+    /// 收发使用不同适配器时，适配器序号写作"发送序号,接收序号"
+    /// ### 返回值
+    /// 成功加载的接口数
+    pub fn load(&mut self,file_path:&str)->usize{
+        let file=match File::open(file_path) {
+            Ok(file)=>file,
+            Err(_)=>{
+                //没有配置文件时使用默认接口
+                return 0;
+            }
+        };
+        let mut interfaces:Vec<Interface>=Vec::new();
+        for (line_number,line) in BufReader::new(file).lines().enumerate(){
+            let line=match line {
+                Ok(line)=>line,
+                Err(_)=>break,
+            };
+            let line=line.split('#').next().unwrap_or("");
+            let fields:Vec<&str>=line.split_whitespace().collect();
+            if fields.is_empty(){
+                continue;
+            }
+            match parse_interface(&fields) {
+                Some(((send_device_number,receive_device_number),mac,mut addresses,mtu))=>{
+                    let (ip,netmask)=addresses.remove(0);
+                    interfaces.push(Interface{
+                        index:interfaces.len(),
+                        send_device_number,
+                        receive_device_number,
+                        mac,
+                        ip,
+                        netmask,
+                        secondary_addresses:addresses,
+                        mtu,
+                        tunnel:None,
+                    });
+                }
+                None=>{
+                    println!("网络接口格式错误！{file_path}:{}",line_number+1);
+                }
+            }
+        }
+        if !interfaces.is_empty(){
+            self.inner=interfaces;
+        }
+        self.inner.len()
+    }
+    /// ### 功能
+    /// 从文件加载隧道接口，追加在物理接口之后。每行一个隧道，格式见parse_tunnel，以#开头的内容为注释。
+    /// 本端地址不是本机物理接口的地址时忽略该隧道
+    /// ### 返回值
+    /// 成功加载的隧道数
+    pub fn load_tunnels(&mut self,file_path:&str)->usize{
+        let file=match File::open(file_path) {
+            Ok(file)=>file,
+            Err(_)=>{
+                //没有配置文件时不使用隧道
+                return 0;
+            }
+        };
+        let mut count=0;
+        for (line_number,line) in BufReader::new(file).lines().enumerate(){
+            let line=match line {
+                Ok(line)=>line,
+                Err(_)=>break,
+            };
+            let line=line.split('#').next().unwrap_or("");
+            let fields:Vec<&str>=line.split_whitespace().collect();
+            if fields.is_empty(){
+                continue;
+            }
+            match parse_tunnel(&fields) {
+                Some((tunnel,ip,netmask,mtu)) if self.physical_interfaces().any(|interface|interface.has_ip(tunnel.local_ip))=>{
+                    self.inner.push(Interface{
+                        index:self.inner.len(),
+                        send_device_number:0,
+                        receive_device_number:0,
+                        mac:[0;6],
+                        ip,
+                        netmask,
+                        secondary_addresses:Vec::new(),
+                        mtu,
+                        tunnel:Some(tunnel),
+                    });
+                    count+=1;
+                }
+                _=>{
+                    println!("隧道格式错误！{file_path}:{}",line_number+1);
+                }
+            }
+        }
+        count
+    }
+    /// ### 功能
+    /// 获取某个接口
+    pub fn get(&self,index:usize)->Option<&Interface>{
+        self.inner.get(index)
+    }
+    /// ### 功能
+    /// 所有接口
+    pub fn interfaces(&self)->&Vec<Interface>{
+        &self.inner
+    }
+    /// ### 功能
+    /// 所有物理接口，即有网络适配器、收发以太网帧的接口
+    pub fn physical_interfaces(&self)->impl Iterator<Item=&Interface>{
+        self.inner.iter().filter(|interface|interface.tunnel.is_none())
+    }
+    /// ### 功能
+    /// IP地址是否属于本机的某个接口
+    pub fn is_local_ip(&self,ip:[u8;4])->bool{
+        self.inner.iter().any(|interface|interface.has_ip(ip))
+    }
+    /// ### 功能
+    /// IP地址是否为受限广播，或本机某个接口所在子网的广播地址
+    pub fn is_broadcast_ip(&self,ip:[u8;4])->bool{
+        if ip==[255;4]{
+            return true;
+        }
+        self.inner.iter().flat_map(|interface|interface.addresses()).any(|(address,netmask)|{
+            let broadcast=u32::from_be_bytes(address) | !u32::from_be_bytes(netmask);
+            u32::from_be_bytes(ip)==broadcast
+        })
+    }
+    /// ### 功能
+    /// 打印接口表
+    pub fn print(&self){
+        for interface in &self.inner{
+            if let Some(tunnel)=&interface.tunnel{
+                println!("接口{}：隧道{} {:?} {}→{} {}/{} MTU {}",
+                    interface.index,tunnel.name,tunnel.kind,format_ip(tunnel.local_ip),format_ip(tunnel.remote_ip),
+                    format_ip(interface.ip),u32::from_be_bytes(interface.netmask).count_ones(),interface.mtu);
+                continue;
+            }
+            println!("接口{}：适配器{},{} {} {}/{} MTU {}",
+                interface.index,interface.send_device_number,interface.receive_device_number,format_mac(interface.mac),
+                format_ip(interface.ip),u32::from_be_bytes(interface.netmask).count_ones(),interface.mtu);
+            for (address,netmask) in &interface.secondary_addresses{
+                println!("  从地址 {}/{}",format_ip(*address),u32::from_be_bytes(*netmask).count_ones());
+            }
+        }
+    }
+}
+
+/// 一行接口配置：((发送适配器序号，接收序号)，MAC，(地址，掩码)的列表（主地址在前），MTU)
+type InterfaceConfig=((usize,usize),[u8;6],Vec<([u8;4],[u8;4])>,usize);
+
+/// ### 功能
+/// 解析一行接口配置
+/// ### 返回值
+/// Option，格式错误时返回None
+fn parse_interface(fields:&[&str])->Option<InterfaceConfig>{
+    if fields.len()<3{
+        return None;
+    }
+    let device_numbers=match fields[0].split_once(',') {
+        Some((send,receive))=>(send.parse().ok()?,receive.parse().ok()?),
+        None=>{
+            let number:usize=fields[0].parse().ok()?;
+            (number,number)
+        }
+    };
+    if device_numbers.0<1 || device_numbers.1<1{
+        return None;
+    }
+    let mac=parse_mac(fields[1])?;
+    let mut addresses=Vec::new();
+    let mut mtu=None;
+    for field in &fields[2..]{
+        match field.split_once('/') {
+            //MTU只能出现一次，且在所有地址之后
+            Some(_) if mtu.is_some()=>return None,
+            Some((ip,prefix_len))=>{
+                let prefix_len:u32=prefix_len.parse().ok().filter(|len|*len<=32)?;
+                addresses.push((parse_ip(ip)?,netmask_from_prefix_len(prefix_len)));
+            }
+            None if mtu.is_none() && !addresses.is_empty()=>mtu=Some(field.parse().ok().filter(|mtu|*mtu>=68)?),
+            None=>return None,
+        }
+    }
+    if addresses.is_empty(){
+        return None;
+    }
+    Some((device_numbers,mac,addresses,mtu.unwrap_or(DEFAULT_MTU)))
+}
@@ -1 +1,3 @@
-pub mod ethernet_v2;
\ No newline at end of file
+pub mod ethernet_v2;
+pub mod interface;
+pub mod traffic_control;
@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead,BufReader};
+use std::sync::{Arc,Mutex};
+use std::time::{Duration,Instant};
+use lazy_static::*;
+
+use crate::tools::address::*;
+
+lazy_static!{
+    ///静态变量--流量控制表：发送时的整形器与接收时的监管器
+    pub static ref TRAFFIC_CONTROL:Arc<Mutex<TrafficControl>> = Arc::new(Mutex::new(TrafficControl::new()));
+}
+
+/// 一个监管器最多跟踪的源地址数，超过时淘汰最久没有收到帧的源
+const MAX_POLICED_SOURCES:usize=1024;
+
+/// ## 令牌桶
+/// 令牌以rate字节每秒的速度增加，最多积累burst字节。
+/// 长于burst的帧在桶满时也可以通过，之后令牌为负，需要等待补足
+pub struct TokenBucket{
+    /// 速率，字节每秒
+    rate:f64,
+    /// 桶的容量，字节
+    burst:f64,
+    tokens:f64,
+    last_update:Instant,
+}
+
+impl TokenBucket{
+    /// ### 功能
+    /// 新建装满令牌的桶
+    pub fn new(rate:f64,burst:f64)->TokenBucket{
+        TokenBucket{
+            rate,
+            burst,
+            tokens:burst,
+            last_update:Instant::now(),
+        }
+    }
+    /// ### 功能
+    /// 按经过的时间补充令牌
+    fn refill(&mut self,now:Instant){
+        let elapsed=now.duration_since(self.last_update).as_secs_f64();
+        self.tokens=(self.tokens+elapsed*self.rate).min(self.burst);
+        self.last_update=now;
+    }
+    /// ### 功能
+    /// 长度为length的帧是否符合速率
+    pub fn conforms(&mut self,length:usize,now:Instant)->bool{
+        self.refill(now);
+        self.tokens>=(length as f64).min(self.burst)
+    }
+    /// ### 功能
+    /// 为通过的帧扣除令牌
+    pub fn consume(&mut self,length:usize){
+        self.tokens-=length as f64;
+    }
+    /// ### 功能
+    /// 还需要等待多久长度为length的帧才符合速率
+    pub fn wait_time(&self,length:usize)->Duration{
+        let needed=(length as f64).min(self.burst)-self.tokens;
+        if needed<=0.0{
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(needed/self.rate)
+    }
+}
+
+/// 超过速率时的动作
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum ExceedAction{
+    /// 暂缓发送，等待令牌补足（只用于整形器）
+    Delay,
+    /// 丢弃
+    Drop,
+    /// 只计数，照常通过
+    Log,
+}
+
+impl ExceedAction{
+    /// ### 功能
+    /// 由配置文件中的名称解析动作
+    pub fn from_name(name:&str)->Option<ExceedAction>{
+        match name {
+            "delay"=>Some(ExceedAction::Delay),
+            "drop"=>Some(ExceedAction::Drop),
+            "log"=>Some(ExceedAction::Log),
+            _=>None,
+        }
+    }
+}
+
+/// 规则的计数
+#[derive(Clone,Copy,Default)]
+pub struct TrafficStatistics{
+    /// 符合速率而通过的帧数
+    pub conformed_packets:u64,
+    pub conformed_bytes:u64,
+    /// 超过速率的次数（整形器的同一帧可能多次超过）
+    pub exceeded:u64,
+    /// 被丢弃的帧数
+    pub dropped_packets:u64,
+}
+
+/// ## 发送方向的整形器
+/// 匹配出接口，以及可选的目的网络；匹配的帧共用一个令牌桶
+pub struct Shaper{
+    pub interface:usize,
+    /// (网络，掩码)，为None时匹配该接口上的所有帧
+    pub destination:Option<([u8;4],[u8;4])>,
+    pub action:ExceedAction,
+    bucket:TokenBucket,
+    pub statistics:TrafficStatistics,
+    rate:u64,
+    burst:u64,
+}
+
+impl Shaper{
+    fn matches(&self,interface:usize,ip:Option<[u8;4]>)->bool{
+        if self.interface!=interface{
+            return false;
+        }
+        match (self.destination,ip) {
+            (None,_)=>true,
+            (Some((network,netmask)),Some(ip))=>is_same_subnet(ip,network,netmask),
+            (Some(_),None)=>false,
+        }
+    }
+}
+
+/// ## 接收方向的监管器
+/// 匹配源网络，以及可选的入接口；网络内的每个源地址各有一个令牌桶
+pub struct Policer{
+    pub interface:Option<usize>,
+    /// (网络，掩码)
+    pub source:([u8;4],[u8;4]),
+    pub action:ExceedAction,
+    buckets:HashMap<[u8;4],TokenBucket>,
+    pub statistics:TrafficStatistics,
+    rate:u64,
+    burst:u64,
+}
+
+/// 整形器检查的结果
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum ShapingVerdict{
+    /// 立即发送
+    Send,
+    /// 丢弃
+    Drop,
+    /// 暂缓，至少等待给定的时间后再检查
+    Delay(Duration),
+}
+
+/// ## 流量控制表
+/// 发送时整形、接收时监管，用于模拟低速链路与抵御洪泛
+pub struct TrafficControl{
+    shapers:Vec<Shaper>,
+    policers:Vec<Policer>,
+}
+
+impl TrafficControl{
+    /// ### 功能
+    /// 新建没有任何规则的表
+    pub fn new()->TrafficControl{
+        TrafficControl{
+            shapers:Vec::new(),
+            policers:Vec::new(),
+        }
+    }
+    /// ### 功能
+    /// 从文件加载规则，以#开头的内容为注释。每行一条规则，格式为
+    /// - "shape 出接口序号 [dst 网络/前缀长度] 速率 突发 delay|drop|log"
+    /// - "police [in 入接口序号] src 网络/前缀长度 速率 突发 drop|log"
+    ///
+    /// 速率的单位为比特每秒，可以带k、m后缀；突发的单位为字节，可以带k后缀
+    /// ### 返回值
+    /// 成功加载的规则数
+    pub fn load(&mut self,file_path:&str)->usize{
+        let file=match File::open(file_path) {
+            Ok(file)=>file,
+            Err(_)=>{
+                //没有配置文件时不限速
+                return 0;
+            }
+        };
+        let mut count=0;
+        for (line_number,line) in BufReader::new(file).lines().enumerate(){
+            let line=match line {
+                Ok(line)=>line,
+                Err(_)=>break,
+            };
+            let line=line.split('#').next().unwrap_or("");
+            let fields:Vec<&str>=line.split_whitespace().collect();
+            if fields.is_empty(){
+                continue;
+            }
+            let loaded=match fields[0] {
+                "shape"=>parse_shaper(&fields[1..]).map(|shaper|self.shapers.push(shaper)),
+                "police"=>parse_policer(&fields[1..]).map(|policer|self.policers.push(policer)),
+                _=>None,
+            };
+            match loaded {
+                Some(())=>count+=1,
+                None=>println!("流量控制规则格式错误！{file_path}:{}",line_number+1),
+            }
+        }
+        count
+    }
+    /// ### 功能
+    /// 发送前检查帧：所有匹配的整形器都符合速率时才发送并扣除令牌。
+    /// 有动作为drop的整形器超过速率时丢弃，否则有delay的整形器超过速率时暂缓
+    pub fn egress(&mut self,interface:usize,ethernet_type:u16,data:&[u8])->ShapingVerdict{
+        let destination_ip=if ethernet_type==0x0800 && data.len()>=20 {
+            Some(data[16..20].try_into().unwrap())
+        }
+        else{
+            None
+        };
+        let now=Instant::now();
+        let mut delay:Option<Duration>=None;
+        for shaper in self.shapers.iter_mut().filter(|shaper|shaper.matches(interface,destination_ip)){
+            if shaper.bucket.conforms(data.len(),now){
+                continue;
+            }
+            shaper.statistics.exceeded+=1;
+            match shaper.action {
+                ExceedAction::Drop=>{
+                    shaper.statistics.dropped_packets+=1;
+                    return ShapingVerdict::Drop;
+                }
+                ExceedAction::Delay=>{
+                    let wait=shaper.bucket.wait_time(data.len());
+                    delay=Some(delay.map_or(wait,|delay|delay.max(wait)));
+                }
+                ExceedAction::Log=>{}
+            }
+        }
+        if let Some(delay)=delay{
+            return ShapingVerdict::Delay(delay);
+        }
+        for shaper in self.shapers.iter_mut().filter(|shaper|shaper.matches(interface,destination_ip)){
+            shaper.bucket.consume(data.len());
+            shaper.statistics.conformed_packets+=1;
+            shaper.statistics.conformed_bytes+=data.len() as u64;
+        }
+        ShapingVerdict::Send
+    }
+    /// ### 功能
+    /// 接收时检查帧，源地址为IP分组的源地址或ARP、RARP报文的发送方IP地址，其他帧不受监管
+    /// ### 返回值
+    /// 是否接收，超过速率且动作为drop时丢弃
+    pub fn ingress(&mut self,interface:usize,ethernet_type:u16,data:&[u8])->bool{
+        let source_ip:[u8;4]=match ethernet_type {
+            0x0800 if data.len()>=20=>data[12..16].try_into().unwrap(),
+            0x0806|0x8035 if data.len()>=28=>data[14..18].try_into().unwrap(),
+            _=>return true,
+        };
+        let now=Instant::now();
+        for policer in self.policers.iter_mut(){
+            if policer.interface.is_some_and(|policed|policed!=interface) || !is_same_subnet(source_ip,policer.source.0,policer.source.1){
+                continue;
+            }
+            if !policer.buckets.contains_key(&source_ip) && policer.buckets.len()>=MAX_POLICED_SOURCES{
+                let oldest=policer.buckets.iter().min_by_key(|(_,bucket)|bucket.last_update).map(|(ip,_)|*ip);
+                if let Some(oldest)=oldest{
+                    policer.buckets.remove(&oldest);
+                }
+            }
+            let (rate,burst)=(policer.rate,policer.burst);
+            let bucket=policer.buckets.entry(source_ip).or_insert_with(||TokenBucket::new(rate as f64/8.0,burst as f64));
+            if bucket.conforms(data.len(),now){
+                bucket.consume(data.len());
+                policer.statistics.conformed_packets+=1;
+                policer.statistics.conformed_bytes+=data.len() as u64;
+                continue;
+            }
+            policer.statistics.exceeded+=1;
+            if policer.action==ExceedAction::Drop{
+                policer.statistics.dropped_packets+=1;
+                println!("来自{}的帧超过监管速率，丢弃",format_ip(source_ip));
+                return false;
+            }
+        }
+        true
+    }
+    /// ### 功能
+    /// 打印规则与计数
+    pub fn print(&self){
+        for shaper in &self.shapers{
+            let destination=match shaper.destination {
+                Some((network,netmask))=>format!(" dst {}/{}",format_ip(network),u32::from_be_bytes(netmask).count_ones()),
+                None=>String::new(),
+            };
+            println!("整形 接口{}{} {}bit/s 突发{}字节 {:?}：{}",
+                shaper.interface,destination,shaper.rate,shaper.burst,shaper.action,describe(&shaper.statistics));
+        }
+        for policer in &self.policers{
+            let interface=match policer.interface {
+                Some(interface)=>format!(" 接口{interface}"),
+                None=>String::new(),
+            };
+            println!("监管{} src {}/{} {}bit/s 突发{}字节 {:?}：{}个源，{}",
+                interface,format_ip(policer.source.0),u32::from_be_bytes(policer.source.1).count_ones(),
+                policer.rate,policer.burst,policer.action,policer.buckets.len(),describe(&policer.statistics));
+        }
+    }
+}
+
+fn describe(statistics:&TrafficStatistics)->String{
+    format!("通过{}帧{}字节，超速{}次，丢弃{}帧",
+        statistics.conformed_packets,statistics.conformed_bytes,statistics.exceeded,statistics.dropped_packets)
+}
+
+/// ### 功能
+/// 解析"出接口序号 [dst 网络/前缀长度] 速率 突发 动作"
+fn parse_shaper(fields:&[&str])->Option<Shaper>{
+    let (destination,rest)=match fields {
+        [_,"dst",prefix,rest @ ..]=>(Some(parse_prefix(prefix)?),rest),
+        [_,rest @ ..]=>(None,rest),
+        _=>return None,
+    };
+    let [rate,burst,action]=rest else {
+        return None;
+    };
+    let (rate,burst)=(parse_rate(rate)?,parse_size(burst)?);
+    Some(Shaper{
+        interface:fields[0].parse().ok()?,
+        destination,
+        action:ExceedAction::from_name(action)?,
+        bucket:TokenBucket::new(rate as f64/8.0,burst as f64),
+        statistics:TrafficStatistics::default(),
+        rate,
+        burst,
+    })
+}
+
+/// ### 功能
+/// 解析"[in 入接口序号] src 网络/前缀长度 速率 突发 动作"，监管器不能暂缓
+fn parse_policer(fields:&[&str])->Option<Policer>{
+    let (interface,rest)=match fields {
+        ["in",interface,rest @ ..]=>(Some(interface.parse().ok()?),rest),
+        rest=>(None,rest),
+    };
+    let ["src",source,rate,burst,action]=rest else {
+        return None;
+    };
+    let action=ExceedAction::from_name(action).filter(|action|*action!=ExceedAction::Delay)?;
+    Some(Policer{
+        interface,
+        source:parse_prefix(source)?,
+        action,
+        buckets:HashMap::new(),
+        statistics:TrafficStatistics::default(),
+        rate:parse_rate(rate)?,
+        burst:parse_size(burst)?,
+    })
+}
+
+/// ### 功能
+/// 解析速率，单位为比特每秒，可以带k、m后缀（1000进制）
+fn parse_rate(s:&str)->Option<u64>{
+    let (number,unit)=match s.to_ascii_lowercase().strip_suffix('m') {
+        Some(number)=>(number.to_string(),1_000_000),
+        None=>match s.to_ascii_lowercase().strip_suffix('k') {
+            Some(number)=>(number.to_string(),1_000),
+            None=>(s.to_string(),1),
+        },
+    };
+    number.parse::<u64>().ok()?.checked_mul(unit).filter(|rate|*rate>0)
+}
+
+/// ### 功能
+/// 解析突发长度，单位为字节，可以带k后缀（1024进制）
+fn parse_size(s:&str)->Option<u64>{
+    let (number,unit)=match s.to_ascii_lowercase().strip_suffix('k') {
+        Some(number)=>(number.to_string(),1024),
+        None=>(s.to_string(),1),
+    };
+    number.parse::<u64>().ok()?.checked_mul(unit).filter(|size|*size>0)
+}
@@ -1,43 +1,177 @@
+mod console;
 mod data_link_layer;
 mod network_layer;
 mod tools;
 
+use std::fs::File;
+use std::io::Write;
 use std::sync::Arc;
 use std::thread;
 
+use network_layer::filter::conntrack::CONNECTION_TABLE;
+use network_layer::filter::table::FILTER_TABLE;
 use network_layer::icmp::receive::ICMP_RECEIVE_QUEUE;
+use network_layer::igmp::group::MULTICAST_GROUP_TABLE;
+use network_layer::igmp::receive::IGMP_RECEIVE_QUEUE;
+use network_layer::igmp::send::IGMP_MEMBERSHIP_QUEUE;
+use network_layer::arp::cache_table::ARP_CACHE_TABLE;
 use network_layer::arp::receive::ARP_RECEIVE_QUEUE;
 use network_layer::arp::send::{ARP_SEND_REPLY_QUEUE, ARP_SEND_REQUEST_QUEUE};
+use network_layer::ip::forward::IP_FORWARD_QUEUE;
+use network_layer::ip::nat::NAT_TABLE;
+use network_layer::ip::pmtu::PATH_MTU_CACHE;
+use network_layer::ip::protocol::{PROTOCOL_GRE, PROTOCOL_ICMP, PROTOCOL_IGMP, PROTOCOL_IPIP, PROTOCOL_REGISTRY, PROTOCOL_UDP};
 use network_layer::ip::receive::IP_RECEIVE_QUEUE;
+use network_layer::ip::route::ROUTING_TABLE;
+use network_layer::ip::send::{IP_OUTPUT_QUEUE, IP_SEND_QUEUE};
+use network_layer::ip::tunnel::TunnelReceiver;
+use network_layer::rarp::receive::RARP_RECEIVE_QUEUE;
+use network_layer::rarp::table::RARP_TABLE;
 
 use data_link_layer::ethernet_v2::send::ETHERNET_V2_SEND_QUEUE;
+use data_link_layer::interface::INTERFACE_TABLE;
+use data_link_layer::traffic_control::TRAFFIC_CONTROL;
+
+use tools::global_variables::{FILTER_RULES_PATH, GATEWAY_IP, INTERFACE_TABLE_PATH, JOINED_MULTICAST_GROUPS, NAT_RULES_PATH, RARP_TABLE_PATH, ROUTE_TABLE_PATH, TRAFFIC_CONTROL_PATH, TUNNEL_TABLE_PATH};
 
 
 
 
 
 fn main() {
+    //加载RARP映射表
+    let count=RARP_TABLE.lock().unwrap().load(RARP_TABLE_PATH);
+    println!("已加载{}条RARP映射",count);
+
+    //加载接口表
+    {
+        let mut interface_table=INTERFACE_TABLE.lock().unwrap();
+        interface_table.load(INTERFACE_TABLE_PATH);
+        interface_table.load_tunnels(TUNNEL_TABLE_PATH);
+        interface_table.print();
+    }
+
+    //初始化路由表：直连路由、默认路由与静态路由
+    {
+        let mut routing_table=ROUTING_TABLE.lock().unwrap();
+        routing_table.add_connected_routes(&INTERFACE_TABLE.lock().unwrap());
+        routing_table.set_default_route(GATEWAY_IP);
+        routing_table.load_static_routes(ROUTE_TABLE_PATH);
+        routing_table.print();
+    }
+
+    //加载过滤规则
+    {
+        let mut filter_table=FILTER_TABLE.lock().unwrap();
+        let count=filter_table.load(FILTER_RULES_PATH);
+        println!("已加载{}条过滤规则",count);
+        filter_table.print();
+    }
+
+    //加载源地址转换规则
+    {
+        let mut nat_table=NAT_TABLE.lock().unwrap();
+        let count=nat_table.load(NAT_RULES_PATH);
+        println!("已加载{}条NAT规则",count);
+        nat_table.print();
+    }
+
+    //加载流量控制规则
+    {
+        let mut traffic_control=TRAFFIC_CONTROL.lock().unwrap();
+        let count=traffic_control.load(TRAFFIC_CONTROL_PATH);
+        println!("已加载{}条流量控制规则",count);
+        traffic_control.print();
+    }
+
+    //注册上层协议：ICMP、IGMP交给各自的接收队列，IPIP、GRE解封装后重新交给IP接收，UDP数据写入文件
+    {
+        let mut protocol_registry=PROTOCOL_REGISTRY.lock().unwrap();
+        let icmp_receive_queue=Arc::clone(&ICMP_RECEIVE_QUEUE);
+        protocol_registry.register(PROTOCOL_ICMP,Box::new(move |datagram| {
+            icmp_receive_queue.lock().unwrap().add_data(datagram.packet.clone());
+        }));
+        let igmp_receive_queue=Arc::clone(&IGMP_RECEIVE_QUEUE);
+        protocol_registry.register(PROTOCOL_IGMP,Box::new(move |datagram| {
+            igmp_receive_queue.lock().unwrap().add_data(datagram.interface,datagram.packet.clone());
+        }));
+        let mut ipip_receiver=TunnelReceiver::new(Arc::clone(&INTERFACE_TABLE),Arc::clone(&IP_RECEIVE_QUEUE));
+        protocol_registry.register(PROTOCOL_IPIP,Box::new(move |datagram| ipip_receiver.receive(datagram)));
+        let mut gre_receiver=TunnelReceiver::new(Arc::clone(&INTERFACE_TABLE),Arc::clone(&IP_RECEIVE_QUEUE));
+        protocol_registry.register(PROTOCOL_GRE,Box::new(move |datagram| gre_receiver.receive(datagram)));
+        protocol_registry.register(PROTOCOL_UDP,Box::new(|datagram| {
+            let mut file=File::create("receive.data").unwrap();
+            file.write_all(datagram.data()).unwrap();
+        }));
+    }
+
     //运行数据链路层-EthernetV2
     let eth2_send_handle = thread::spawn(move || {
         //EthernetV2协议-发送
         data_link_layer::ethernet_v2::send::send(
-            Arc::clone(&ETHERNET_V2_SEND_QUEUE)
+            Arc::clone(&INTERFACE_TABLE),
+            Arc::clone(&ETHERNET_V2_SEND_QUEUE),
+            Arc::clone(&TRAFFIC_CONTROL)
         );
     });
 
-    let eth2_receive_handle = thread::spawn(move || {
+    //每个物理接口一个接收线程
+    let interfaces:Vec<_>=INTERFACE_TABLE.lock().unwrap().physical_interfaces().cloned().collect();
+    let eth2_receive_handles:Vec<_> = interfaces.into_iter().map(|interface| thread::spawn(move || {
         //EthernetV2协议-接收
         data_link_layer::ethernet_v2::receive::receive(
+            interface,
             Arc::clone(&ARP_RECEIVE_QUEUE),
-        Arc::clone(&IP_RECEIVE_QUEUE));
-    });
+            Arc::clone(&IP_RECEIVE_QUEUE),
+            Arc::clone(&RARP_RECEIVE_QUEUE),
+            Arc::clone(&MULTICAST_GROUP_TABLE),
+            Arc::clone(&FILTER_TABLE),
+            Arc::clone(&TRAFFIC_CONTROL),
+            Arc::clone(&IP_SEND_QUEUE));
+    })).collect();
 
     //运行网络层
-    let ip_send_handle = thread::spawn(move || {
+    let ip_receive_handle = thread::spawn(move || {
         //ip协议-接收
         network_layer::ip::receive::receive(
+            Arc::clone(&INTERFACE_TABLE),
+            Arc::clone(&ROUTING_TABLE),
             Arc::clone(&IP_RECEIVE_QUEUE),
-            Arc::clone(&ICMP_RECEIVE_QUEUE));
+            Arc::clone(&IP_FORWARD_QUEUE),
+            Arc::clone(&IP_SEND_QUEUE),
+            Arc::clone(&MULTICAST_GROUP_TABLE),
+            Arc::clone(&NAT_TABLE),
+            Arc::clone(&FILTER_TABLE),
+            Arc::clone(&CONNECTION_TABLE),
+            Arc::clone(&PROTOCOL_REGISTRY));
+    });
+
+    let ip_send_handle = thread::spawn(move || {
+        //ip协议-发送
+        network_layer::ip::send::send(
+            Arc::clone(&INTERFACE_TABLE),
+            Arc::clone(&ROUTING_TABLE),
+            Arc::clone(&PATH_MTU_CACHE),
+            Arc::clone(&FILTER_TABLE),
+            Arc::clone(&CONNECTION_TABLE),
+            Arc::clone(&ARP_CACHE_TABLE),
+            Arc::clone(&ETHERNET_V2_SEND_QUEUE),
+            Arc::clone(&IP_SEND_QUEUE),
+            Arc::clone(&IP_OUTPUT_QUEUE),
+            Arc::clone(&IP_RECEIVE_QUEUE));
+    });
+
+    let ip_forward_handle = thread::spawn(move || {
+        //ip协议-转发
+        network_layer::ip::forward::forward(
+            Arc::clone(&INTERFACE_TABLE),
+            Arc::clone(&ROUTING_TABLE),
+            Arc::clone(&IP_FORWARD_QUEUE),
+            Arc::clone(&IP_OUTPUT_QUEUE),
+            Arc::clone(&IP_SEND_QUEUE),
+            Arc::clone(&NAT_TABLE),
+            Arc::clone(&FILTER_TABLE),
+            Arc::clone(&CONNECTION_TABLE));
     });
 
     let arp_send_handle = thread::spawn(move || {
@@ -45,24 +179,66 @@ fn main() {
         network_layer::arp::send::send(
             Arc::clone(&ETHERNET_V2_SEND_QUEUE),
             Arc::clone(&ARP_SEND_REPLY_QUEUE),
-            Arc::clone(&ARP_SEND_REQUEST_QUEUE));
+            Arc::clone(&ARP_SEND_REQUEST_QUEUE),
+            Arc::clone(&INTERFACE_TABLE),
+            Arc::clone(&ROUTING_TABLE));
     });
     let arp_receive_handle = thread::spawn(move || {
         //arp协议-接收
         network_layer::arp::receive::receive(
+            Arc::clone(&INTERFACE_TABLE),
+            Arc::clone(&ARP_CACHE_TABLE),
             Arc::clone(&ARP_RECEIVE_QUEUE));
     });
 
+    let rarp_receive_handle = thread::spawn(move || {
+        //rarp协议-接收并应答
+        network_layer::rarp::receive::receive(
+            Arc::clone(&INTERFACE_TABLE),
+            Arc::clone(&RARP_TABLE),
+            Arc::clone(&RARP_RECEIVE_QUEUE),
+            Arc::clone(&ETHERNET_V2_SEND_QUEUE));
+    });
+
     let icmp_receive_handle = thread::spawn(move || {
         //icmp协议-接收
         network_layer::icmp::receive::receive(
-            Arc::clone(&ICMP_RECEIVE_QUEUE));
+            Arc::clone(&ICMP_RECEIVE_QUEUE),
+            Arc::clone(&PATH_MTU_CACHE),
+            Arc::clone(&INTERFACE_TABLE));
+    });
+
+    let igmp_receive_handle = thread::spawn(move || {
+        //igmp协议-接收查询并发送成员报告
+        network_layer::igmp::receive::receive(
+            Arc::clone(&INTERFACE_TABLE),
+            Arc::clone(&MULTICAST_GROUP_TABLE),
+            Arc::clone(&IGMP_RECEIVE_QUEUE),
+            Arc::clone(&IGMP_MEMBERSHIP_QUEUE),
+            Arc::clone(&IP_SEND_QUEUE));
     });
 
+    let console_handle = thread::spawn(move || {
+        //从标准输入读取管理命令：加入、离开组播组，查看、修改路由表
+        console::read_commands();
+    });
+
+    //加入配置的组播组，与应用程序的请求一样交给IGMP线程处理
+    for group in JOINED_MULTICAST_GROUPS{
+        IGMP_MEMBERSHIP_QUEUE.lock().unwrap().join(0,*group);
+    }
+
     eth2_send_handle.join().unwrap();
-    eth2_receive_handle.join().unwrap();
+    for handle in eth2_receive_handles{
+        handle.join().unwrap();
+    }
+    ip_receive_handle.join().unwrap();
     ip_send_handle.join().unwrap();
+    ip_forward_handle.join().unwrap();
     arp_send_handle.join().unwrap();
     arp_receive_handle.join().unwrap();
+    rarp_receive_handle.join().unwrap();
     icmp_receive_handle.join().unwrap();
+    igmp_receive_handle.join().unwrap();
+    console_handle.join().unwrap();
 }
@@ -1,5 +1,6 @@
 use lazy_static::*;
 use std::sync::{Arc,Mutex};
+use std::time::{Duration,Instant};
 
 use super::send::ARP_SEND_REQUEST_QUEUE;
 
@@ -27,11 +28,16 @@ impl ArpCacheEntry{
     }
 }
 
+/// 对同一IP地址发送ARP请求的最小间隔
+const ARP_REQUEST_INTERVAL:Duration=Duration::from_secs(1);
+
 /// ##ARP表
 /// ###容量
 /// 可变
 pub struct  ArpCacheTable{
-    inner:Vec<ArpCacheEntry>
+    inner:Vec<ArpCacheEntry>,
+    /// 最近发出ARP请求的时间，用于避免重复请求
+    requested:Vec<([u8;4],Instant)>
 }
 
 impl ArpCacheTable{
@@ -40,7 +46,8 @@ impl ArpCacheTable{
     pub fn new()->ArpCacheTable{
         let v:Vec<ArpCacheEntry>=Vec::new();
         ArpCacheTable{
-            inner:v
+            inner:v,
+            requested:Vec::new()
         }
     }
     /// ### 功能
@@ -62,16 +69,15 @@ impl ArpCacheTable{
     /// 删除某一个表项
     /// ### 返回值
     /// 是否删除成功
+    #[allow(dead_code)]
     pub fn delete_entry(&mut self,element:ArpCacheEntry)-> bool{
-        let mut index=0;
-        for old in &mut self.inner{
-            if old.ip==element.ip && old.mac==element.mac{
+        match self.inner.iter().position(|old|old.ip==element.ip && old.mac==element.mac) {
+            Some(index)=>{
                 self.inner.remove(index);
-                return true;
+                true
             }
-            index+=1;
+            None=>false,
         }
-        false
     }
     /// ### 功能
     /// 缓存表中是否存在此IP地址
@@ -103,7 +109,7 @@ impl ArpCacheTable{
     /// 根据IP寻找MAC地址
     /// ### 返回值
     /// Option，成功找到则返回mac地址
-    pub fn find_mac_from_ip(&self,ip:[u8;4])->Option<[u8;6]>{
+    pub fn find_mac_from_ip(&mut self,ip:[u8;4])->Option<[u8;6]>{
         for element in  &self.inner{
             if element.ip==ip{
                 return Some(element.mac);
@@ -111,7 +117,13 @@ impl ArpCacheTable{
         }
 
         //如果没找到，则应当向arp发送队列中写入数据，以获取对应的mac
-        ARP_SEND_REQUEST_QUEUE.lock().unwrap().add_data(ip);
+        //同一地址在间隔内只请求一次
+        let now=Instant::now();
+        self.requested.retain(|(_,time)|now.duration_since(*time)<ARP_REQUEST_INTERVAL);
+        if !self.requested.iter().any(|(old_ip,_)|*old_ip==ip){
+            self.requested.push((ip,now));
+            ARP_SEND_REQUEST_QUEUE.lock().unwrap().add_data(ip);
+        }
         None
     }
 }
\ No newline at end of file
@@ -3,17 +3,19 @@ use std::sync::{Arc,Mutex};
 use std::thread::yield_now;
 use lazy_static::*;
 
-use crate::network_layer::arp::send::ARP_SEND_REPLY_QUEUE;
-use crate::tools::global_variables::*;
+use crate::data_link_layer::interface::InterfaceTable;
+use crate::network_layer::arp::send::{build_arp_frame,ARP_SEND_REPLY_QUEUE};
+
+use super::cache_table::{ArpCacheEntry,ArpCacheTable};
 
 lazy_static!{
     ///静态变量--ARP的发送队列
     pub static ref ARP_RECEIVE_QUEUE:Arc<Mutex<ArpReceiveQueue>> = Arc::new(Mutex::new(ArpReceiveQueue::new()));
 }
 
-///ARP的接收队列
+///ARP的接收队列，元素为(收到该帧的接口，ARP帧)
 pub struct ArpReceiveQueue(
-    VecDeque<[u8;28]>
+    VecDeque<(usize,[u8;28])>
 );
 
 impl ArpReceiveQueue{
@@ -24,15 +26,15 @@ impl ArpReceiveQueue{
     }
     /// datalink向其中写入数据。
     /// datalink层的数据长度应在46与1500之间，这一点暂未实现。
-    pub fn add_data(&mut self,buffer: [u8;28]) -> bool{
+    pub fn add_data(&mut self,interface:usize,buffer: [u8;28]) -> bool{
         //if buffer.len()>1500 || buffer.len()<46{
         //    return false;
         //}
-        self.0.push_back(buffer.clone());
+        self.0.push_back((interface,buffer));
         true
     }
     /// 获取队列数据
-    pub fn get_data(&mut self)-> Option<[u8;28]>{
+    pub fn get_data(&mut self)-> Option<(usize,[u8;28])>{
         self.0.pop_front()
     }
 
@@ -43,8 +45,14 @@ impl ArpReceiveQueue{
 }
 
 ///### 功能
-/// 考虑到为server端，这里只考虑对收到的arp请求报文的处理。arp应答报文处理在client端。
-pub fn receive(shared_arp_receive_queue:Arc<Mutex<ArpReceiveQueue>>){
+/// 处理收到的arp报文：
+/// - 请求报文：只应答询问收到该帧的接口IP地址的请求，并记下请求者的地址
+/// - 应答报文：写入缓存表，转发与发送IP数据报时需要用到
+pub fn receive(
+    shared_interface_table:Arc<Mutex<InterfaceTable>>,
+    shared_arp_cache_table:Arc<Mutex<ArpCacheTable>>,
+    shared_arp_receive_queue:Arc<Mutex<ArpReceiveQueue>>
+){
     loop{
         let mut receive_queue=shared_arp_receive_queue.lock().unwrap();
         if receive_queue.is_empty(){
@@ -52,42 +60,52 @@ pub fn receive(shared_arp_receive_queue:Arc<Mutex<ArpReceiveQueue>>){
             continue;
         }
 
-        let arp_frame=receive_queue.get_data().unwrap();
+        let (interface_index,arp_frame)=receive_queue.get_data().unwrap();
+        let interface=match shared_interface_table.lock().unwrap().get(interface_index) {
+            Some(interface)=>interface.clone(),
+            None=>continue,
+        };
+
+        let sender_mac:[u8;6]=arp_frame[8..14].try_into().unwrap();
+        let sender_ip:[u8;4]=arp_frame[14..18].try_into().unwrap();
+        let target_ip:[u8;4]=arp_frame[24..28].try_into().unwrap();
+
+        if arp_frame[6..8]==[0x00,0x02]{
+            //应答报文，写入缓存表
+            update_cache(&shared_arp_cache_table,sender_ip,sender_mac);
+            continue;
+        }
 
         //我们只处理arp请求报文，不是则直接丢弃
         if arp_frame[6..8]!=[0x00,0x01]{
             continue;
         }
 
-        //个人主机，只处理关于自己的请求报文
-        if arp_frame[24..28]!=LOCAL_IP{
+        //只处理关于收到该帧的接口的请求报文，主地址与从地址都应答
+        if !interface.has_ip(target_ip){
             continue;
         }
 
+        //请求者之后多半会与本机通信，顺便记下它的地址
+        update_cache(&shared_arp_cache_table,sender_ip,sender_mac);
+
+        //封装为帧。op=2代表为ARP应答
+        let reply_frame=build_arp_frame(2,interface.mac,target_ip,sender_mac,sender_ip);
 
-        let dest_mac:[u8;6]=arp_frame[8..14].try_into().unwrap();
-        let dest_ip:[u8;4]=arp_frame[14..18].try_into().unwrap();
-        //封装为帧
-        let mut reply_frame:[u8;28]=[0;28];
-        //硬件类型
-        reply_frame[0..2].copy_from_slice(&[0x00,0x01]);
-        //协议类型
-        reply_frame[2..4].copy_from_slice(&[0x08,0x00]);
-        //硬件地址长度
-        reply_frame[4]=6;
-        //协议地址长度
-        reply_frame[5]=4;
-        //操作字段。op=2代表为ARP应答
-        reply_frame[6..8].copy_from_slice(&[0x00,0x02]);
-        //发送端mac地址
-        reply_frame[8..14].copy_from_slice(&LOCAL_MAC);
-        //发送端ip地址
-        reply_frame[14..18].copy_from_slice(&LOCAL_IP);
-        //目的mac地址。
-        reply_frame[18..24].copy_from_slice(&dest_mac);
-        //目的ip地址
-        reply_frame[24..28].copy_from_slice(&dest_ip);
-
-        ARP_SEND_REPLY_QUEUE.lock().unwrap().add_data(reply_frame);
+        ARP_SEND_REPLY_QUEUE.lock().unwrap().add_data(interface.index,reply_frame);
     }
-}
\ No newline at end of file
+}
+
+/// ### 功能
+/// 把一对IP与MAC写入缓存表，已存在则更新
+fn update_cache(shared_arp_cache_table:&Arc<Mutex<ArpCacheTable>>,ip:[u8;4],mac:[u8;6]){
+    let mut arp_cache_table=shared_arp_cache_table.lock().unwrap();
+    // 1:静态 2:动态 3: log
+    let arp_cache_entry=ArpCacheEntry::new(ip,mac,2);
+    if arp_cache_table.is_existed_ip(ip){
+        arp_cache_table.update_entry(arp_cache_entry);
+    }
+    else{
+        arp_cache_table.insert_entry(arp_cache_entry);
+    }
+}
@@ -1,9 +1,12 @@
 use std::collections::VecDeque;
 use std::sync::{Arc,Mutex};
+use std::thread::yield_now;
 use lazy_static::*;
 use crate::tools::global_variables::*;
 
 use crate::data_link_layer::ethernet_v2::send::Eth2SendQueue;
+use crate::data_link_layer::interface::InterfaceTable;
+use crate::network_layer::ip::route::RoutingTable;
 lazy_static!{
     ///静态变量--ARP应答报文的发送队列
     pub static ref ARP_SEND_REPLY_QUEUE:Arc<Mutex<ArpSendReplyQueue>> = Arc::new(Mutex::new(ArpSendReplyQueue::new()));
@@ -11,9 +14,9 @@ lazy_static!{
     pub static ref ARP_SEND_REQUEST_QUEUE:Arc<Mutex<ArpSendRequestQueue>> = Arc::new(Mutex::new(ArpSendRequestQueue::new()));
 }
 
-///ARP应答报文的发送队列
+///ARP应答报文的发送队列，元素为(发出的接口，ARP帧)
 pub struct ArpSendReplyQueue(
-    VecDeque<[u8;28]>
+    VecDeque<(usize,[u8;28])>
 );
 
 impl ArpSendReplyQueue{
@@ -23,12 +26,12 @@ impl ArpSendReplyQueue{
         ArpSendReplyQueue(new_send_queue)
     }
     /// 由receive控制，向其中加入封装好的arp应答帧
-    pub fn add_data(&mut self,arp_frame: [u8;28]) -> bool{
-        self.0.push_back(arp_frame.clone());
+    pub fn add_data(&mut self,interface:usize,arp_frame: [u8;28]) -> bool{
+        self.0.push_back((interface,arp_frame));
         true
     }
     /// 获取应答报文队列数据
-    pub fn get_data(&mut self)-> Option<[u8;28]>{
+    pub fn get_data(&mut self)-> Option<(usize,[u8;28])>{
         self.0.pop_front()
     }
 
@@ -51,7 +54,7 @@ impl ArpSendRequestQueue{
     }
     /// 由ip控制，向其中加入ipv4地址
     pub fn add_data(&mut self,ip: [u8;4]) -> bool{
-        self.0.push_back(ip.clone());
+        self.0.push_back(ip);
         true
     }
     /// 获取队列数据
@@ -64,12 +67,48 @@ impl ArpSendRequestQueue{
         self.0.is_empty()
     }
 }
+/// ### 功能
+/// 按ARP报文格式（以太网+IPv4）封装一个28字节的帧，RARP也使用同样的格式
+/// ### 参数
+/// operation为操作字段：1为ARP请求，2为ARP应答，3为RARP请求，4为RARP应答
+pub fn build_arp_frame(
+    operation:u16,
+    sender_mac:[u8;6],
+    sender_ip:[u8;4],
+    target_mac:[u8;6],
+    target_ip:[u8;4]
+)->[u8;28]{
+    let mut arp_frame:[u8;28]=[0;28];
+    //硬件类型
+    arp_frame[0..2].copy_from_slice(&[0x00,0x01]);
+    //协议类型
+    arp_frame[2..4].copy_from_slice(&[0x08,0x00]);
+    //硬件地址长度
+    arp_frame[4]=6;
+    //协议地址长度
+    arp_frame[5]=4;
+    //操作字段
+    arp_frame[6..8].copy_from_slice(&operation.to_be_bytes());
+    //发送端mac地址
+    arp_frame[8..14].copy_from_slice(&sender_mac);
+    //发送端ip地址
+    arp_frame[14..18].copy_from_slice(&sender_ip);
+    //目的mac地址
+    arp_frame[18..24].copy_from_slice(&target_mac);
+    //目的ip地址
+    arp_frame[24..28].copy_from_slice(&target_ip);
+    arp_frame
+}
+
 ///### 功能
-/// 考虑到为server端，目前仅支持发送arp应答报文。arp请求报文发送在client端。
+/// 发送arp应答报文与请求报文。
+/// 请求报文从路由表中直连路由所在的接口发出；下一跳由ip层确定，这里不再替换为网关
 pub fn send(
     shared_ethernet_v2_send_queue:Arc<Mutex<Eth2SendQueue>>,
     shared_arp_send_reply_queue:Arc<Mutex<ArpSendReplyQueue>>,
-    shared_arp_send_request_queue:Arc<Mutex<ArpSendRequestQueue>>
+    shared_arp_send_request_queue:Arc<Mutex<ArpSendRequestQueue>>,
+    shared_interface_table:Arc<Mutex<InterfaceTable>>,
+    shared_routing_table:Arc<Mutex<RoutingTable>>
 ) {
     loop{
         if !shared_arp_send_reply_queue.lock().unwrap().is_empty(){
@@ -79,50 +118,39 @@ pub fn send(
                 continue;
             }
             //队列不为空，则封装为帧，并发送
-            let arp_frame=sendqueue.get_data().unwrap();
+            let (interface,arp_frame)=sendqueue.get_data().unwrap();
             
             let dest_mac:[u8;6]=arp_frame[18..24].try_into().unwrap();
 
             //发送--写入到Ethernet-v2的发送队列里
             let mut ethernet_v2_send_queue=shared_ethernet_v2_send_queue.lock().unwrap();
-            ethernet_v2_send_queue.add_data(dest_mac,0x0806,&Vec::from(arp_frame));
+            ethernet_v2_send_queue.add_data_to(interface,dest_mac,0x0806,&Vec::from(arp_frame));
         }
         else if !shared_arp_send_request_queue.lock().unwrap().is_empty(){
             let mut sendqueue=shared_arp_send_request_queue.lock().unwrap();
             //队列不为空，则封装为帧，并发送
-            let mut dest_ip=sendqueue.get_data().unwrap();
-            //如果为同一子网，为目的ip
-            dest_ip= if (u32::from_be_bytes(dest_ip) & u32::from_be_bytes(NETMASK))==(u32::from_be_bytes(LOCAL_IP) & u32::from_be_bytes(NETMASK)){
-                dest_ip
-            }
-            else{
-                //否则为网关的ip
-                GATEWAY_IP
+            let dest_ip=sendqueue.get_data().unwrap();
+
+            //目的ip必须在某个接口的直连子网内
+            let route=shared_routing_table.lock().unwrap().lookup(dest_ip);
+            let interface_index=match route {
+                Some(route) if route.gateway.is_none()=>route.interface,
+                _=>continue,
+            };
+            //隧道接口上没有ARP
+            let interface=match shared_interface_table.lock().unwrap().get(interface_index) {
+                Some(interface) if interface.tunnel.is_none()=>interface.clone(),
+                _=>continue,
             };
-            
-            //封装为帧
-            let mut arp_frame:[u8;28]=[0;28];
-            //硬件类型
-            arp_frame[0..2].copy_from_slice(&[0x00,0x01]);
-            //协议类型
-            arp_frame[2..4].copy_from_slice(&[0x08,0x00]);
-            //硬件地址长度
-            arp_frame[4]=6;
-            //协议地址长度
-            arp_frame[5]=4;
-            //操作字段。op=1代表为ARP应答
-            arp_frame[6..8].copy_from_slice(&[0x00,0x02]);
-            //发送端mac地址
-            arp_frame[8..14].copy_from_slice(&LOCAL_MAC);
-            //发送端ip地址
-            arp_frame[14..18].copy_from_slice(&LOCAL_IP);
-            //目的mac地址。全0
-            arp_frame[18..24].copy_from_slice(&[0;6]);
-            //目的ip地址
-            arp_frame[24..28].copy_from_slice(&dest_ip);
+
+            //封装为帧。op=1代表为ARP请求，目的mac地址全0；发送方地址使用与目的地址同一子网的地址
+            let arp_frame=build_arp_frame(1,interface.mac,interface.select_source(dest_ip,dest_ip),[0;6],dest_ip);
 
             //发送--写入到Ethernet-v2的发送队列里
-            shared_ethernet_v2_send_queue.lock().unwrap().add_data(BROADCAST_MAC,0x0806,&Vec::from(arp_frame));
+            shared_ethernet_v2_send_queue.lock().unwrap().add_data_to(interface.index,BROADCAST_MAC,0x0806,&Vec::from(arp_frame));
+        }
+        else{
+            yield_now();
         }
     }
 }
@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::sync::{Arc,Mutex};
+use std::time::{Duration,Instant};
+use lazy_static::*;
+
+use crate::network_layer::ip::header::{header_length,FRAGMENT_OFFSET_MASK};
+use crate::network_layer::ip::protocol::{PROTOCOL_ICMP,PROTOCOL_TCP,PROTOCOL_UDP};
+use crate::tools::address::format_ip;
+
+lazy_static!{
+    ///静态变量--连接跟踪表，IP接收与发送时更新，过滤规则按连接状态匹配
+    pub static ref CONNECTION_TABLE:Arc<Mutex<ConnectionTable>> = Arc::new(Mutex::new(ConnectionTable::new()));
+}
+
+/// 最多跟踪的连接数，超过时淘汰最久没有分组的连接
+pub const MAX_CONNECTIONS:usize=4096;
+/// ICMP查询的超时
+pub const ICMP_TIMEOUT:Duration=Duration::from_secs(30);
+/// UDP只有单向分组时的超时
+pub const UDP_TIMEOUT:Duration=Duration::from_secs(30);
+/// UDP双向都有分组后的超时
+pub const UDP_STREAM_TIMEOUT:Duration=Duration::from_secs(180);
+/// TCP握手与关闭过程中的超时
+pub const TCP_TRANSITORY_TIMEOUT:Duration=Duration::from_secs(120);
+/// TCP已建立连接的超时
+pub const TCP_ESTABLISHED_TIMEOUT:Duration=Duration::from_secs(7440);
+/// TCP连接被RST复位或双方都关闭后的超时
+pub const TCP_CLOSE_TIMEOUT:Duration=Duration::from_secs(10);
+/// 其他协议的超时
+pub const GENERIC_TIMEOUT:Duration=Duration::from_secs(600);
+/// 清理超时连接的间隔
+const EXPIRE_INTERVAL:Duration=Duration::from_secs(1);
+
+const TCP_FIN:u8=0x01;
+const TCP_SYN:u8=0x02;
+const TCP_RST:u8=0x04;
+const TCP_ACK:u8=0x10;
+
+/// 分组相对于连接的状态
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum ConnectionState{
+    /// 发起连接的分组，或尚未收到应答的连接中的分组
+    New,
+    /// 双向都已经有分组的连接中的分组
+    Established,
+    /// 与已有连接相关的ICMP差错报文
+    Related,
+    /// 不属于任何连接，也不能发起连接的分组（如没有请求的回送回答）
+    Invalid,
+}
+
+impl ConnectionState{
+    /// ### 功能
+    /// 由规则文件中的名称解析状态
+    pub fn from_name(name:&str)->Option<ConnectionState>{
+        match name.to_ascii_uppercase().as_str() {
+            "NEW"=>Some(ConnectionState::New),
+            "ESTABLISHED"=>Some(ConnectionState::Established),
+            "RELATED"=>Some(ConnectionState::Related),
+            "INVALID"=>Some(ConnectionState::Invalid),
+            _=>None,
+        }
+    }
+}
+
+/// TCP连接的状态（简化）
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum TcpState{
+    /// 发出了SYN
+    SynSent,
+    /// 收到了SYN+ACK
+    SynReceived,
+    Established,
+    /// 某一方发出了FIN
+    Closing,
+    /// 收到了RST，或双方都发出了FIN
+    Closed,
+}
+
+/// 一个方向的五元组。ICMP回送的端口均为标识，其他协议的端口为0
+#[derive(Clone,Copy,PartialEq,Eq,Hash,Debug)]
+pub struct FlowTuple{
+    pub protocol:u8,
+    pub source_ip:[u8;4],
+    pub destination_ip:[u8;4],
+    pub source_port:u16,
+    pub destination_port:u16,
+}
+
+impl FlowTuple{
+    /// ### 功能
+    /// 反方向的五元组
+    pub fn reverse(&self)->FlowTuple{
+        FlowTuple{
+            protocol:self.protocol,
+            source_ip:self.destination_ip,
+            destination_ip:self.source_ip,
+            source_port:self.destination_port,
+            destination_port:self.source_port,
+        }
+    }
+}
+
+/// 一个被跟踪的连接
+struct Connection{
+    /// 是否已经有应答方向的分组
+    replied:bool,
+    tcp_state:Option<TcpState>,
+    last_seen:Instant,
+    timeout:Duration,
+    /// (发起方向，应答方向)的分组数
+    packets:(u64,u64),
+}
+
+/// 分组中提取出的信息
+enum FlowKind{
+    /// 可以发起连接的分组：五元组，以及能否发起新连接（回送回答不能）
+    Flow(FlowTuple,bool),
+    /// ICMP差错报文：被引用分组的五元组
+    Error(FlowTuple),
+    /// 后续分片：(协议，源地址，目的地址)
+    Fragment(u8,[u8;4],[u8;4]),
+}
+
+/// ## 连接跟踪表
+/// 以发起方向的五元组记录连接，应答方向的五元组即其反方向。
+/// ICMP回送请求与回答、UDP五元组、TCP连接（按标志位跟踪状态）各自有超时；
+/// ICMP差错报文按其引用的分组关联到原连接
+pub struct ConnectionTable{
+    inner:HashMap<FlowTuple,Connection>,
+    last_expire:Instant,
+}
+
+impl ConnectionTable{
+    /// ### 功能
+    /// 新建空的连接跟踪表
+    pub fn new()->ConnectionTable{
+        ConnectionTable{
+            inner:HashMap::new(),
+            last_expire:Instant::now(),
+        }
+    }
+    /// ### 功能
+    /// 跟踪一个分组：必要时建立新连接，更新连接的状态与超时。分组应当已经通过首部校验
+    /// ### 返回值
+    /// 分组相对于连接的状态
+    pub fn track(&mut self,packet:&[u8])->ConnectionState{
+        self.expire();
+        match flow_kind(packet) {
+            None=>ConnectionState::Invalid,
+            Some(FlowKind::Error(tuple))=>self.related(tuple),
+            Some(FlowKind::Fragment(protocol,source_ip,destination_ip))=>self.fragment_state(protocol,source_ip,destination_ip),
+            Some(FlowKind::Flow(tuple,can_create))=>{
+                let tcp_flags=tcp_flags(packet);
+                if let Some(connection)=self.inner.get_mut(&tuple){
+                    connection.packets.0+=1;
+                    update(connection,tuple.protocol,tcp_flags,false);
+                    return if connection.replied {ConnectionState::Established} else {ConnectionState::New};
+                }
+                if let Some(connection)=self.inner.get_mut(&tuple.reverse()){
+                    connection.packets.1+=1;
+                    connection.replied=true;
+                    update(connection,tuple.protocol,tcp_flags,true);
+                    return ConnectionState::Established;
+                }
+                //TCP只有SYN才能发起连接
+                if !can_create || (tuple.protocol==PROTOCOL_TCP && tcp_flags.is_some_and(|flags|flags & (TCP_SYN|TCP_ACK)!=TCP_SYN)){
+                    return ConnectionState::Invalid;
+                }
+                if self.inner.len()>=MAX_CONNECTIONS{
+                    self.evict_oldest();
+                }
+                let mut connection=Connection{
+                    replied:false,
+                    tcp_state:None,
+                    last_seen:Instant::now(),
+                    timeout:GENERIC_TIMEOUT,
+                    packets:(1,0),
+                };
+                update(&mut connection,tuple.protocol,tcp_flags,false);
+                self.inner.insert(tuple,connection);
+                ConnectionState::New
+            }
+        }
+    }
+    /// ### 功能
+    /// 查询分组相对于连接的状态，不修改连接跟踪表。过滤时先用它得到状态，分组被接受后再调用track
+    pub fn classify(&self,packet:&[u8])->ConnectionState{
+        match flow_kind(packet) {
+            None=>ConnectionState::Invalid,
+            Some(FlowKind::Error(tuple))=>self.related(tuple),
+            Some(FlowKind::Fragment(protocol,source_ip,destination_ip))=>self.fragment_state(protocol,source_ip,destination_ip),
+            Some(FlowKind::Flow(tuple,can_create))=>{
+                if let Some(connection)=self.inner.get(&tuple){
+                    return if connection.replied {ConnectionState::Established} else {ConnectionState::New};
+                }
+                if self.inner.contains_key(&tuple.reverse()){
+                    return ConnectionState::Established;
+                }
+                if !can_create || (tuple.protocol==PROTOCOL_TCP && tcp_flags(packet).is_some_and(|flags|flags & (TCP_SYN|TCP_ACK)!=TCP_SYN)){
+                    return ConnectionState::Invalid;
+                }
+                ConnectionState::New
+            }
+        }
+    }
+    /// ### 功能
+    /// 打印连接跟踪表
+    pub fn print(&self){
+        println!("{:<6}{:<22}{:<22}{:<8}{:<14}{:<12}剩余(秒)","协议","发起方","应答方","已应答","TCP状态","分组");
+        for (tuple,connection) in &self.inner{
+            let tcp_state=match connection.tcp_state {
+                Some(tcp_state)=>format!("{:?}",tcp_state),
+                None=>String::from("-"),
+            };
+            println!("{:<6}{:<22}{:<22}{:<8}{:<14}{:<12}{}",tuple.protocol,
+                format!("{}:{}",format_ip(tuple.source_ip),tuple.source_port),
+                format!("{}:{}",format_ip(tuple.destination_ip),tuple.destination_port),
+                connection.replied,tcp_state,
+                format!("{}/{}",connection.packets.0,connection.packets.1),
+                connection.timeout.saturating_sub(connection.last_seen.elapsed()).as_secs());
+        }
+    }
+
+    /// ### 功能
+    /// ICMP差错报文引用的分组属于某个连接（任一方向）时为RELATED
+    fn related(&self,tuple:FlowTuple)->ConnectionState{
+        if self.inner.contains_key(&tuple) || self.inner.contains_key(&tuple.reverse()){
+            ConnectionState::Related
+        }
+        else{
+            ConnectionState::Invalid
+        }
+    }
+    /// ### 功能
+    /// 后续分片没有端口，两个地址之间有该协议的连接时视为属于该连接
+    fn fragment_state(&self,protocol:u8,source_ip:[u8;4],destination_ip:[u8;4])->ConnectionState{
+        let mut state=ConnectionState::Invalid;
+        for (tuple,connection) in &self.inner{
+            if tuple.protocol!=protocol{
+                continue;
+            }
+            if tuple.source_ip==source_ip && tuple.destination_ip==destination_ip{
+                if connection.replied{
+                    return ConnectionState::Established;
+                }
+                state=ConnectionState::New;
+            }
+            else if tuple.source_ip==destination_ip && tuple.destination_ip==source_ip{
+                return ConnectionState::Established;
+            }
+        }
+        state
+    }
+    /// ### 功能
+    /// 淘汰最久没有分组的连接
+    fn evict_oldest(&mut self){
+        let oldest=self.inner.iter().min_by_key(|(_,connection)|connection.last_seen).map(|(tuple,_)|*tuple);
+        if let Some(tuple)=oldest{
+            self.inner.remove(&tuple);
+        }
+    }
+    /// ### 功能
+    /// 删除超时的连接
+    fn expire(&mut self){
+        if self.last_expire.elapsed()<EXPIRE_INTERVAL{
+            return;
+        }
+        self.last_expire=Instant::now();
+        self.inner.retain(|_,connection|connection.last_seen.elapsed()<connection.timeout);
+    }
+}
+
+/// ### 功能
+/// 收到连接中的分组后更新TCP状态与超时
+fn update(connection:&mut Connection,protocol:u8,tcp_flags:Option<u8>,reply:bool){
+    connection.last_seen=Instant::now();
+    connection.timeout=match protocol {
+        PROTOCOL_ICMP=>ICMP_TIMEOUT,
+        PROTOCOL_UDP=>if connection.replied {UDP_STREAM_TIMEOUT} else {UDP_TIMEOUT},
+        PROTOCOL_TCP=>{
+            let flags=tcp_flags.unwrap_or(0);
+            let state=connection.tcp_state.unwrap_or(TcpState::SynSent);
+            let state=if flags & TCP_RST!=0 {
+                TcpState::Closed
+            }
+            else if flags & TCP_FIN!=0 {
+                if state==TcpState::Closing {TcpState::Closed} else {TcpState::Closing}
+            }
+            else{
+                match state {
+                    TcpState::SynSent if reply && flags & (TCP_SYN|TCP_ACK)==TCP_SYN|TCP_ACK=>TcpState::SynReceived,
+                    TcpState::SynReceived if !reply && flags & TCP_ACK!=0=>TcpState::Established,
+                    state=>state,
+                }
+            };
+            connection.tcp_state=Some(state);
+            match state {
+                TcpState::Established=>TCP_ESTABLISHED_TIMEOUT,
+                TcpState::Closed=>TCP_CLOSE_TIMEOUT,
+                _=>TCP_TRANSITORY_TIMEOUT,
+            }
+        }
+        _=>GENERIC_TIMEOUT,
+    };
+}
+
+/// ### 功能
+/// TCP分组的标志位，不是TCP或长度不足时为None
+fn tcp_flags(packet:&[u8])->Option<u8>{
+    if packet[9]!=PROTOCOL_TCP{
+        return None;
+    }
+    packet.get(header_length(packet)+13).copied()
+}
+
+/// ### 功能
+/// 从分组中提取五元组
+fn flow_kind(packet:&[u8])->Option<FlowKind>{
+    let protocol=packet[9];
+    let source_ip:[u8;4]=packet[12..16].try_into().unwrap();
+    let destination_ip:[u8;4]=packet[16..20].try_into().unwrap();
+    if u16::from_be_bytes([packet[6],packet[7]]) & FRAGMENT_OFFSET_MASK!=0{
+        return Some(FlowKind::Fragment(protocol,source_ip,destination_ip));
+    }
+    let data=packet.get(header_length(packet)..)?;
+    let tuple=|source_port:u16,destination_port:u16|FlowTuple{protocol,source_ip,destination_ip,source_port,destination_port};
+    match protocol {
+        PROTOCOL_TCP|PROTOCOL_UDP=>{
+            if data.len()<4{
+                return None;
+            }
+            let source_port=u16::from_be_bytes([data[0],data[1]]);
+            let destination_port=u16::from_be_bytes([data[2],data[3]]);
+            Some(FlowKind::Flow(tuple(source_port,destination_port),true))
+        }
+        PROTOCOL_ICMP=>{
+            if data.len()<8{
+                return None;
+            }
+            match data[0] {
+                //回送请求与回答：以标识区分
+                8|0=>{
+                    let id=u16::from_be_bytes([data[4],data[5]]);
+                    Some(FlowKind::Flow(tuple(id,id),data[0]==8))
+                }
+                //差错报文：引用的是对方收到的分组，即本连接中发往对方的分组
+                3|4|5|11|12=>{
+                    let inner=&data[8..];
+                    if inner.len()<20 || header_length(inner)<20 || inner.len()<header_length(inner)+4{
+                        return None;
+                    }
+                    let inner_data=&inner[header_length(inner)..];
+                    let (source_port,destination_port)=match inner[9] {
+                        PROTOCOL_TCP|PROTOCOL_UDP=>(u16::from_be_bytes([inner_data[0],inner_data[1]]),u16::from_be_bytes([inner_data[2],inner_data[3]])),
+                        PROTOCOL_ICMP if inner_data.len()>=6=>{
+                            let id=u16::from_be_bytes([inner_data[4],inner_data[5]]);
+                            (id,id)
+                        }
+                        _=>(0,0),
+                    };
+                    Some(FlowKind::Error(FlowTuple{
+                        protocol:inner[9],
+                        source_ip:inner[12..16].try_into().unwrap(),
+                        destination_ip:inner[16..20].try_into().unwrap(),
+                        source_port,
+                        destination_port,
+                    }))
+                }
+                _=>Some(FlowKind::Flow(tuple(0,0),true)),
+            }
+        }
+        _=>Some(FlowKind::Flow(tuple(0,0),true)),
+    }
+}
@@ -0,0 +1,3 @@
+pub mod rule;
+pub mod table;
+pub mod conntrack;
\ No newline at end of file
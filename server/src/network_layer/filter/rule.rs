@@ -0,0 +1,246 @@
+use crate::network_layer::ip::header::{header_length,FRAGMENT_OFFSET_MASK};
+use crate::tools::address::*;
+
+use super::conntrack::ConnectionState;
+
+/// 过滤的挂载点
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum Hook{
+    /// 以太网接收：校验通过、写入各协议的接收队列之前
+    Ethernet,
+    /// IP接收：目的地址是本机的分组，交付上层协议之前
+    Input,
+    /// IP发送：本机产生的数据报，分片之前
+    Output,
+    /// IP转发：查找路由之后、转发之前
+    Forward,
+}
+
+impl Hook{
+    /// ### 功能
+    /// 由规则文件中的名称解析挂载点
+    pub fn from_name(name:&str)->Option<Hook>{
+        match name.to_ascii_lowercase().as_str() {
+            "ethernet"=>Some(Hook::Ethernet),
+            "input"=>Some(Hook::Input),
+            "output"=>Some(Hook::Output),
+            "forward"=>Some(Hook::Forward),
+            _=>None,
+        }
+    }
+}
+
+/// 规则匹配后的动作
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum FilterAction{
+    /// 放行
+    Accept,
+    /// 静默丢弃
+    Drop,
+    /// 丢弃并回送ICMP目的不可达报文
+    Reject,
+}
+
+impl FilterAction{
+    /// ### 功能
+    /// 由规则文件中的名称解析动作
+    pub fn from_name(name:&str)->Option<FilterAction>{
+        match name.to_ascii_uppercase().as_str() {
+            "ACCEPT"=>Some(FilterAction::Accept),
+            "DROP"=>Some(FilterAction::Drop),
+            "REJECT"=>Some(FilterAction::Reject),
+            _=>None,
+        }
+    }
+}
+
+/// 被检查的分组：以太网挂载点有帧首部，IP挂载点只有IP分组
+pub struct FilterPacket<'a>{
+    /// 入接口
+    pub in_interface:Option<usize>,
+    /// 出接口
+    pub out_interface:Option<usize>,
+    /// 以太网帧的(源MAC，类型)
+    pub frame_header:Option<([u8;6],u16)>,
+    /// IP分组，非IP的帧为None
+    pub packet:Option<&'a [u8]>,
+    /// 连接跟踪的状态，没有跟踪的分组（如以太网挂载点）为None
+    pub state:Option<ConnectionState>,
+    /// 计入字节计数的长度
+    pub length:usize,
+}
+
+/// ## 一条过滤规则
+/// 所有给出的条件都满足时匹配；端口与ICMP类型只在不是后续分片、且长度足够时才能匹配
+pub struct FilterRule{
+    pub hook:Hook,
+    pub action:FilterAction,
+    pub in_interface:Option<usize>,
+    pub out_interface:Option<usize>,
+    pub source_mac:Option<[u8;6]>,
+    pub ether_type:Option<u16>,
+    /// (网络，掩码)
+    pub source:Option<([u8;4],[u8;4])>,
+    pub destination:Option<([u8;4],[u8;4])>,
+    pub protocol:Option<u8>,
+    pub icmp_type:Option<u8>,
+    /// (最小端口，最大端口)
+    pub source_port:Option<(u16,u16)>,
+    pub destination_port:Option<(u16,u16)>,
+    /// 连接状态，满足其中之一即可
+    pub states:Option<Vec<ConnectionState>>,
+    /// 匹配的分组数
+    pub packets:u64,
+    /// 匹配的字节数
+    pub bytes:u64,
+}
+
+impl FilterRule{
+    /// ### 功能
+    /// 解析一行规则，格式为"挂载点 动作 [条件 值]..."，条件有：
+    /// in/out 接口序号、mac 源MAC、type 以太网类型、src/dst 网络/前缀长度、
+    /// proto tcp|udp|icmp|协议号、icmp-type 类型、sport/dport 端口或端口范围（如1000-2000）、
+    /// state 连接状态（NEW、ESTABLISHED、RELATED、INVALID，以逗号分隔）
+    /// ### 返回值
+    /// Option，格式错误时返回None
+    pub fn parse(fields:&[&str])->Option<FilterRule>{
+        if fields.len()<2 || !fields.len().is_multiple_of(2){
+            return None;
+        }
+        let mut rule=FilterRule{
+            hook:Hook::from_name(fields[0])?,
+            action:FilterAction::from_name(fields[1])?,
+            in_interface:None,
+            out_interface:None,
+            source_mac:None,
+            ether_type:None,
+            source:None,
+            destination:None,
+            protocol:None,
+            icmp_type:None,
+            source_port:None,
+            destination_port:None,
+            states:None,
+            packets:0,
+            bytes:0,
+        };
+        for pair in fields[2..].chunks(2){
+            let value=pair[1];
+            match pair[0] {
+                "in"=>rule.in_interface=Some(value.parse().ok()?),
+                "out"=>rule.out_interface=Some(value.parse().ok()?),
+                "mac"=>rule.source_mac=Some(parse_mac(value)?),
+                "type"=>rule.ether_type=Some(u16::from_str_radix(value.trim_start_matches("0x"),16).ok()?),
+                "src"=>rule.source=Some(parse_prefix(value)?),
+                "dst"=>rule.destination=Some(parse_prefix(value)?),
+                "proto"=>rule.protocol=Some(match value {
+                    "icmp"=>1,
+                    "igmp"=>2,
+                    "tcp"=>6,
+                    "udp"=>17,
+                    _=>value.parse().ok()?,
+                }),
+                "icmp-type"=>rule.icmp_type=Some(value.parse().ok()?),
+                "sport"=>rule.source_port=Some(parse_port_range(value)?),
+                "dport"=>rule.destination_port=Some(parse_port_range(value)?),
+                "state"=>rule.states=Some(value.split(',').map(ConnectionState::from_name).collect::<Option<Vec<_>>>()?),
+                _=>return None,
+            }
+        }
+        Some(rule)
+    }
+    /// ### 功能
+    /// 分组是否满足规则的所有条件
+    pub fn matches(&self,packet:&FilterPacket)->bool{
+        if self.in_interface.is_some() && self.in_interface!=packet.in_interface{
+            return false;
+        }
+        if self.out_interface.is_some() && self.out_interface!=packet.out_interface{
+            return false;
+        }
+        if self.source_mac.is_some() || self.ether_type.is_some(){
+            let (source_mac,ether_type)=match packet.frame_header {
+                Some(frame_header)=>frame_header,
+                None=>return false,
+            };
+            if self.source_mac.is_some_and(|mac|mac!=source_mac) || self.ether_type.is_some_and(|t|t!=ether_type){
+                return false;
+            }
+        }
+        if let Some(states)=&self.states{
+            match packet.state {
+                Some(state) if states.contains(&state)=>{}
+                _=>return false,
+            }
+        }
+        let has_ip_condition=self.source.is_some() || self.destination.is_some() || self.protocol.is_some()
+            || self.icmp_type.is_some() || self.source_port.is_some() || self.destination_port.is_some();
+        if !has_ip_condition{
+            return true;
+        }
+        let ip=match packet.packet {
+            Some(ip) if ip.len()>=20=>ip,
+            _=>return false,
+        };
+        let source_ip:[u8;4]=ip[12..16].try_into().unwrap();
+        let destination_ip:[u8;4]=ip[16..20].try_into().unwrap();
+        if self.source.is_some_and(|(network,netmask)|!is_same_subnet(source_ip,network,netmask)){
+            return false;
+        }
+        if self.destination.is_some_and(|(network,netmask)|!is_same_subnet(destination_ip,network,netmask)){
+            return false;
+        }
+        if self.protocol.is_some_and(|protocol|protocol!=ip[9]){
+            return false;
+        }
+        if self.icmp_type.is_none() && self.source_port.is_none() && self.destination_port.is_none(){
+            return true;
+        }
+
+        //上层协议的字段：后续分片中没有
+        if u16::from_be_bytes([ip[6],ip[7]]) & FRAGMENT_OFFSET_MASK!=0{
+            return false;
+        }
+        let data=match ip.get(header_length(ip)..) {
+            Some(data)=>data,
+            None=>return false,
+        };
+        if let Some(icmp_type)=self.icmp_type{
+            if ip[9]!=1 || data.is_empty() || data[0]!=icmp_type{
+                return false;
+            }
+        }
+        if self.source_port.is_some() || self.destination_port.is_some(){
+            if !matches!(ip[9],6|17) || data.len()<4{
+                return false;
+            }
+            let source_port=u16::from_be_bytes([data[0],data[1]]);
+            let destination_port=u16::from_be_bytes([data[2],data[3]]);
+            if self.source_port.is_some_and(|(min,max)|source_port<min || source_port>max){
+                return false;
+            }
+            if self.destination_port.is_some_and(|(min,max)|destination_port<min || destination_port>max){
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// ### 功能
+/// 解析端口或端口范围
+fn parse_port_range(s:&str)->Option<(u16,u16)>{
+    match s.split_once('-') {
+        Some((min,max))=>{
+            let (min,max)=(min.parse().ok()?,max.parse().ok()?);
+            if min>max{
+                return None;
+            }
+            Some((min,max))
+        }
+        None=>{
+            let port=s.parse().ok()?;
+            Some((port,port))
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::fs::File;
+use std::io::{BufRead,BufReader};
+use std::sync::{Arc,Mutex};
+use lazy_static::*;
+
+use crate::network_layer::icmp::send::{send_error,ICMP_DESTINATION_UNREACHABLE};
+use crate::network_layer::ip::send::IpSendQueue;
+use crate::tools::address::*;
+
+use super::rule::*;
+
+lazy_static!{
+    ///静态变量--过滤规则表，以太网接收、IP接收、IP发送与转发时检查
+    pub static ref FILTER_TABLE:Arc<Mutex<FilterTable>> = Arc::new(Mutex::new(FilterTable::new()));
+}
+
+/// 拒绝时回送的ICMP目的不可达报文的code：通信被管理性禁止（RFC 1812）
+const REJECT_CODE:u8=13;
+/// 所有挂载点
+const HOOKS:[Hook;4]=[Hook::Ethernet,Hook::Input,Hook::Output,Hook::Forward];
+
+/// ## 过滤规则表
+/// 每个挂载点按顺序检查规则，第一条匹配的规则决定动作；都不匹配时使用该挂载点的默认策略（默认放行）
+pub struct FilterTable{
+    rules:Vec<FilterRule>,
+    /// 各挂载点的(默认策略，使用默认策略的分组数)，下标与HOOKS相同
+    policies:[(FilterAction,u64);4],
+}
+
+impl FilterTable{
+    /// ### 功能
+    /// 新建没有规则、全部放行的规则表
+    pub fn new()->FilterTable{
+        FilterTable{
+            rules:Vec::new(),
+            policies:[(FilterAction::Accept,0);4],
+        }
+    }
+    /// ### 功能
+    /// 在末尾添加规则
+    pub fn add_rule(&mut self,rule:FilterRule){
+        self.rules.push(rule);
+    }
+    /// ### 功能
+    /// 设置挂载点的默认策略
+    pub fn set_policy(&mut self,hook:Hook,action:FilterAction){
+        self.policies[hook_index(hook)].0=action;
+    }
+    /// ### 功能
+    /// 从文件加载规则，按文件中的顺序检查。每行一条规则，格式见FilterRule::parse；
+    /// "policy 挂载点 动作"设置默认策略，以#开头的内容为注释
+    /// ### 返回值
+    /// 成功加载的规则数
+    pub fn load(&mut self,file_path:&str)->usize{
+        let file=match File::open(file_path) {
+            Ok(file)=>file,
+            Err(_)=>{
+                //没有配置文件时全部放行
+                return 0;
+            }
+        };
+        let mut count=0;
+        for (line_number,line) in BufReader::new(file).lines().enumerate(){
+            let line=match line {
+                Ok(line)=>line,
+                Err(_)=>break,
+            };
+            let line=line.split('#').next().unwrap_or("");
+            let fields:Vec<&str>=line.split_whitespace().collect();
+            if fields.is_empty(){
+                continue;
+            }
+            if fields[0]=="policy"{
+                let policy=match fields.len() {
+                    3=>Hook::from_name(fields[1]).zip(FilterAction::from_name(fields[2])),
+                    _=>None,
+                };
+                match policy {
+                    Some((hook,action))=>self.set_policy(hook,action),
+                    None=>println!("过滤策略格式错误！{file_path}:{}",line_number+1),
+                }
+                continue;
+            }
+            match FilterRule::parse(&fields) {
+                Some(rule)=>{
+                    self.add_rule(rule);
+                    count+=1;
+                }
+                None=>{
+                    println!("过滤规则格式错误！{file_path}:{}",line_number+1);
+                }
+            }
+        }
+        count
+    }
+    /// ### 功能
+    /// 在挂载点检查分组，并累加匹配规则的计数
+    /// ### 返回值
+    /// 第一条匹配规则的动作，都不匹配时为默认策略
+    pub fn check(&mut self,hook:Hook,packet:&FilterPacket)->FilterAction{
+        for rule in self.rules.iter_mut().filter(|rule|rule.hook==hook){
+            if rule.matches(packet){
+                rule.packets+=1;
+                rule.bytes+=packet.length as u64;
+                return rule.action;
+            }
+        }
+        let policy=&mut self.policies[hook_index(hook)];
+        policy.1+=1;
+        policy.0
+    }
+    /// ### 功能
+    /// 打印规则与计数
+    pub fn print(&self){
+        for (hook,(action,packets)) in HOOKS.iter().zip(self.policies.iter()){
+            println!("{:?}：默认策略{:?}（{}个分组）",hook,action,packets);
+            for rule in self.rules.iter().filter(|rule|rule.hook==*hook){
+                println!("  {:<8}{:>8}个分组{:>10}字节  {}",format!("{:?}",rule.action),rule.packets,rule.bytes,describe(rule));
+            }
+        }
+    }
+}
+
+/// ### 功能
+/// 拒绝分组：回送ICMP目的不可达报文（通信被管理性禁止）
+pub fn reject(shared_ip_send_queue:&Arc<Mutex<IpSendQueue>>,packet:&[u8]){
+    send_error(shared_ip_send_queue,ICMP_DESTINATION_UNREACHABLE,REJECT_CODE,0,packet);
+}
+
+fn hook_index(hook:Hook)->usize{
+    HOOKS.iter().position(|h|*h==hook).unwrap()
+}
+
+/// ### 功能
+/// 把规则的条件格式化为与规则文件相同的形式
+fn describe(rule:&FilterRule)->String{
+    let mut conditions:Vec<String>=Vec::new();
+    if let Some(interface)=rule.in_interface{
+        conditions.push(format!("in {interface}"));
+    }
+    if let Some(interface)=rule.out_interface{
+        conditions.push(format!("out {interface}"));
+    }
+    if let Some(mac)=rule.source_mac{
+        conditions.push(format!("mac {}",format_mac(mac)));
+    }
+    if let Some(ether_type)=rule.ether_type{
+        conditions.push(format!("type {:#06x}",ether_type));
+    }
+    if let Some((network,netmask))=rule.source{
+        conditions.push(format!("src {}/{}",format_ip(network),u32::from_be_bytes(netmask).count_ones()));
+    }
+    if let Some((network,netmask))=rule.destination{
+        conditions.push(format!("dst {}/{}",format_ip(network),u32::from_be_bytes(netmask).count_ones()));
+    }
+    if let Some(protocol)=rule.protocol{
+        conditions.push(format!("proto {protocol}"));
+    }
+    if let Some(icmp_type)=rule.icmp_type{
+        conditions.push(format!("icmp-type {icmp_type}"));
+    }
+    if let Some((min,max))=rule.source_port{
+        conditions.push(format!("sport {min}-{max}"));
+    }
+    if let Some((min,max))=rule.destination_port{
+        conditions.push(format!("dport {min}-{max}"));
+    }
+    if let Some(states)=&rule.states{
+        let states:Vec<String>=states.iter().map(|state|format!("{:?}",state).to_ascii_uppercase()).collect();
+        conditions.push(format!("state {}",states.join(",")));
+    }
+    conditions.join(" ")
+}
@@ -1 +1,2 @@
-pub mod receive;
\ No newline at end of file
+pub mod receive;
+pub mod send;
\ No newline at end of file
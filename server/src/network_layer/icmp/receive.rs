@@ -3,6 +3,14 @@ use std::sync::{Arc,Mutex};
 use std::thread::yield_now;
 use lazy_static::*;
 
+use crate::data_link_layer::interface::InterfaceTable;
+use crate::network_layer::ip::header::{header_length,parse_header_options,MIN_HEADER_LENGTH};
+use crate::network_layer::ip::option::IpOption;
+use crate::network_layer::ip::pmtu::PathMtuCache;
+use crate::network_layer::ip::protocol::{PROTOCOL_GRE,PROTOCOL_ICMP,PROTOCOL_IGMP,PROTOCOL_IPIP,PROTOCOL_UDP};
+use crate::tools::address::format_ip;
+use crate::tools::checksum::internet_checksum;
+
 
 lazy_static!{
     ///静态变量--ARP的发送队列
@@ -20,7 +28,7 @@ impl IcmpReceiveQueue{
             let new_send_queue=VecDeque::new();
             IcmpReceiveQueue(new_send_queue)
         }
-        /// 由ipv4协议写入
+        /// 由ipv4协议写入，为包含首部的完整分组
         pub fn add_data(&mut self,data: Vec<u8>) -> bool{
             self.0.push_back(data.clone());
             true
@@ -36,34 +44,35 @@ impl IcmpReceiveQueue{
         }
 }
 
-pub struct icmp_header{
+pub struct IcmpHeader{
     icmp_type:[u8;1],
     code:[u8;1],
     check_sum:[u8;2],
     other:[u8;4]
 }
 
-impl icmp_header {
+impl IcmpHeader {
     /// ### 功能
     /// 计算首部校验和时使用，把首部转化为2字节的数组（30*2）
-    pub fn into_u16_array(&self) -> Vec<u16> {
-        let mut result:Vec<u16>=Vec::new();
-        result.push((self.icmp_type[0] as u16) << 8 | self.code[0] as u16);
-        result.push((self.check_sum[0] as u16) << 8 | self.check_sum[1] as u16);
-        result.push((self.other[0] as u16)<<8|(self.other[1] as u16));
-        result.push((self.other[2] as u16)<<8|(self.other[3] as u16));
-        result
+    pub fn to_u16_array(&self) -> Vec<u16> {
+        vec![
+            (self.icmp_type[0] as u16) << 8 | self.code[0] as u16,
+            (self.check_sum[0] as u16) << 8 | self.check_sum[1] as u16,
+            (self.other[0] as u16)<<8|(self.other[1] as u16),
+            (self.other[2] as u16)<<8|(self.other[3] as u16),
+        ]
     }
     /// ### 功能
     /// 根据所给值生成头部，并自动计算首部校验和
     /// ### 返回值
     /// 计算过首部校验和的头部
+    #[allow(dead_code)]
     pub fn new (
         icmp_type:u8,
         code:u8,
         other:u32
-    )-> icmp_header{
-        let mut hdr=icmp_header{
+    )-> IcmpHeader{
+        let mut hdr=IcmpHeader{
             icmp_type:[0;1],
             code:[0;1],
             check_sum:[0;2],
@@ -76,8 +85,8 @@ impl icmp_header {
         hdr
     }
 
-    pub fn from_vec_u8(v:Vec<u8>)->icmp_header{
-        let mut hdr=icmp_header{
+    pub fn from_vec_u8(v:Vec<u8>)->IcmpHeader{
+        let mut hdr=IcmpHeader{
             icmp_type:[0;1],
             code:[0;1],
             check_sum:[0;2],
@@ -86,7 +95,7 @@ impl icmp_header {
         hdr.icmp_type[0]=v[0];
         hdr.code[0]=v[1];
         hdr.check_sum=v[2..4].try_into().unwrap();
-        hdr.other=v[5..9].try_into().unwrap();
+        hdr.other=v[4..8].try_into().unwrap();
         hdr
     }
 
@@ -95,19 +104,19 @@ impl icmp_header {
     pub fn calculate_check_sum(&self)-> u16{
         let mut sum:u32=0;
         let len:usize=8;
-        let hdr=self.into_u16_array();
+        let hdr=self.to_u16_array();
     
-        for i in 0..hdr.len()-1{
-            sum=sum+hdr[i] as u32;
+        for value in &hdr[..hdr.len()-1]{
+            sum+=*value as u32;
         }
     
         //如果最后剩了一字节
         if len%2==1{
             //那么只需要加最后一个[u16]的高8位即可
-            sum=sum+ ( (hdr[hdr.len()-1]>>8) & 0x00ff )as u32;
+            sum+=( (hdr[hdr.len()-1]>>8) & 0x00ff )as u32;
         }
         else{
-            sum=sum+hdr[hdr.len()-1]as u32;
+            sum+=hdr[hdr.len()-1]as u32;
         }
         
         //压缩32位到16位
@@ -121,17 +130,60 @@ impl icmp_header {
 }
 
 
-pub fn receive(shared_icmp_receive_queue:Arc<Mutex<IcmpReceiveQueue>>){
+/// ### 功能
+/// 需要分片报文引用的分组是否确实由本机发出：源地址是本机地址，且协议是本机会发送的协议。
+/// 用于防止伪造的报文降低路径MTU
+fn quotes_own_packet(interface_table:&InterfaceTable,original:&[u8])->bool{
+    let source_ip:[u8;4]=original[12..16].try_into().unwrap();
+    interface_table.is_local_ip(source_ip)
+        && matches!(original[9],PROTOCOL_ICMP|PROTOCOL_IGMP|PROTOCOL_IPIP|PROTOCOL_UDP|PROTOCOL_GRE)
+}
+
+/// ### 功能
+/// 打印回送回答带回的记录路由、时间戳选项
+fn print_options(packet:&[u8]){
+    for option in parse_header_options(packet).unwrap_or_default(){
+        match option {
+            IpOption::RecordRoute{route,..}=>{
+                let route:Vec<String>=route.into_iter().filter(|address|*address!=[0;4]).map(format_ip).collect();
+                println!("记录的路由：{}",route.join(" "));
+            }
+            IpOption::Timestamp{overflow,entries,..}=>{
+                let entries:Vec<String>=entries.into_iter()
+                    .filter(|entry|entry.timestamp!=0)
+                    .map(|entry|match entry.address {
+                        Some(address)=>format!("{}@{}",format_ip(address),entry.timestamp),
+                        None=>entry.timestamp.to_string(),
+                    })
+                    .collect();
+                println!("时间戳（毫秒）：{}，溢出{}次",entries.join(" "),overflow);
+            }
+            _=>{}
+        }
+    }
+}
+
+pub fn receive(
+    shared_icmp_receive_queue:Arc<Mutex<IcmpReceiveQueue>>,
+    shared_path_mtu_cache:Arc<Mutex<PathMtuCache>>,
+    shared_interface_table:Arc<Mutex<InterfaceTable>>
+){
     loop{
         let mut receive_queue=shared_icmp_receive_queue.lock().unwrap();
         if receive_queue.is_empty(){
+            drop(receive_queue);
             yield_now();
             continue;
         }
 
-        let data=receive_queue.get_data().unwrap();
+        let packet=receive_queue.get_data().unwrap();
+        drop(receive_queue);
+        let data=packet[header_length(&packet)..].to_vec();
+        if data.len()<8{
+            continue;
+        }
 
-        let hdr=icmp_header::from_vec_u8(data);
+        let hdr=IcmpHeader::from_vec_u8(data.clone());
 
         if hdr.icmp_type[0]==11{
             println!("接收到ICMP超时报文！");
@@ -141,7 +193,28 @@ pub fn receive(shared_icmp_receive_queue:Arc<Mutex<IcmpReceiveQueue>>){
         }
         else if  hdr.icmp_type[0]==0 {
             println!("接收到ICMP回送回答报文！");
+            print_options(&packet);
+        }
+        else if hdr.icmp_type[0]==3 && hdr.code[0]==4{
+            //需要分片：后4字节的低16位为下一跳MTU，数据部分为原分组的首部
+            let original=&data[8..];
+            if original.len()<MIN_HEADER_LENGTH || original.len()<header_length(original){
+                continue;
+            }
+            //校验和错误、或引用的分组不是本机发出的，不更新路径MTU
+            if internet_checksum(&data)!=0 || !quotes_own_packet(&shared_interface_table.lock().unwrap(),original){
+                println!("丢弃无效的ICMP需要分片报文");
+                continue;
+            }
+            let next_hop_mtu=u16::from_be_bytes([hdr.other[2],hdr.other[3]]) as usize;
+            let original_total_length=u16::from_be_bytes([original[2],original[3]]) as usize;
+            let destination_ip:[u8;4]=original[16..20].try_into().unwrap();
+            println!("接收到ICMP需要分片报文！");
+            shared_path_mtu_cache.lock().unwrap().update(destination_ip,next_hop_mtu,original_total_length);
+        }
+        else if hdr.icmp_type[0]==3{
+            println!("接收到ICMP目的不可达报文！code={}",hdr.code[0]);
         }
         
     }
-}
\ No newline at end of file
+}
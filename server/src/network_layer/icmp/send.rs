@@ -0,0 +1,92 @@
+use std::sync::{Arc,Mutex};
+
+use crate::network_layer::ip::header::{header_length,FRAGMENT_OFFSET_MASK};
+use crate::network_layer::ip::option::IpOption;
+use crate::network_layer::ip::send::{IpSendQueue,IpSendQueueElement,DSCP_CS6};
+use crate::tools::checksum::internet_checksum;
+
+/// 上层协议字段-ICMPV4
+const ICMPV4_PROTOCOL:u8=1;
+
+/// ICMP类型-目的不可达
+pub const ICMP_DESTINATION_UNREACHABLE:u8=3;
+/// ICMP类型-超时
+pub const ICMP_TIME_EXCEEDED:u8=11;
+/// ICMP类型-参数问题
+pub const ICMP_PARAMETER_PROBLEM:u8=12;
+/// ICMP类型-回送请求
+pub const ICMP_ECHO_REQUEST:u8=8;
+/// 回送请求的数据部分长度
+const ECHO_DATA_LENGTH:usize=32;
+
+/// ### 功能
+/// 针对一个出错的IP分组，生成ICMP差错报文并交给IP层发回其源地址。
+/// 报文数据部分为原分组的首部加上数据的前8字节。
+/// 按RFC 1122，不对ICMP差错报文、非首个分片以及源地址为0或广播的分组产生差错报文。
+/// 报文的DSCP为CS6（网络控制）
+/// ### 参数
+/// other为ICMP首部的后4字节，例如"需要分片"报文在其中携带下一跳MTU
+/// ### 返回值
+/// 是否产生了差错报文
+pub fn send_error(
+    shared_ip_send_queue:&Arc<Mutex<IpSendQueue>>,
+    icmp_type:u8,
+    code:u8,
+    other:u32,
+    original:&[u8]
+)->bool{
+    if original.len()<20{
+        return false;
+    }
+    let header_len=header_length(original);
+    let source_ip:[u8;4]=original[12..16].try_into().unwrap();
+    if source_ip==[0;4] || source_ip==[255;4]{
+        return false;
+    }
+    //非首个分片
+    if u16::from_be_bytes([original[6],original[7]]) & FRAGMENT_OFFSET_MASK!=0{
+        return false;
+    }
+    //ICMP差错报文
+    if original[9]==ICMPV4_PROTOCOL && original.len()>header_len && matches!(original[header_len],3|4|5|11|12){
+        return false;
+    }
+
+    let mut message:Vec<u8>=Vec::new();
+    message.push(icmp_type);
+    message.push(code);
+    //校验和先置0
+    message.extend_from_slice(&[0,0]);
+    message.extend_from_slice(&other.to_be_bytes());
+    let quoted_len=(header_len+8).min(original.len());
+    message.extend_from_slice(&original[0..quoted_len]);
+    let check_sum=internet_checksum(&message);
+    message[2..4].copy_from_slice(&check_sum.to_be_bytes());
+
+    let mut element=IpSendQueueElement::new(source_ip,ICMPV4_PROTOCOL,message);
+    element.set_dscp(DSCP_CS6);
+    shared_ip_send_queue.lock().unwrap().add_element(element);
+    true
+}
+
+/// ### 功能
+/// 向destination_ip发送一个ICMP回送请求。options为IP首部携带的选项，
+/// 例如记录路由、时间戳选项，回送回答会带回途经的路由器记录的内容
+pub fn send_echo_request(
+    shared_ip_send_queue:&Arc<Mutex<IpSendQueue>>,
+    destination_ip:[u8;4],
+    identifier:u16,
+    sequence_number:u16,
+    options:Vec<IpOption>
+){
+    let mut message:Vec<u8>=vec![ICMP_ECHO_REQUEST,0,0,0];
+    message.extend_from_slice(&identifier.to_be_bytes());
+    message.extend_from_slice(&sequence_number.to_be_bytes());
+    message.extend((0..ECHO_DATA_LENGTH).map(|i|b'a'+(i%26) as u8));
+    let check_sum=internet_checksum(&message);
+    message[2..4].copy_from_slice(&check_sum.to_be_bytes());
+
+    let mut element=IpSendQueueElement::new(destination_ip,ICMPV4_PROTOCOL,message);
+    element.options=options;
+    shared_ip_send_queue.lock().unwrap().add_element(element);
+}
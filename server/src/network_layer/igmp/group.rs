@@ -0,0 +1,146 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher,Hasher};
+use std::sync::{Arc,Mutex};
+use std::time::{Duration,Instant};
+use lazy_static::*;
+
+use crate::tools::address::{format_ip,is_multicast_ip,multicast_mac};
+
+lazy_static!{
+    ///静态变量--本机加入的组播组，IP与以太网接收按此过滤组播
+    pub static ref MULTICAST_GROUP_TABLE:Arc<Mutex<MulticastGroupTable>> = Arc::new(Mutex::new(MulticastGroupTable::new()));
+}
+
+/// 所有主机组，每个接口都默认加入，不发送报告
+pub const ALL_HOSTS_GROUP:[u8;4]=[224,0,0,1];
+/// 所有路由器组，离开报告发往此组
+pub const ALL_ROUTERS_GROUP:[u8;4]=[224,0,0,2];
+/// 加入组后重复发送成员报告的最大延时（RFC 2236的Unsolicited Report Interval）
+pub const UNSOLICITED_REPORT_INTERVAL:Duration=Duration::from_secs(10);
+
+/// 一个接口上的组成员关系
+struct GroupMembership{
+    interface:usize,
+    group:[u8;4],
+    /// 计划发送成员报告的时间，None表示没有待发送的报告
+    report_time:Option<Instant>,
+    /// 最近一次成员报告是否由本机发送，离开时只有本机是最后的报告者才发送离开报告
+    last_reporter:bool,
+}
+
+/// ## 组播组表
+/// 记录每个接口加入的组，以及IGMPv2的报告计时器（RFC 2236）：
+/// - 收到查询后在最大响应时间内随机延时发送报告
+/// - 延时期间听到其他主机的报告则取消本机的报告
+pub struct MulticastGroupTable{
+    inner:Vec<GroupMembership>
+}
+
+impl MulticastGroupTable{
+    /// ### 功能
+    /// 新建空的组表
+    pub fn new()->MulticastGroupTable{
+        MulticastGroupTable{
+            inner:Vec::new()
+        }
+    }
+    /// ### 功能
+    /// 在接口上加入组，并计划一次重复的成员报告
+    /// ### 返回值
+    /// 是否新加入，不是组播地址、是所有主机组或已经加入时返回false
+    pub fn join(&mut self,interface:usize,group:[u8;4])->bool{
+        if !is_multicast_ip(group) || group==ALL_HOSTS_GROUP || self.position(interface,group).is_some(){
+            return false;
+        }
+        self.inner.push(GroupMembership{
+            interface,
+            group,
+            report_time:Some(Instant::now()+random_delay(UNSOLICITED_REPORT_INTERVAL)),
+            last_reporter:true,
+        });
+        true
+    }
+    /// ### 功能
+    /// 在接口上离开组
+    /// ### 返回值
+    /// Option，没有加入该组时返回None，否则为本机是否是最后的报告者
+    pub fn leave(&mut self,interface:usize,group:[u8;4])->Option<bool>{
+        let index=self.position(interface,group)?;
+        Some(self.inner.remove(index).last_reporter)
+    }
+    /// ### 功能
+    /// 接口是否加入了组，所有主机组总是加入的
+    pub fn is_member(&self,interface:usize,group:[u8;4])->bool{
+        group==ALL_HOSTS_GROUP || self.position(interface,group).is_some()
+    }
+    /// ### 功能
+    /// 接口是否接收发往该组播MAC地址的帧
+    pub fn accepts_mac(&self,interface:usize,mac:[u8;6])->bool{
+        mac==multicast_mac(ALL_HOSTS_GROUP)
+            || self.inner.iter().any(|membership|membership.interface==interface && multicast_mac(membership.group)==mac)
+    }
+    /// ### 功能
+    /// 收到查询：group为0时是通用查询，对接口上的所有组计划报告；否则是特定组查询。
+    /// 在最大响应时间内随机选择发送时间，已经计划了更早的报告时保持不变
+    pub fn query_received(&mut self,interface:usize,group:[u8;4],max_response_time:Duration){
+        let now=Instant::now();
+        for membership in self.inner.iter_mut(){
+            if membership.interface!=interface || (group!=[0;4] && membership.group!=group){
+                continue;
+            }
+            let report_time=now+random_delay(max_response_time);
+            match membership.report_time {
+                Some(time) if time<=report_time=>{}
+                _=>membership.report_time=Some(report_time),
+            }
+        }
+    }
+    /// ### 功能
+    /// 听到其他主机对该组的报告，取消本机待发送的报告
+    pub fn report_heard(&mut self,interface:usize,group:[u8;4]){
+        if let Some(index)=self.position(interface,group){
+            let membership=&mut self.inner[index];
+            membership.report_time=None;
+            membership.last_reporter=false;
+        }
+    }
+    /// ### 功能
+    /// 取出已经到时间的报告
+    /// ### 返回值
+    /// 需要发送成员报告的(接口，组)
+    pub fn due_reports(&mut self)->Vec<(usize,[u8;4])>{
+        let now=Instant::now();
+        let mut reports=Vec::new();
+        for membership in self.inner.iter_mut(){
+            if membership.report_time.is_some_and(|time|time<=now){
+                membership.report_time=None;
+                membership.last_reporter=true;
+                reports.push((membership.interface,membership.group));
+            }
+        }
+        reports
+    }
+    /// ### 功能
+    /// 打印组表
+    pub fn print(&self){
+        println!("{:<8}{:<16}最后报告者","接口","组");
+        for membership in &self.inner{
+            println!("{:<8}{:<16}{}",membership.interface,format_ip(membership.group),membership.last_reporter);
+        }
+    }
+
+    fn position(&self,interface:usize,group:[u8;4])->Option<usize>{
+        self.inner.iter().position(|membership|membership.interface==interface && membership.group==group)
+    }
+}
+
+/// ### 功能
+/// 生成[0,max)内的随机延时
+fn random_delay(max:Duration)->Duration{
+    let max_millis=max.as_millis() as u64;
+    if max_millis==0{
+        return Duration::ZERO;
+    }
+    //RandomState每次创建时使用不同的随机种子
+    Duration::from_millis(RandomState::new().build_hasher().finish() % max_millis)
+}
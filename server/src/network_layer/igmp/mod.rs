@@ -0,0 +1,3 @@
+pub mod group;
+pub mod receive;
+pub mod send;
\ No newline at end of file
@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+use std::sync::{Arc,Mutex};
+use std::thread::yield_now;
+use std::time::Duration;
+use lazy_static::*;
+
+use crate::data_link_layer::interface::InterfaceTable;
+use crate::network_layer::ip::header::header_length;
+use crate::network_layer::ip::send::IpSendQueue;
+use crate::tools::checksum::internet_checksum;
+
+use super::group::MulticastGroupTable;
+use super::send::*;
+
+lazy_static!{
+    ///静态变量--IGMP的接收队列
+    pub static ref IGMP_RECEIVE_QUEUE:Arc<Mutex<IgmpReceiveQueue>> = Arc::new(Mutex::new(IgmpReceiveQueue::new()));
+}
+
+/// IGMPv1查询没有最大响应时间，按10秒处理
+const V1_MAX_RESPONSE_TIME:Duration=Duration::from_secs(10);
+
+///IGMP的接收队列，元素为(收到该分组的接口，完整的IP分组)
+pub struct IgmpReceiveQueue(
+    VecDeque<(usize,Vec<u8>)>
+);
+
+impl IgmpReceiveQueue{
+    ///生成接收队列
+    pub fn new() -> Self{
+        let new_receive_queue=VecDeque::new();
+        IgmpReceiveQueue(new_receive_queue)
+    }
+    /// 由IP协议写入
+    pub fn add_data(&mut self,interface:usize,packet:Vec<u8>) -> bool{
+        self.0.push_back((interface,packet));
+        true
+    }
+    /// 获取队列数据
+    pub fn get_data(&mut self)-> Option<(usize,Vec<u8>)>{
+        self.0.pop_front()
+    }
+}
+
+///### 功能
+/// IGMPv2主机（RFC 2236）：
+/// - 收到通用查询或特定组查询后，在最大响应时间内随机延时发送成员报告
+/// - 听到其他主机的成员报告后取消本机的报告
+/// - 发送到时间的报告
+/// - 处理应用程序加入、离开组的请求
+pub fn receive(
+    shared_interface_table:Arc<Mutex<InterfaceTable>>,
+    shared_multicast_group_table:Arc<Mutex<MulticastGroupTable>>,
+    shared_igmp_receive_queue:Arc<Mutex<IgmpReceiveQueue>>,
+    shared_igmp_membership_queue:Arc<Mutex<MembershipRequestQueue>>,
+    shared_ip_send_queue:Arc<Mutex<IpSendQueue>>
+){
+    loop{
+        let request=shared_igmp_membership_queue.lock().unwrap().get_data();
+        match request {
+            Some(MembershipRequest::Join(interface,group))=>{
+                join_group(&shared_interface_table,&shared_multicast_group_table,&shared_ip_send_queue,interface,group);
+            }
+            Some(MembershipRequest::Leave(interface,group))=>{
+                leave_group(&shared_interface_table,&shared_multicast_group_table,&shared_ip_send_queue,interface,group);
+            }
+            None=>{}
+        }
+
+        let reports=shared_multicast_group_table.lock().unwrap().due_reports();
+        for (interface,group) in reports{
+            if let Some(interface)=shared_interface_table.lock().unwrap().get(interface){
+                send_report(&shared_ip_send_queue,interface.ip,group);
+            }
+        }
+
+        let data=shared_igmp_receive_queue.lock().unwrap().get_data();
+        let (interface,packet)=match data {
+            Some(data)=>data,
+            None=>{
+                yield_now();
+                continue;
+            }
+        };
+        let message=&packet[header_length(&packet)..];
+        if message.len()<8 || internet_checksum(message)!=0{
+            continue;
+        }
+        let group:[u8;4]=message[4..8].try_into().unwrap();
+        match message[0] {
+            IGMP_MEMBERSHIP_QUERY=>{
+                //最大响应时间的单位为0.1秒，为0时是IGMPv1查询
+                let max_response_time=if message[1]==0 {
+                    V1_MAX_RESPONSE_TIME
+                }
+                else{
+                    Duration::from_millis(message[1] as u64*100)
+                };
+                shared_multicast_group_table.lock().unwrap().query_received(interface,group,max_response_time);
+            }
+            IGMP_V1_MEMBERSHIP_REPORT|IGMP_V2_MEMBERSHIP_REPORT=>{
+                shared_multicast_group_table.lock().unwrap().report_heard(interface,group);
+            }
+            _=>{}
+        }
+    }
+}
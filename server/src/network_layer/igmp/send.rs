@@ -0,0 +1,159 @@
+use std::collections::VecDeque;
+use std::sync::{Arc,Mutex};
+use lazy_static::*;
+
+use crate::data_link_layer::interface::InterfaceTable;
+use crate::network_layer::ip::option::IpOption;
+use crate::network_layer::ip::protocol::PROTOCOL_IGMP;
+use crate::network_layer::ip::send::{IpSendQueue,IpSendQueueElement,DSCP_CS6};
+use crate::tools::address::{format_ip,is_multicast_ip,parse_ip};
+use crate::tools::checksum::internet_checksum;
+
+use super::group::{MulticastGroupTable,ALL_ROUTERS_GROUP};
+
+lazy_static!{
+    ///静态变量--应用程序加入、离开组播组的请求队列，由IGMP线程处理
+    pub static ref IGMP_MEMBERSHIP_QUEUE:Arc<Mutex<MembershipRequestQueue>> = Arc::new(Mutex::new(MembershipRequestQueue::new()));
+}
+
+/// IGMP类型-成员查询
+pub const IGMP_MEMBERSHIP_QUERY:u8=0x11;
+/// IGMP类型-IGMPv1成员报告
+pub const IGMP_V1_MEMBERSHIP_REPORT:u8=0x12;
+/// IGMP类型-IGMPv2成员报告
+pub const IGMP_V2_MEMBERSHIP_REPORT:u8=0x16;
+/// IGMP类型-离开组
+pub const IGMP_LEAVE_GROUP:u8=0x17;
+
+/// 应用程序对组成员关系的请求：(接口，组播地址)
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum MembershipRequest{
+    Join(usize,[u8;4]),
+    Leave(usize,[u8;4]),
+}
+
+///组成员关系的请求队列。应用程序（如接收组播UDP的程序）写入，IGMP线程取出后加入或离开组并发送报告
+pub struct MembershipRequestQueue(
+    VecDeque<MembershipRequest>
+);
+
+impl MembershipRequestQueue{
+    ///生成请求队列
+    pub fn new() -> Self{
+        let new_request_queue=VecDeque::new();
+        MembershipRequestQueue(new_request_queue)
+    }
+    /// 请求在接口上加入组
+    pub fn join(&mut self,interface:usize,group:[u8;4]){
+        self.0.push_back(MembershipRequest::Join(interface,group));
+    }
+    /// 请求在接口上离开组
+    pub fn leave(&mut self,interface:usize,group:[u8;4]){
+        self.0.push_back(MembershipRequest::Leave(interface,group));
+    }
+    /// 获取队列数据
+    pub fn get_data(&mut self)-> Option<MembershipRequest>{
+        self.0.pop_front()
+    }
+}
+
+/// ### 功能
+/// 执行应用程序加入、离开组播组的请求，格式为"join|leave 接口 组播地址"，写入请求队列
+/// ### 返回值
+/// 请求格式是否正确
+pub fn request_membership(shared_igmp_membership_queue:&Arc<Mutex<MembershipRequestQueue>>,fields:&[&str])->bool{
+    let request=match fields {
+        [command,interface,group]=>interface.parse::<usize>().ok()
+            .zip(parse_ip(group).filter(|group|is_multicast_ip(*group)))
+            .map(|(interface,group)|(*command,interface,group)),
+        _=>None,
+    };
+    let mut membership_queue=shared_igmp_membership_queue.lock().unwrap();
+    match request {
+        Some(("join",interface,group))=>membership_queue.join(interface,group),
+        Some(("leave",interface,group))=>membership_queue.leave(interface,group),
+        _=>return false,
+    }
+    true
+}
+
+/// ### 功能
+/// 发送一个IGMP报文：TTL为1，带路由器警告选项（RFC 2236），DSCP为CS6
+fn send_message(
+    shared_ip_send_queue:&Arc<Mutex<IpSendQueue>>,
+    source_ip:[u8;4],
+    destination_ip:[u8;4],
+    igmp_type:u8,
+    group:[u8;4]
+){
+    let mut message:Vec<u8>=vec![igmp_type,0,0,0];
+    message.extend_from_slice(&group);
+    let check_sum=internet_checksum(&message);
+    message[2..4].copy_from_slice(&check_sum.to_be_bytes());
+
+    let mut element=IpSendQueueElement::new(destination_ip,PROTOCOL_IGMP,message);
+    element.source_ip=Some(source_ip);
+    element.time_to_live=1;
+    element.set_dscp(DSCP_CS6);
+    element.options=vec![IpOption::RouterAlert(0)];
+    shared_ip_send_queue.lock().unwrap().add_element(element);
+}
+
+/// ### 功能
+/// 发送成员报告，目的地址为组本身
+pub fn send_report(shared_ip_send_queue:&Arc<Mutex<IpSendQueue>>,source_ip:[u8;4],group:[u8;4]){
+    send_message(shared_ip_send_queue,source_ip,group,IGMP_V2_MEMBERSHIP_REPORT,group);
+}
+
+/// ### 功能
+/// 发送离开报告，目的地址为所有路由器组
+pub fn send_leave(shared_ip_send_queue:&Arc<Mutex<IpSendQueue>>,source_ip:[u8;4],group:[u8;4]){
+    send_message(shared_ip_send_queue,source_ip,ALL_ROUTERS_GROUP,IGMP_LEAVE_GROUP,group);
+}
+
+/// ### 功能
+/// 在接口上加入组播组，立即发送一次成员报告，稍后再重复一次
+/// ### 返回值
+/// 是否新加入
+pub fn join_group(
+    shared_interface_table:&Arc<Mutex<InterfaceTable>>,
+    shared_multicast_group_table:&Arc<Mutex<MulticastGroupTable>>,
+    shared_ip_send_queue:&Arc<Mutex<IpSendQueue>>,
+    interface:usize,
+    group:[u8;4]
+)->bool{
+    let source_ip=match shared_interface_table.lock().unwrap().get(interface) {
+        Some(interface)=>interface.ip,
+        None=>return false,
+    };
+    if !shared_multicast_group_table.lock().unwrap().join(interface,group){
+        return false;
+    }
+    println!("接口{}加入组播组{}",interface,format_ip(group));
+    send_report(shared_ip_send_queue,source_ip,group);
+    true
+}
+
+/// ### 功能
+/// 在接口上离开组播组，本机是最后的报告者时发送离开报告
+/// ### 返回值
+/// 是否离开，没有加入该组时返回false
+pub fn leave_group(
+    shared_interface_table:&Arc<Mutex<InterfaceTable>>,
+    shared_multicast_group_table:&Arc<Mutex<MulticastGroupTable>>,
+    shared_ip_send_queue:&Arc<Mutex<IpSendQueue>>,
+    interface:usize,
+    group:[u8;4]
+)->bool{
+    let last_reporter=match shared_multicast_group_table.lock().unwrap().leave(interface,group) {
+        Some(last_reporter)=>last_reporter,
+        None=>return false,
+    };
+    println!("接口{}离开组播组{}",interface,format_ip(group));
+    if last_reporter{
+        if let Some(interface)=shared_interface_table.lock().unwrap().get(interface){
+            send_leave(shared_ip_send_queue,interface.ip,group);
+        }
+    }
+    true
+}
@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+use std::sync::{Arc,Mutex};
+use std::thread::yield_now;
+use lazy_static::*;
+
+use crate::data_link_layer::interface::InterfaceTable;
+use crate::network_layer::filter::conntrack::ConnectionTable;
+use crate::network_layer::filter::rule::{FilterAction,FilterPacket,Hook};
+use crate::network_layer::filter::table::{reject,FilterTable};
+use crate::network_layer::icmp::send::{send_error,ICMP_DESTINATION_UNREACHABLE,ICMP_PARAMETER_PROBLEM,ICMP_TIME_EXCEEDED};
+use crate::tools::address::format_ip;
+
+use super::nat::NatTable;
+use super::header::{fragment,parse_header_options,replace_header_options,update_check_sum};
+use super::option::IpOption;
+use super::route::RoutingTable;
+use super::send::{IpOutputQueue,IpSendQueue};
+
+lazy_static!{
+    ///静态变量--IP转发队列，目的地址不是本机的分组由接收端写入
+    pub static ref IP_FORWARD_QUEUE:Arc<Mutex<IpForwardQueue>> = Arc::new(Mutex::new(IpForwardQueue::new()));
+}
+
+///IP转发队列，元素为(入接口，分组)
+pub struct IpForwardQueue(
+    VecDeque<(usize,Vec<u8>)>
+);
+
+impl IpForwardQueue{
+    /// 生成转发队列
+    pub fn new() -> Self{
+        let new_forward_queue=VecDeque::new();
+        IpForwardQueue(new_forward_queue)
+    }
+    /// 由ip接收端写入
+    pub fn add_data(&mut self,interface:usize,packet:Vec<u8>) -> bool{
+        self.0.push_back((interface,packet));
+        true
+    }
+    /// 获取队列数据
+    pub fn get_data(&mut self)-> Option<(usize,Vec<u8>)>{
+        self.0.pop_front()
+    }
+}
+
+/// ### 功能
+/// 转发IP分组（路由器模式）：
+/// 1. TTL减1，减到0则丢弃并回送ICMP超时报文
+/// 2. 按路由表选择出接口与下一跳，没有路由则回送ICMP网络不可达报文
+/// 3. 按过滤规则丢弃或拒绝
+/// 4. 在记录路由、时间戳选项中记录出接口地址；严格源路由的下一跳不是直连时回送ICMP源路由失败报文
+/// 5. 重新计算首部校验和
+/// 6. 按NAT规则改写源地址与源端口
+/// 7. 超过出接口的MTU时分片（只有复制标志置位的选项进入后续分片）；DF置位时丢弃并回送ICMP需要分片报文
+#[allow(clippy::too_many_arguments)]
+pub fn forward(
+    shared_interface_table:Arc<Mutex<InterfaceTable>>,
+    shared_routing_table:Arc<Mutex<RoutingTable>>,
+    shared_ip_forward_queue:Arc<Mutex<IpForwardQueue>>,
+    shared_ip_output_queue:Arc<Mutex<IpOutputQueue>>,
+    shared_ip_send_queue:Arc<Mutex<IpSendQueue>>,
+    shared_nat_table:Arc<Mutex<NatTable>>,
+    shared_filter_table:Arc<Mutex<FilterTable>>,
+    shared_connection_table:Arc<Mutex<ConnectionTable>>
+){
+    loop{
+        let element=shared_ip_forward_queue.lock().unwrap().get_data();
+        let (in_interface,mut packet)=match element {
+            Some(element)=>element,
+            None=>{
+                yield_now();
+                continue;
+            }
+        };
+        let destination_ip:[u8;4]=packet[16..20].try_into().unwrap();
+
+        //生存时间
+        let time_to_live=packet[8];
+        if time_to_live<=1{
+            //code=0代表传输中超时
+            send_error(&shared_ip_send_queue,ICMP_TIME_EXCEEDED,0,0,&packet);
+            continue;
+        }
+
+        //查找路由
+        let route=shared_routing_table.lock().unwrap().lookup(destination_ip);
+        let route=match route {
+            Some(route)=>route,
+            None=>{
+                //code=0代表网络不可达
+                send_error(&shared_ip_send_queue,ICMP_DESTINATION_UNREACHABLE,0,0,&packet);
+                continue;
+            }
+        };
+        let next_hop=route.gateway.unwrap_or(destination_ip);
+        let (interface_ip,mtu)=match shared_interface_table.lock().unwrap().get(route.interface) {
+            Some(interface)=>(interface.select_source(destination_ip,next_hop),interface.mtu),
+            None=>continue,
+        };
+
+        //过滤：forward挂载点。先只查询连接状态，通过后才跟踪，被丢弃的分组不建立连接
+        let state=shared_connection_table.lock().unwrap().classify(&packet);
+        let action=shared_filter_table.lock().unwrap().check(Hook::Forward,&FilterPacket{
+            in_interface:Some(in_interface),
+            out_interface:Some(route.interface),
+            frame_header:None,
+            packet:Some(&packet),
+            state:Some(state),
+            length:packet.len(),
+        });
+        match action {
+            FilterAction::Accept=>{
+                shared_connection_table.lock().unwrap().track(&packet);
+            }
+            FilterAction::Drop=>continue,
+            FilterAction::Reject=>{
+                reject(&shared_ip_send_queue,&packet);
+                continue;
+            }
+        }
+
+        //处理选项
+        let mut options=match parse_header_options(&packet) {
+            Some(options)=>options,
+            None=>{
+                //code=0代表指针指示出错位置，这里指向选项部分的起始处
+                send_error(&shared_ip_send_queue,ICMP_PARAMETER_PROBLEM,0,20<<24,&packet);
+                continue;
+            }
+        };
+        if route.gateway.is_some() && options.iter().any(|option|matches!(option,IpOption::StrictSourceRoute{..})){
+            //code=5代表源路由失败
+            send_error(&shared_ip_send_queue,ICMP_DESTINATION_UNREACHABLE,5,0,&packet);
+            continue;
+        }
+        if !options.is_empty(){
+            for option in options.iter_mut(){
+                option.stamp(interface_ip);
+            }
+            if !replace_header_options(&mut packet,&options){
+                //记录后的选项放不下原来的首部，指针指向选项部分的起始处
+                send_error(&shared_ip_send_queue,ICMP_PARAMETER_PROBLEM,0,20<<24,&packet);
+                continue;
+            }
+        }
+
+        packet[8]=time_to_live-1;
+        update_check_sum(&mut packet);
+
+        //源地址转换，之后的差错报文引用转换前的分组
+        let untranslated=packet.clone();
+        if !shared_nat_table.lock().unwrap().translate_outbound(&mut packet,route.interface,interface_ip){
+            continue;
+        }
+
+        match fragment(&packet,mtu) {
+            Some(fragments)=>{
+                let mut output_queue=shared_ip_output_queue.lock().unwrap();
+                for fragment in fragments{
+                    output_queue.add_data(route.interface,next_hop,fragment);
+                }
+            }
+            None=>{
+                //DF置位却需要分片。code=4代表需要分片，后4字节的低16位为下一跳MTU
+                println!("到{}的分组超过MTU {}且不允许分片，丢弃",format_ip(destination_ip),mtu);
+                send_error(&shared_ip_send_queue,ICMP_DESTINATION_UNREACHABLE,4,mtu as u32,&untranslated);
+            }
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use crate::tools::checksum::internet_checksum;
+
+use super::option::{copied_options,parse_options,IpOption};
+
+/// 不带选项的IPv4首部长度
+pub const MIN_HEADER_LENGTH:usize=20;
+/// 标志位-DF，不分片
+pub const FLAG_DF:u16=1<<14;
+/// 标志位-MF，还有更多分片
+pub const FLAG_MF:u16=1<<13;
+/// 片偏移的掩码，单位为8字节
+pub const FRAGMENT_OFFSET_MASK:u16=0b0001_1111_1111_1111;
+
+/// ### 功能
+/// 生成一个IPv4首部，并计算首部校验和。
+/// options为已经编码并补齐到4字节整数倍的选项，首部长度字段由其长度决定
+/// ### 返回值
+/// 首部的字节序列
+#[allow(clippy::too_many_arguments)]
+pub fn build_header(
+    type_of_service:u8,
+    total_length:u16,
+    id:u16,
+    flags_and_fragment_offset:u16,
+    time_to_live:u8,
+    upper_protocol_type:u8,
+    source_ip:[u8;4],
+    destination_ip:[u8;4],
+    options:&[u8]
+)->Vec<u8>{
+    let header_len=MIN_HEADER_LENGTH+options.len();
+    let mut header:Vec<u8>=Vec::with_capacity(header_len);
+    //版本4，首部长度以4字节为单位
+    header.push(0x40 | (header_len/4) as u8);
+    header.push(type_of_service);
+    header.extend_from_slice(&total_length.to_be_bytes());
+    header.extend_from_slice(&id.to_be_bytes());
+    header.extend_from_slice(&flags_and_fragment_offset.to_be_bytes());
+    header.push(time_to_live);
+    header.push(upper_protocol_type);
+    //校验和先置0
+    header.extend_from_slice(&[0,0]);
+    header.extend_from_slice(&source_ip);
+    header.extend_from_slice(&destination_ip);
+    header.extend_from_slice(options);
+    update_check_sum(&mut header);
+    header
+}
+
+/// ### 功能
+/// 分组的首部长度（字节），由IHL字段确定
+pub fn header_length(packet:&[u8])->usize{
+    ((packet[0]&0x0f) as usize)*4
+}
+
+/// ### 功能
+/// 分组首部中的选项部分（含填充）
+pub fn header_options(packet:&[u8])->&[u8]{
+    &packet[MIN_HEADER_LENGTH..header_length(packet)]
+}
+
+/// ### 功能
+/// 解析分组首部中的选项
+/// ### 返回值
+/// Option，选项格式错误时返回None
+pub fn parse_header_options(packet:&[u8])->Option<Vec<IpOption>>{
+    parse_options(header_options(packet))
+}
+
+/// ### 功能
+/// 用修改后的选项（如记录了路由的选项）覆盖分组首部中的选项部分，首部长度不变，
+/// 不足的部分用选项表结束填充。覆盖后需要重新计算校验和
+/// ### 返回值
+/// 是否覆盖成功，编码后的选项比原来长时失败
+pub fn replace_header_options(packet:&mut [u8],options:&[IpOption])->bool{
+    let mut buffer=Vec::new();
+    for option in options{
+        option.encode(&mut buffer);
+    }
+    let header_len=header_length(packet);
+    if MIN_HEADER_LENGTH+buffer.len()>header_len{
+        return false;
+    }
+    buffer.resize(header_len-MIN_HEADER_LENGTH,0);
+    packet[MIN_HEADER_LENGTH..header_len].copy_from_slice(&buffer);
+    true
+}
+
+/// ### 功能
+/// 重新计算分组的首部校验和，修改TTL等字段后需要调用
+pub fn update_check_sum(packet:&mut [u8]){
+    let len=header_length(packet);
+    packet[10..12].copy_from_slice(&[0,0]);
+    let check_sum=internet_checksum(&packet[0..len]);
+    packet[10..12].copy_from_slice(&check_sum.to_be_bytes());
+}
+
+/// ### 功能
+/// 按MTU把一个分组分片。数据部分按8字节对齐切分，原分组本身是分片时保留其偏移与MF标志。
+/// 第一个分片携带全部选项，其余分片只携带复制标志置位的选项
+/// ### 返回值
+/// Option，不需要分片时返回只含原分组的数组；DF置位而又需要分片时返回None
+pub fn fragment(packet:&[u8],mtu:usize)->Option<Vec<Vec<u8>>>{
+    let total_length=u16::from_be_bytes([packet[2],packet[3]]) as usize;
+    let total_length=total_length.min(packet.len());
+    if total_length<=mtu{
+        return Some(vec![packet[0..total_length].to_vec()]);
+    }
+    let flags_and_fragment_offset=u16::from_be_bytes([packet[6],packet[7]]);
+    if flags_and_fragment_offset & FLAG_DF!=0{
+        return None;
+    }
+    let header_len=header_length(packet);
+    let original_offset=flags_and_fragment_offset & FRAGMENT_OFFSET_MASK;
+    let original_more_fragments=flags_and_fragment_offset & FLAG_MF!=0;
+
+    //后续分片的首部：基本首部加上需要复制的选项
+    let mut later_header=packet[0..MIN_HEADER_LENGTH].to_vec();
+    later_header.extend_from_slice(&copied_options(header_options(packet)));
+    later_header[0]=0x40 | (later_header.len()/4) as u8;
+
+    let data=&packet[header_len..total_length];
+    let mut fragments=Vec::new();
+    let mut start=0;
+    while start<data.len(){
+        let header=if start==0 {&packet[0..header_len]} else {&later_header[..]};
+        //每个分片最多携带的数据，必须是8的倍数
+        let max_data_len=mtu.saturating_sub(header.len()) & !7;
+        if max_data_len==0{
+            return None;
+        }
+        let end=(start+max_data_len).min(data.len());
+        let last=end==data.len();
+        let mut fragment=header.to_vec();
+        fragment.extend_from_slice(&data[start..end]);
+        let total_length=(header.len()+end-start) as u16;
+        fragment[2..4].copy_from_slice(&total_length.to_be_bytes());
+        let mut flags_and_fragment_offset=original_offset+(start/8) as u16;
+        if !last || original_more_fragments{
+            flags_and_fragment_offset|=FLAG_MF;
+        }
+        fragment[6..8].copy_from_slice(&flags_and_fragment_offset.to_be_bytes());
+        update_check_sum(&mut fragment);
+        fragments.push(fragment);
+        start=end;
+    }
+    Some(fragments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_layer::ip::option::encode_options;
+
+    /// ### 功能
+    /// 生成数据长度为data_len的分组，数据为字节序号
+    fn packet(flags_and_fragment_offset:u16,options:&[u8],data_len:usize)->Vec<u8>{
+        let mut packet=build_header(0,(MIN_HEADER_LENGTH+options.len()+data_len) as u16,7,flags_and_fragment_offset,64,17,[10,0,0,1],[10,0,0,2],options);
+        packet.extend((0..data_len).map(|i|i as u8));
+        packet
+    }
+
+    #[test]
+    fn small_packet_is_not_fragmented(){
+        let packet=packet(0,&[],100);
+        assert_eq!(fragment(&packet,1500).unwrap(),vec![packet]);
+    }
+
+    #[test]
+    fn dont_fragment_fails(){
+        assert!(fragment(&packet(FLAG_DF,&[],100),68).is_none());
+    }
+
+    #[test]
+    fn fragments_are_aligned_and_complete(){
+        let packet=packet(0,&[],100);
+        let fragments=fragment(&packet,68).unwrap();
+        assert_eq!(fragments.len(),3);
+        let mut data=Vec::new();
+        for (i,fragment) in fragments.iter().enumerate(){
+            let flags_and_fragment_offset=u16::from_be_bytes([fragment[6],fragment[7]]);
+            assert_eq!((flags_and_fragment_offset & FRAGMENT_OFFSET_MASK) as usize*8,data.len());
+            assert_eq!(flags_and_fragment_offset & FLAG_MF!=0,i<2);
+            assert_eq!(u16::from_be_bytes([fragment[2],fragment[3]]) as usize,fragment.len());
+            assert!(fragment.len()<=68);
+            assert_eq!(internet_checksum(&fragment[0..header_length(fragment)]),0);
+            data.extend_from_slice(&fragment[header_length(fragment)..]);
+        }
+        assert_eq!(data,packet[MIN_HEADER_LENGTH..]);
+    }
+
+    #[test]
+    fn fragmenting_a_fragment_keeps_offset_and_more_fragments(){
+        //偏移为80字节、MF置位的分片再次分片
+        let packet=packet(FLAG_MF | 10,&[],64);
+        let fragments=fragment(&packet,60).unwrap();
+        assert_eq!(fragments.len(),2);
+        for (fragment,offset) in fragments.iter().zip([10,15]){
+            let flags_and_fragment_offset=u16::from_be_bytes([fragment[6],fragment[7]]);
+            assert_eq!(flags_and_fragment_offset & FRAGMENT_OFFSET_MASK,offset);
+            assert!(flags_and_fragment_offset & FLAG_MF!=0);
+        }
+    }
+
+    #[test]
+    fn only_first_fragment_carries_all_options(){
+        let options=encode_options(&[
+            IpOption::record_route(1),
+            IpOption::LooseSourceRoute{pointer:4,route:vec![[10,0,0,1]]},
+        ]).unwrap();
+        let packet=packet(0,&options,64);
+        let fragments=fragment(&packet,60).unwrap();
+        assert_eq!(parse_header_options(&fragments[0]).unwrap().len(),3);
+        for later in &fragments[1..]{
+            assert_eq!(parse_header_options(later).unwrap(),vec![
+                IpOption::LooseSourceRoute{pointer:4,route:vec![[10,0,0,1]]},
+                IpOption::EndOfList,
+            ]);
+        }
+    }
+}
@@ -0,0 +1,12 @@
+/// 回环接口的序号。回环接口不在接口表中，也没有对应的网络适配器
+pub const LOOPBACK_INTERFACE:usize=usize::MAX;
+/// 回环接口的地址
+pub const LOOPBACK_IP:[u8;4]=[127,0,0,1];
+/// 回环接口的MTU，本机之间的数据报不需要分片
+pub const LOOPBACK_MTU:usize=65535;
+
+/// ### 功能
+/// IP地址是否属于回环网络127.0.0.0/8
+pub fn is_loopback_ip(ip:[u8;4])->bool{
+    ip[0]==127
+}
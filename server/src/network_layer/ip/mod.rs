@@ -1 +1,15 @@
-pub mod receive;
\ No newline at end of file
+pub mod receive;
+pub mod send;
+pub mod header;
+pub mod option;
+pub mod id;
+pub mod route;
+pub mod forward;
+pub mod validate;
+pub mod reassembly;
+pub mod pmtu;
+pub mod protocol;
+pub mod loopback;
+pub mod nat;
+pub mod tunnel;
+pub mod rpf;
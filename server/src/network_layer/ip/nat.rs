@@ -0,0 +1,454 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead,BufReader};
+use std::sync::{Arc,Mutex};
+use std::time::{Duration,Instant};
+use lazy_static::*;
+
+use crate::tools::address::*;
+use crate::tools::checksum::internet_checksum;
+
+use super::header::{header_length,update_check_sum,FLAG_MF,FRAGMENT_OFFSET_MASK};
+use super::protocol::{PROTOCOL_ICMP,PROTOCOL_TCP,PROTOCOL_UDP};
+
+lazy_static!{
+    ///静态变量--源地址转换（SNAT/伪装）的规则与转换表，路由器模式下由转发与接收使用
+    pub static ref NAT_TABLE:Arc<Mutex<NatTable>> = Arc::new(Mutex::new(NatTable::new()));
+}
+
+/// TCP映射的空闲超时
+pub const NAT_TCP_TIMEOUT:Duration=Duration::from_secs(7440);
+/// UDP映射的空闲超时
+pub const NAT_UDP_TIMEOUT:Duration=Duration::from_secs(300);
+/// ICMP查询映射的空闲超时
+pub const NAT_ICMP_TIMEOUT:Duration=Duration::from_secs(60);
+/// 转换后可以使用的端口（ICMP标识）范围
+const NAT_PORT_MIN:u16=1024;
+const NAT_PORT_MAX:u16=65535;
+/// 清理超时映射的间隔
+const EXPIRE_INTERVAL:Duration=Duration::from_secs(1);
+
+/// 一条转换规则：源地址属于network/netmask、从interface发出的分组改写源地址
+#[derive(Clone)]
+pub struct NatRule{
+    pub network:[u8;4],
+    pub netmask:[u8;4],
+    /// 出接口
+    pub interface:usize,
+    /// 转换后的源地址，None表示伪装（使用出接口的地址）
+    pub translated_ip:Option<[u8;4]>,
+}
+
+/// 内部一侧的(协议，地址，端口或ICMP标识)
+type Endpoint=(u8,[u8;4],u16);
+
+/// 一条地址与端口的映射
+struct NatMapping{
+    /// 转换后的地址与端口
+    outside:Endpoint,
+    /// 最近一次使用的时间
+    last_used:Instant,
+}
+
+/// ## 源地址转换表
+/// 按规则改写内部主机发出的分组的源地址与源端口（ICMP回送请求改写标识），
+/// 并把返回的分组（包括引用了转换后分组的ICMP差错报文）反向转换为内部地址；
+/// 内部主机发出的ICMP差错报文按其引用的分组所属的映射转换。
+/// 同一内部地址与端口映射为同一外部地址与端口，与远端无关；空闲超时后删除映射。
+/// 分片的数据报没有完整的端口信息，不进行转换
+pub struct NatTable{
+    rules:Vec<NatRule>,
+    /// 内部 -> 映射
+    mappings:HashMap<Endpoint,NatMapping>,
+    /// 外部 -> 内部
+    reverse:HashMap<Endpoint,Endpoint>,
+    /// 下一个尝试分配的端口
+    next_port:u16,
+    /// 上一次清理的时间
+    last_expire:Instant,
+}
+
+impl NatTable{
+    /// ### 功能
+    /// 新建没有规则的转换表
+    pub fn new()->NatTable{
+        NatTable{
+            rules:Vec::new(),
+            mappings:HashMap::new(),
+            reverse:HashMap::new(),
+            next_port:NAT_PORT_MIN,
+            last_expire:Instant::now(),
+        }
+    }
+    /// ### 功能
+    /// 添加规则
+    pub fn add_rule(&mut self,rule:NatRule){
+        self.rules.push(rule);
+    }
+    /// ### 功能
+    /// 从文件加载规则。每行一条，格式为"源网络/前缀长度 出接口序号 [转换后的源地址]"，
+    /// 省略转换后的源地址时为伪装，以#开头的内容为注释
+    /// ### 返回值
+    /// 成功加载的规则数
+    pub fn load(&mut self,file_path:&str)->usize{
+        let file=match File::open(file_path) {
+            Ok(file)=>file,
+            Err(_)=>{
+                //没有配置文件时不进行转换
+                return 0;
+            }
+        };
+        let mut count=0;
+        for (line_number,line) in BufReader::new(file).lines().enumerate(){
+            let line=match line {
+                Ok(line)=>line,
+                Err(_)=>break,
+            };
+            let line=line.split('#').next().unwrap_or("");
+            let fields:Vec<&str>=line.split_whitespace().collect();
+            if fields.is_empty(){
+                continue;
+            }
+            match parse_rule(&fields) {
+                Some(rule)=>{
+                    self.add_rule(rule);
+                    count+=1;
+                }
+                None=>{
+                    println!("NAT规则格式错误！{file_path}:{}",line_number+1);
+                }
+            }
+        }
+        count
+    }
+    /// ### 功能
+    /// 转发前转换内部主机发出的分组：匹配规则时改写源地址与源端口，并修正IP与上层协议的校验和
+    /// ### 返回值
+    /// 分组是否可以继续转发。匹配规则却无法转换（分片、不支持的协议、端口耗尽、
+    /// 差错报文引用的分组没有映射）时返回false
+    pub fn translate_outbound(&mut self,packet:&mut [u8],interface:usize,interface_ip:[u8;4])->bool{
+        self.expire();
+        let source_ip:[u8;4]=packet[12..16].try_into().unwrap();
+        let rule=match self.rules.iter().find(|rule|rule.interface==interface && is_same_subnet(source_ip,rule.network,rule.netmask)) {
+            Some(rule)=>rule.clone(),
+            None=>return true,
+        };
+        if is_fragment(packet){
+            println!("NAT不转换分片的数据报，丢弃来自{}的分组",format_ip(source_ip));
+            return false;
+        }
+        //ICMP差错报文：按被引用分组的目的地址与目的端口查找已有的映射
+        let header_len=header_length(packet);
+        if packet[9]==PROTOCOL_ICMP && packet.len()>=header_len+8 && matches!(packet[header_len],3|4|5|11|12){
+            if self.translate_outbound_error(packet){
+                return true;
+            }
+            println!("NAT没有与差错报文匹配的映射，丢弃来自{}的分组",format_ip(source_ip));
+            return false;
+        }
+        let port_offset=match outbound_port_offset(packet) {
+            Some(port_offset)=>port_offset,
+            None=>{
+                println!("NAT不支持协议{}，丢弃来自{}的分组",packet[9],format_ip(source_ip));
+                return false;
+            }
+        };
+        let protocol=packet[9];
+        let port=u16::from_be_bytes([packet[header_len+port_offset],packet[header_len+port_offset+1]]);
+        let inside=(protocol,source_ip,port);
+        let outside_ip=rule.translated_ip.unwrap_or(interface_ip);
+
+        let outside=match self.mappings.get_mut(&inside) {
+            Some(mapping) if mapping.outside.1==outside_ip=>{
+                mapping.last_used=Instant::now();
+                mapping.outside
+            }
+            _=>{
+                self.remove(inside);
+                let outside=match self.allocate(protocol,outside_ip,port) {
+                    Some(outside)=>outside,
+                    None=>{
+                        println!("NAT端口耗尽，丢弃来自{}的分组",format_ip(source_ip));
+                        return false;
+                    }
+                };
+                self.mappings.insert(inside,NatMapping{outside,last_used:Instant::now()});
+                self.reverse.insert(outside,inside);
+                println!("NAT新映射 {}:{} -> {}:{}（协议{}）",format_ip(source_ip),port,format_ip(outside.1),outside.2,protocol);
+                outside
+            }
+        };
+        rewrite(packet,12,header_len+port_offset,outside.1,outside.2);
+        true
+    }
+    /// ### 功能
+    /// 接收时反向转换返回的分组：目的地址与端口属于某个映射时改写为内部地址与端口；
+    /// 引用了转换后分组的ICMP差错报文同时改写被引用的首部
+    /// ### 返回值
+    /// 是否进行了转换，转换后的分组应当被转发给内部主机
+    pub fn translate_inbound(&mut self,packet:&mut [u8])->bool{
+        self.expire();
+        if self.mappings.is_empty() || is_fragment(packet){
+            return false;
+        }
+        let header_len=header_length(packet);
+        let protocol=packet[9];
+        let destination_ip:[u8;4]=packet[16..20].try_into().unwrap();
+
+        //ICMP差错报文：按被引用分组的源地址与源端口查找
+        if protocol==PROTOCOL_ICMP && packet.len()>=header_len+8 && matches!(packet[header_len],3|4|5|11|12){
+            return self.translate_inbound_error(packet);
+        }
+
+        let port_offset=match protocol {
+            PROTOCOL_TCP|PROTOCOL_UDP=>2,
+            PROTOCOL_ICMP if packet.len()>=header_len+8 && packet[header_len]==0=>4,
+            _=>return false,
+        };
+        if packet.len()<header_len+transport_min_length(protocol){
+            return false;
+        }
+        let port=u16::from_be_bytes([packet[header_len+port_offset],packet[header_len+port_offset+1]]);
+        let inside=match self.reverse.get(&(protocol,destination_ip,port)) {
+            Some(inside)=>*inside,
+            None=>return false,
+        };
+        if let Some(mapping)=self.mappings.get_mut(&inside){
+            mapping.last_used=Instant::now();
+        }
+        rewrite(packet,16,header_len+port_offset,inside.1,inside.2);
+        true
+    }
+    /// ### 功能
+    /// 打印规则与映射
+    pub fn print(&self){
+        for rule in &self.rules{
+            let translated=match rule.translated_ip {
+                Some(ip)=>format_ip(ip),
+                None=>String::from("伪装"),
+            };
+            println!("NAT规则：{}/{} 出接口{} -> {}",
+                format_ip(rule.network),u32::from_be_bytes(rule.netmask).count_ones(),rule.interface,translated);
+        }
+        println!("{:<8}{:<22}{:<22}空闲(秒)","协议","内部","外部");
+        for (inside,mapping) in &self.mappings{
+            println!("{:<8}{:<22}{:<22}{}",inside.0,
+                format!("{}:{}",format_ip(inside.1),inside.2),
+                format!("{}:{}",format_ip(mapping.outside.1),mapping.outside.2),
+                mapping.last_used.elapsed().as_secs());
+        }
+    }
+
+    /// ### 功能
+    /// 反向转换ICMP差错报文：改写被引用分组的源地址与源端口、外层分组的目的地址，并重新计算各校验和
+    fn translate_inbound_error(&mut self,packet:&mut [u8])->bool{
+        let reverse=&self.reverse;
+        translate_error(packet,12,16,|inner_protocol,ip,port|reverse.get(&(inner_protocol,ip,port)).copied())
+    }
+    /// ### 功能
+    /// 转换内部主机发出的ICMP差错报文（它引用的是反向转换后交给内部主机的分组）：
+    /// 改写被引用分组的目的地址与目的端口、外层分组的源地址，并重新计算各校验和
+    fn translate_outbound_error(&mut self,packet:&mut [u8])->bool{
+        let mappings=&mut self.mappings;
+        translate_error(packet,16,12,|inner_protocol,ip,port|{
+            let mapping=mappings.get_mut(&(inner_protocol,ip,port))?;
+            mapping.last_used=Instant::now();
+            Some(mapping.outside)
+        })
+    }
+    /// ### 功能
+    /// 分配外部端口：优先保留原端口，被占用时依次尝试其他端口
+    fn allocate(&mut self,protocol:u8,outside_ip:[u8;4],port:u16)->Option<Endpoint>{
+        if port>=NAT_PORT_MIN && !self.reverse.contains_key(&(protocol,outside_ip,port)){
+            return Some((protocol,outside_ip,port));
+        }
+        for _ in NAT_PORT_MIN..=NAT_PORT_MAX{
+            let candidate=self.next_port;
+            self.next_port=if self.next_port==NAT_PORT_MAX {NAT_PORT_MIN} else {self.next_port+1};
+            if !self.reverse.contains_key(&(protocol,outside_ip,candidate)){
+                return Some((protocol,outside_ip,candidate));
+            }
+        }
+        None
+    }
+    /// ### 功能
+    /// 删除内部端点的映射
+    fn remove(&mut self,inside:Endpoint){
+        if let Some(mapping)=self.mappings.remove(&inside){
+            self.reverse.remove(&mapping.outside);
+        }
+    }
+    /// ### 功能
+    /// 删除空闲超时的映射
+    fn expire(&mut self){
+        if self.last_expire.elapsed()<EXPIRE_INTERVAL{
+            return;
+        }
+        self.last_expire=Instant::now();
+        let expired:Vec<Endpoint>=self.mappings.iter()
+            .filter(|(inside,mapping)|mapping.last_used.elapsed()>timeout(inside.0))
+            .map(|(inside,_)|*inside)
+            .collect();
+        for inside in expired{
+            self.remove(inside);
+        }
+    }
+}
+
+/// ### 功能
+/// 各协议映射的空闲超时
+fn timeout(protocol:u8)->Duration{
+    match protocol {
+        PROTOCOL_TCP=>NAT_TCP_TIMEOUT,
+        PROTOCOL_UDP=>NAT_UDP_TIMEOUT,
+        _=>NAT_ICMP_TIMEOUT,
+    }
+}
+
+/// ### 功能
+/// 转换ICMP差错报文。被引用分组中地址偏移为inner_address_offset的地址及对应的端口（ICMP为标识）
+/// 由lookup给出新的值，外层分组中偏移为outer_address_offset的地址改为同一地址
+/// ### 返回值
+/// 是否进行了转换，被引用的分组不完整、协议不支持或lookup没有结果时返回false
+fn translate_error(
+    packet:&mut [u8],
+    inner_address_offset:usize,
+    outer_address_offset:usize,
+    lookup:impl FnOnce(u8,[u8;4],u16)->Option<Endpoint>
+)->bool{
+    let header_len=header_length(packet);
+    let inner_start=header_len+8;
+    if packet.len()<inner_start+20{
+        return false;
+    }
+    let inner_header_len=header_length(&packet[inner_start..]);
+    let inner_protocol=packet[inner_start+9];
+    //源端口在上层首部的偏移0，目的端口在偏移2；ICMP两个方向都使用偏移4的标识
+    let port_offset=match (inner_protocol,inner_address_offset) {
+        (PROTOCOL_TCP|PROTOCOL_UDP,12)=>0,
+        (PROTOCOL_TCP|PROTOCOL_UDP,_)=>2,
+        (PROTOCOL_ICMP,_)=>4,
+        _=>return false,
+    };
+    let port_start=inner_start+inner_header_len+port_offset;
+    if packet.len()<port_start+2{
+        return false;
+    }
+    let address_start=inner_start+inner_address_offset;
+    let inner_ip:[u8;4]=packet[address_start..address_start+4].try_into().unwrap();
+    let port=u16::from_be_bytes([packet[port_start],packet[port_start+1]]);
+    let (_,ip,port)=match lookup(inner_protocol,inner_ip,port) {
+        Some(endpoint)=>endpoint,
+        None=>return false,
+    };
+    //被引用的分组：地址与端口
+    packet[address_start..address_start+4].copy_from_slice(&ip);
+    packet[port_start..port_start+2].copy_from_slice(&port.to_be_bytes());
+    update_check_sum(&mut packet[inner_start..]);
+    //外层：地址，ICMP校验和覆盖整个报文
+    packet[outer_address_offset..outer_address_offset+4].copy_from_slice(&ip);
+    packet[header_len+2..header_len+4].copy_from_slice(&[0,0]);
+    let check_sum=internet_checksum(&packet[header_len..]);
+    packet[header_len+2..header_len+4].copy_from_slice(&check_sum.to_be_bytes());
+    update_check_sum(packet);
+    true
+}
+
+/// ### 功能
+/// 分组是否为分片（MF置位或偏移不为0）
+fn is_fragment(packet:&[u8])->bool{
+    u16::from_be_bytes([packet[6],packet[7]]) & (FLAG_MF | FRAGMENT_OFFSET_MASK)!=0
+}
+
+/// ### 功能
+/// 上层协议首部中需要的最短长度：包含端口（标识）与校验和
+fn transport_min_length(protocol:u8)->usize{
+    match protocol {
+        PROTOCOL_TCP=>18,
+        _=>8,
+    }
+}
+
+/// ### 功能
+/// 发出的分组中源端口（ICMP回送请求的标识）在上层数据中的位置
+/// ### 返回值
+/// Option，不支持的协议或长度不足时返回None
+fn outbound_port_offset(packet:&[u8])->Option<usize>{
+    let header_len=header_length(packet);
+    let protocol=packet[9];
+    if packet.len()<header_len+transport_min_length(protocol){
+        return None;
+    }
+    match protocol {
+        PROTOCOL_TCP|PROTOCOL_UDP=>Some(0),
+        PROTOCOL_ICMP if packet[header_len]==8=>Some(4),
+        _=>None,
+    }
+}
+
+/// ### 功能
+/// 改写地址（源地址偏移12，目的地址偏移16）与端口，增量修正上层协议的校验和，并重新计算首部校验和。
+/// TCP、UDP的校验和包含伪首部，需要同时反映地址的变化；UDP校验和为0表示未使用，保持不变
+fn rewrite(packet:&mut [u8],address_offset:usize,port_start:usize,ip:[u8;4],port:u16){
+    let header_len=header_length(packet);
+    let protocol=packet[9];
+    let old_ip:[u8;4]=packet[address_offset..address_offset+4].try_into().unwrap();
+    let old_port=[packet[port_start],packet[port_start+1]];
+    packet[address_offset..address_offset+4].copy_from_slice(&ip);
+    packet[port_start..port_start+2].copy_from_slice(&port.to_be_bytes());
+
+    let check_sum_start=header_len+match protocol {
+        PROTOCOL_TCP=>16,
+        PROTOCOL_UDP=>6,
+        _=>2,
+    };
+    let mut check_sum=u16::from_be_bytes([packet[check_sum_start],packet[check_sum_start+1]]);
+    if !(protocol==PROTOCOL_UDP && check_sum==0){
+        if protocol!=PROTOCOL_ICMP{
+            check_sum=adjust_check_sum(check_sum,&old_ip,&ip);
+        }
+        check_sum=adjust_check_sum(check_sum,&old_port,&port.to_be_bytes());
+        if protocol==PROTOCOL_UDP && check_sum==0{
+            check_sum=0xFFFF;
+        }
+        packet[check_sum_start..check_sum_start+2].copy_from_slice(&check_sum.to_be_bytes());
+    }
+    update_check_sum(packet);
+}
+
+/// ### 功能
+/// 按RFC 1624增量更新校验和：HC' = ~(~HC + ~m + m')，old与new长度相同且为偶数
+fn adjust_check_sum(check_sum:u16,old:&[u8],new:&[u8])->u16{
+    let mut sum=(!check_sum) as u32;
+    for chunk in old.chunks(2){
+        sum+=(!u16::from_be_bytes([chunk[0],chunk[1]])) as u32;
+    }
+    for chunk in new.chunks(2){
+        sum+=u16::from_be_bytes([chunk[0],chunk[1]]) as u32;
+    }
+    while sum>>16>0{
+        sum=(sum & 0xffff)+(sum>>16);
+    }
+    !(sum as u16)
+}
+
+/// ### 功能
+/// 解析一行NAT规则
+fn parse_rule(fields:&[&str])->Option<NatRule>{
+    if fields.len()<2 || fields.len()>3{
+        return None;
+    }
+    let (network,prefix_len)=fields[0].split_once('/')?;
+    let prefix_len:u32=prefix_len.parse().ok().filter(|len|*len<=32)?;
+    let interface:usize=fields[1].parse().ok()?;
+    let translated_ip=match fields.get(2) {
+        Some(ip)=>Some(parse_ip(ip)?),
+        None=>None,
+    };
+    Some(NatRule{
+        network:parse_ip(network)?,
+        netmask:netmask_from_prefix_len(prefix_len),
+        interface,
+        translated_ip,
+    })
+}
@@ -0,0 +1,397 @@
+use std::time::{SystemTime,UNIX_EPOCH};
+
+/// 选项部分的最大长度（首部最长60字节）
+pub const MAX_OPTIONS_LENGTH:usize=40;
+
+/// 选项类型-选项表结束
+pub const OPTION_END_OF_LIST:u8=0;
+/// 选项类型-无操作，用于对齐
+pub const OPTION_NO_OPERATION:u8=1;
+/// 选项类型-记录路由
+pub const OPTION_RECORD_ROUTE:u8=7;
+/// 选项类型-时间戳
+pub const OPTION_TIMESTAMP:u8=68;
+/// 选项类型-宽松源路由
+pub const OPTION_LOOSE_SOURCE_ROUTE:u8=131;
+/// 选项类型-严格源路由
+pub const OPTION_STRICT_SOURCE_ROUTE:u8=137;
+/// 选项类型-路由器告警（RFC 2113）
+pub const OPTION_ROUTER_ALERT:u8=148;
+
+/// 选项类型的最高位为复制标志，置位的选项需要复制到每一个分片中
+const OPTION_COPIED_FLAG:u8=0x80;
+
+/// 时间戳选项的标志
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum TimestampFlag{
+    /// 只记录时间戳
+    TimestampOnly=0,
+    /// 记录地址与时间戳
+    AddressAndTimestamp=1,
+    /// 只在预先指定的地址处记录时间戳
+    Prespecified=3,
+}
+
+/// 时间戳选项中的一项
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub struct TimestampEntry{
+    /// 记录时间戳的地址，只记录时间戳时为None
+    pub address:Option<[u8;4]>,
+    /// 自UT午夜起的毫秒数
+    pub timestamp:u32,
+}
+
+/// ## IPv4首部的选项
+#[derive(Clone,PartialEq,Debug)]
+pub enum IpOption{
+    /// 选项表结束，其后的内容均为填充
+    EndOfList,
+    /// 无操作
+    NoOperation,
+    /// 记录路由。pointer为下一个空位的位置（从选项起始处计，最小为4），route包括已记录与未记录的全部空位
+    RecordRoute{pointer:u8,route:Vec<[u8;4]>},
+    /// 时间戳
+    Timestamp{pointer:u8,overflow:u8,flag:TimestampFlag,entries:Vec<TimestampEntry>},
+    /// 宽松源路由，pointer与route的含义同记录路由
+    LooseSourceRoute{pointer:u8,route:Vec<[u8;4]>},
+    /// 严格源路由，pointer与route的含义同记录路由
+    StrictSourceRoute{pointer:u8,route:Vec<[u8;4]>},
+    /// 路由器告警，值为0表示路由器应检查该分组
+    RouterAlert(u16),
+    /// 其他不认识的选项，原样保留
+    Unknown{kind:u8,data:Vec<u8>},
+}
+
+impl IpOption{
+    /// ### 功能
+    /// 生成一个有slots个空位的记录路由选项
+    pub fn record_route(slots:usize)->IpOption{
+        IpOption::RecordRoute{pointer:4,route:vec![[0;4];slots]}
+    }
+    /// ### 功能
+    /// 生成一个有slots个空位的时间戳选项
+    pub fn timestamp(flag:TimestampFlag,slots:usize)->IpOption{
+        let address=if flag==TimestampFlag::TimestampOnly {None} else {Some([0;4])};
+        IpOption::Timestamp{
+            pointer:5,
+            overflow:0,
+            flag,
+            entries:vec![TimestampEntry{address,timestamp:0};slots],
+        }
+    }
+    /// ### 功能
+    /// 选项类型字段
+    pub fn kind(&self)->u8{
+        match self {
+            IpOption::EndOfList=>OPTION_END_OF_LIST,
+            IpOption::NoOperation=>OPTION_NO_OPERATION,
+            IpOption::RecordRoute{..}=>OPTION_RECORD_ROUTE,
+            IpOption::Timestamp{..}=>OPTION_TIMESTAMP,
+            IpOption::LooseSourceRoute{..}=>OPTION_LOOSE_SOURCE_ROUTE,
+            IpOption::StrictSourceRoute{..}=>OPTION_STRICT_SOURCE_ROUTE,
+            IpOption::RouterAlert(_)=>OPTION_ROUTER_ALERT,
+            IpOption::Unknown{kind,..}=>*kind,
+        }
+    }
+    /// ### 功能
+    /// 分片时是否需要复制到每一个分片中
+    pub fn is_copied(&self)->bool{
+        self.kind() & OPTION_COPIED_FLAG!=0
+    }
+    /// ### 功能
+    /// 把选项编码后追加到buffer末尾
+    pub fn encode(&self,buffer:&mut Vec<u8>){
+        match self {
+            IpOption::EndOfList|IpOption::NoOperation=>buffer.push(self.kind()),
+            IpOption::RecordRoute{pointer,route}
+            |IpOption::LooseSourceRoute{pointer,route}
+            |IpOption::StrictSourceRoute{pointer,route}=>{
+                buffer.push(self.kind());
+                buffer.push((3+4*route.len()) as u8);
+                buffer.push(*pointer);
+                for address in route{
+                    buffer.extend_from_slice(address);
+                }
+            }
+            IpOption::Timestamp{pointer,overflow,flag,entries}=>{
+                let entry_len=if *flag==TimestampFlag::TimestampOnly {4} else {8};
+                buffer.push(self.kind());
+                buffer.push((4+entry_len*entries.len()) as u8);
+                buffer.push(*pointer);
+                buffer.push(overflow<<4 | *flag as u8);
+                for entry in entries{
+                    if let Some(address)=entry.address{
+                        buffer.extend_from_slice(&address);
+                    }
+                    buffer.extend_from_slice(&entry.timestamp.to_be_bytes());
+                }
+            }
+            IpOption::RouterAlert(value)=>{
+                buffer.push(self.kind());
+                buffer.push(4);
+                buffer.extend_from_slice(&value.to_be_bytes());
+            }
+            IpOption::Unknown{kind,data}=>{
+                buffer.push(*kind);
+                buffer.push((2+data.len()) as u8);
+                buffer.extend_from_slice(data);
+            }
+        }
+    }
+    /// ### 功能
+    /// 经过本机时处理记录路由与时间戳选项：在下一个空位记录出接口地址（及时间戳）。
+    /// 空位已满时，时间戳选项的溢出计数加1
+    pub fn stamp(&mut self,address:[u8;4]){
+        match self {
+            IpOption::RecordRoute{pointer,route}=>{
+                let index=(*pointer as usize-4)/4;
+                if index<route.len(){
+                    route[index]=address;
+                    *pointer+=4;
+                }
+            }
+            IpOption::Timestamp{pointer,overflow,flag,entries}=>{
+                let entry_len=if *flag==TimestampFlag::TimestampOnly {4} else {8};
+                let index=(*pointer as usize-5)/entry_len;
+                if index>=entries.len(){
+                    *overflow=(*overflow+1).min(15);
+                    return;
+                }
+                let entry=&mut entries[index];
+                match flag {
+                    TimestampFlag::TimestampOnly=>{}
+                    TimestampFlag::AddressAndTimestamp=>entry.address=Some(address),
+                    //只有预先指定的地址是本机时才记录
+                    TimestampFlag::Prespecified=>if entry.address!=Some(address){
+                        return;
+                    }
+                }
+                entry.timestamp=timestamp_now();
+                *pointer+=entry_len as u8;
+            }
+            _=>{}
+        }
+    }
+    /// ### 功能
+    /// 到达源路由选项中的当前地址时，取出下一个地址作为新的目的地址，
+    /// 并在该位置记录本机的出接口地址
+    /// ### 返回值
+    /// Option，源路由已经走完或不是源路由选项时返回None
+    pub fn next_source_route_hop(&mut self,address:[u8;4])->Option<[u8;4]>{
+        match self {
+            IpOption::LooseSourceRoute{pointer,route}|IpOption::StrictSourceRoute{pointer,route}=>{
+                let index=(*pointer as usize-4)/4;
+                let next_hop=*route.get(index)?;
+                route[index]=address;
+                *pointer+=4;
+                Some(next_hop)
+            }
+            _=>None,
+        }
+    }
+}
+
+/// ### 功能
+/// 解析首部的选项部分。遇到选项表结束后停止，其后的内容视为填充
+/// ### 返回值
+/// Option，选项格式错误（长度越界、指针非法等）时返回None
+pub fn parse_options(buffer:&[u8])->Option<Vec<IpOption>>{
+    let mut options=Vec::new();
+    let mut i=0;
+    while i<buffer.len(){
+        let kind=buffer[i];
+        match kind {
+            OPTION_END_OF_LIST=>{
+                options.push(IpOption::EndOfList);
+                break;
+            }
+            OPTION_NO_OPERATION=>{
+                options.push(IpOption::NoOperation);
+                i+=1;
+                continue;
+            }
+            _=>{}
+        }
+        let len=*buffer.get(i+1)? as usize;
+        if len<2 || i+len>buffer.len(){
+            return None;
+        }
+        let data=&buffer[i+2..i+len];
+        let option=match kind {
+            OPTION_RECORD_ROUTE|OPTION_LOOSE_SOURCE_ROUTE|OPTION_STRICT_SOURCE_ROUTE=>{
+                let (pointer,route)=parse_route(data)?;
+                match kind {
+                    OPTION_RECORD_ROUTE=>IpOption::RecordRoute{pointer,route},
+                    OPTION_LOOSE_SOURCE_ROUTE=>IpOption::LooseSourceRoute{pointer,route},
+                    _=>IpOption::StrictSourceRoute{pointer,route},
+                }
+            }
+            OPTION_TIMESTAMP=>parse_timestamp(data)?,
+            OPTION_ROUTER_ALERT=>{
+                if data.len()!=2{
+                    return None;
+                }
+                IpOption::RouterAlert(u16::from_be_bytes([data[0],data[1]]))
+            }
+            _=>IpOption::Unknown{kind,data:data.to_vec()},
+        };
+        options.push(option);
+        i+=len;
+    }
+    Some(options)
+}
+
+/// ### 功能
+/// 编码选项，并用选项表结束补齐到4字节的整数倍
+/// ### 返回值
+/// Option，超过40字节时返回None
+pub fn encode_options(options:&[IpOption])->Option<Vec<u8>>{
+    let mut buffer=Vec::new();
+    for option in options{
+        option.encode(&mut buffer);
+    }
+    while buffer.len()%4!=0{
+        buffer.push(OPTION_END_OF_LIST);
+    }
+    if buffer.len()>MAX_OPTIONS_LENGTH{
+        return None;
+    }
+    Some(buffer)
+}
+
+/// ### 功能
+/// 从选项部分中取出需要复制到后续分片的选项（复制标志置位者），并补齐到4字节的整数倍。
+/// 第一个分片携带全部选项，其余分片只携带这些选项
+pub fn copied_options(buffer:&[u8])->Vec<u8>{
+    let copied:Vec<IpOption>=parse_options(buffer).unwrap_or_default()
+        .into_iter()
+        .filter(IpOption::is_copied)
+        .collect();
+    //复制的选项不会比原来的长，编码不会失败
+    encode_options(&copied).unwrap_or_default()
+}
+
+/// ### 功能
+/// 当前时刻自UT午夜起的毫秒数，时间戳选项使用
+pub fn timestamp_now()->u32{
+    let now=SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    ((now.as_secs()%86400)*1000+now.subsec_millis() as u64) as u32
+}
+
+/// ### 功能
+/// 解析记录路由与源路由选项的数据部分（不含类型与长度）
+fn parse_route(data:&[u8])->Option<(u8,Vec<[u8;4]>)>{
+    let (pointer,addresses)=data.split_first()?;
+    if *pointer<4 || addresses.len()%4!=0{
+        return None;
+    }
+    let route=addresses.chunks(4).map(|address|address.try_into().unwrap()).collect();
+    Some((*pointer,route))
+}
+
+/// ### 功能
+/// 解析时间戳选项的数据部分（不含类型与长度）
+fn parse_timestamp(data:&[u8])->Option<IpOption>{
+    if data.len()<2{
+        return None;
+    }
+    let pointer=data[0];
+    let overflow=data[1]>>4;
+    let flag=match data[1]&0x0f {
+        0=>TimestampFlag::TimestampOnly,
+        1=>TimestampFlag::AddressAndTimestamp,
+        3=>TimestampFlag::Prespecified,
+        _=>return None,
+    };
+    let entry_len=if flag==TimestampFlag::TimestampOnly {4} else {8};
+    let body=&data[2..];
+    if pointer<5 || !body.len().is_multiple_of(entry_len){
+        return None;
+    }
+    let entries=body.chunks(entry_len).map(|entry|{
+        if entry_len==4{
+            TimestampEntry{address:None,timestamp:u32::from_be_bytes(entry.try_into().unwrap())}
+        }
+        else{
+            TimestampEntry{
+                address:Some(entry[0..4].try_into().unwrap()),
+                timestamp:u32::from_be_bytes(entry[4..8].try_into().unwrap()),
+            }
+        }
+    }).collect();
+    Some(IpOption::Timestamp{pointer,overflow,flag,entries})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_round_trip(){
+        let options=vec![
+            IpOption::NoOperation,
+            IpOption::RecordRoute{pointer:8,route:vec![[10,0,0,1],[0;4]]},
+            IpOption::LooseSourceRoute{pointer:4,route:vec![[192,168,1,1]]},
+            IpOption::Timestamp{pointer:13,overflow:2,flag:TimestampFlag::AddressAndTimestamp,entries:vec![
+                TimestampEntry{address:Some([10,0,0,1]),timestamp:1234},
+                TimestampEntry{address:Some([0;4]),timestamp:0},
+            ]},
+        ];
+        let buffer=encode_options(&options).unwrap();
+        assert_eq!(buffer.len()%4,0);
+        let mut parsed=parse_options(&buffer).unwrap();
+        //补齐使用的选项表结束
+        assert_eq!(parsed.pop(),Some(IpOption::EndOfList));
+        assert_eq!(parsed,options);
+    }
+
+    #[test]
+    fn router_alert_and_unknown_round_trip(){
+        let options=vec![
+            IpOption::RouterAlert(0),
+            IpOption::StrictSourceRoute{pointer:4,route:vec![[172,16,0,1]]},
+            IpOption::Timestamp{pointer:5,overflow:0,flag:TimestampFlag::TimestampOnly,entries:vec![TimestampEntry{address:None,timestamp:0}]},
+            IpOption::Unknown{kind:0x99,data:vec![1,2]},
+        ];
+        let buffer=encode_options(&options).unwrap();
+        assert_eq!(buffer.len(),24);
+        let mut parsed=parse_options(&buffer).unwrap();
+        assert_eq!(parsed.pop(),Some(IpOption::EndOfList));
+        assert_eq!(parsed,options);
+    }
+
+    #[test]
+    fn options_longer_than_40_bytes_are_rejected(){
+        assert!(encode_options(&[IpOption::record_route(10)]).is_none());
+        assert_eq!(encode_options(&[IpOption::record_route(9)]).unwrap().len(),MAX_OPTIONS_LENGTH);
+    }
+
+    #[test]
+    fn malformed_options_are_rejected(){
+        //长度越界
+        assert!(parse_options(&[OPTION_RECORD_ROUTE,12,4,0]).is_none());
+        //长度小于2
+        assert!(parse_options(&[0x99,1,0,0]).is_none());
+        //指针小于4
+        assert!(parse_options(&[OPTION_RECORD_ROUTE,7,3,0,0,0,0,0]).is_none());
+        //时间戳的标志非法
+        assert!(parse_options(&[OPTION_TIMESTAMP,8,5,2,0,0,0,0]).is_none());
+        //路由器告警的长度错误
+        assert!(parse_options(&[OPTION_ROUTER_ALERT,3,0,0]).is_none());
+    }
+
+    #[test]
+    fn only_copied_options_are_kept_for_later_fragments(){
+        let buffer=encode_options(&[
+            IpOption::record_route(1),
+            IpOption::LooseSourceRoute{pointer:4,route:vec![[10,0,0,1]]},
+            IpOption::RouterAlert(0),
+        ]).unwrap();
+        let copied=parse_options(&copied_options(&buffer)).unwrap();
+        assert_eq!(copied,vec![
+            IpOption::LooseSourceRoute{pointer:4,route:vec![[10,0,0,1]]},
+            IpOption::RouterAlert(0),
+            IpOption::EndOfList,
+        ]);
+    }
+}
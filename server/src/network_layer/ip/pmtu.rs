@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::sync::{Arc,Mutex};
+use std::time::{Duration,Instant};
+use lazy_static::*;
+
+use crate::tools::address::format_ip;
+
+lazy_static!{
+    ///静态变量--路径MTU缓存，由ICMP需要分片报文更新，IP发送时查询
+    pub static ref PATH_MTU_CACHE:Arc<Mutex<PathMtuCache>> = Arc::new(Mutex::new(PathMtuCache::new()));
+}
+
+/// 路径MTU估计值的有效期，过期后重新使用出接口的MTU探测（RFC 1191建议10分钟）
+pub const PATH_MTU_AGING:Duration=Duration::from_secs(600);
+/// IPv4要求所有链路都能传输的最小MTU
+pub const MIN_PATH_MTU:usize=68;
+/// 不支持RFC 1191的路由器不给出下一跳MTU时，按原分组长度在这些常见MTU中选择较小的一个（RFC 1191第7节）
+const MTU_PLATEAUS:[usize;10]=[65535,32000,17914,8166,4352,2002,1492,1006,508,296];
+
+/// 路径MTU缓存的表项
+struct PathMtuEntry{
+    mtu:usize,
+    /// 最近一次更新的时间
+    update_time:Instant,
+}
+
+/// ## 路径MTU缓存
+/// 按目的地址记录路径MTU（RFC 1191）。只会因为ICMP需要分片报文而减小，
+/// 过期后删除，使发送端重新按出接口的MTU发送，从而发现变大的路径MTU
+pub struct PathMtuCache{
+    inner:HashMap<[u8;4],PathMtuEntry>
+}
+
+impl PathMtuCache{
+    /// ### 功能
+    /// 新建空的缓存
+    pub fn new()->PathMtuCache{
+        PathMtuCache{
+            inner:HashMap::new()
+        }
+    }
+    /// ### 功能
+    /// 查询到目的地址的路径MTU，过期的表项会被删除
+    /// ### 返回值
+    /// Option，没有记录时返回None，此时使用出接口的MTU
+    pub fn get(&mut self,destination_ip:[u8;4])->Option<usize>{
+        let expired=match self.inner.get(&destination_ip) {
+            Some(entry)=>entry.update_time.elapsed()>PATH_MTU_AGING,
+            None=>return None,
+        };
+        if expired{
+            self.inner.remove(&destination_ip);
+            println!("到{}的路径MTU已过期，重新探测",format_ip(destination_ip));
+            return None;
+        }
+        self.inner.get(&destination_ip).map(|entry|entry.mtu)
+    }
+    /// ### 功能
+    /// 收到ICMP需要分片报文后更新路径MTU。
+    /// next_hop_mtu为报文中的下一跳MTU，为0时（旧式路由器）根据原分组的总长度估计
+    /// ### 返回值
+    /// 更新后的路径MTU
+    pub fn update(&mut self,destination_ip:[u8;4],next_hop_mtu:usize,original_total_length:usize)->usize{
+        let mtu=if next_hop_mtu==0 || next_hop_mtu>=original_total_length {
+            //下一跳MTU无效，取比原分组小的下一个常见MTU
+            MTU_PLATEAUS.iter().copied().find(|plateau|*plateau<original_total_length).unwrap_or(MIN_PATH_MTU)
+        }
+        else{
+            next_hop_mtu
+        };
+        let mtu=mtu.max(MIN_PATH_MTU);
+        let mtu=match self.inner.get(&destination_ip) {
+            //只会减小，更大的值可能是过时的报文
+            Some(entry)=>entry.mtu.min(mtu),
+            None=>mtu,
+        };
+        self.inner.insert(destination_ip,PathMtuEntry{mtu,update_time:Instant::now()});
+        println!("到{}的路径MTU更新为{}",format_ip(destination_ip),mtu);
+        mtu
+    }
+    /// ### 功能
+    /// 打印缓存
+    pub fn print(&self){
+        println!("{:<16}{:<8}已存在(秒)","目的地址","路径MTU");
+        for (destination_ip,entry) in &self.inner{
+            println!("{:<16}{:<8}{}",format_ip(*destination_ip),entry.mtu,entry.update_time.elapsed().as_secs());
+        }
+    }
+}
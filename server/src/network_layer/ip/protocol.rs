@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::sync::{Arc,Mutex};
+use lazy_static::*;
+
+use super::header::header_length;
+
+lazy_static!{
+    ///静态变量--上层协议的注册表，IP接收端按协议字段把数据报交付给注册的处理函数
+    pub static ref PROTOCOL_REGISTRY:Arc<Mutex<ProtocolRegistry>> = Arc::new(Mutex::new(ProtocolRegistry::new()));
+}
+
+/// 上层协议字段-ICMPV4
+pub const PROTOCOL_ICMP:u8=1;
+/// 上层协议字段-IGMP
+pub const PROTOCOL_IGMP:u8=2;
+/// 上层协议字段-IP-in-IP
+pub const PROTOCOL_IPIP:u8=4;
+/// 上层协议字段-TCP
+pub const PROTOCOL_TCP:u8=6;
+/// 上层协议字段-UDP
+pub const PROTOCOL_UDP:u8=17;
+/// 上层协议字段-GRE
+pub const PROTOCOL_GRE:u8=47;
+
+/// 交付给上层协议的完整数据报（已经重组）
+pub struct IpDatagram{
+    /// 收到该数据报的接口
+    pub interface:usize,
+    /// 完整的分组，含首部
+    pub packet:Vec<u8>,
+}
+
+impl IpDatagram{
+    /// ### 功能
+    /// 源IP地址
+    pub fn source_ip(&self)->[u8;4]{
+        self.packet[12..16].try_into().unwrap()
+    }
+    /// ### 功能
+    /// 目的IP地址
+    pub fn destination_ip(&self)->[u8;4]{
+        self.packet[16..20].try_into().unwrap()
+    }
+    /// ### 功能
+    /// 上层协议
+    pub fn protocol_type(&self)->u8{
+        self.packet[9]
+    }
+    /// ### 功能
+    /// 上层数据（不含IP首部）
+    pub fn data(&self)->&[u8]{
+        &self.packet[header_length(&self.packet)..]
+    }
+}
+
+/// 上层协议的处理函数，在IP接收线程中被调用，应当尽快返回（例如只写入该协议的接收队列）
+pub type ProtocolHandler=Box<dyn FnMut(&IpDatagram)+Send>;
+
+/// ## 上层协议的注册表
+/// 协议字段到处理函数的映射。新的传输层协议只需注册处理函数，不必修改IP的接收与重组
+pub struct ProtocolRegistry{
+    handlers:HashMap<u8,ProtocolHandler>
+}
+
+impl ProtocolRegistry{
+    /// ### 功能
+    /// 新建空的注册表
+    pub fn new()->ProtocolRegistry{
+        ProtocolRegistry{
+            handlers:HashMap::new()
+        }
+    }
+    /// ### 功能
+    /// 为协议注册处理函数
+    /// ### 返回值
+    /// 是否注册成功，该协议已经注册过时失败
+    pub fn register(&mut self,protocol_type:u8,handler:ProtocolHandler)->bool{
+        if self.handlers.contains_key(&protocol_type){
+            return false;
+        }
+        self.handlers.insert(protocol_type,handler);
+        true
+    }
+    /// ### 功能
+    /// 注销协议的处理函数
+    /// ### 返回值
+    /// 是否注销成功
+    pub fn unregister(&mut self,protocol_type:u8)->bool{
+        self.handlers.remove(&protocol_type).is_some()
+    }
+    /// ### 功能
+    /// 协议是否已经注册
+    pub fn is_registered(&self,protocol_type:u8)->bool{
+        self.handlers.contains_key(&protocol_type)
+    }
+    /// ### 功能
+    /// 把数据报交付给对应协议的处理函数
+    /// ### 返回值
+    /// 是否交付成功，协议没有注册时失败
+    pub fn deliver(&mut self,datagram:&IpDatagram)->bool{
+        match self.handlers.get_mut(&datagram.protocol_type()) {
+            Some(handler)=>{
+                handler(datagram);
+                true
+            }
+            None=>false,
+        }
+    }
+}
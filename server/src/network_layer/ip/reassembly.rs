@@ -0,0 +1,427 @@
+use std::collections::VecDeque;
+use std::sync::{Arc,Mutex};
+use std::time::{Duration,Instant};
+use lazy_static::*;
+
+use crate::tools::address::format_ip;
+
+use super::header::{header_length,update_check_sum,FLAG_MF,FRAGMENT_OFFSET_MASK};
+
+lazy_static!{
+    ///静态变量--IP分片重组器
+    pub static ref REASSEMBLER:Arc<Mutex<Reassembler>> = Arc::new(Mutex::new(Reassembler::new()));
+}
+
+/// 重组超时时间，超时仍未收齐的数据报被丢弃
+pub const REASSEMBLY_TIMEOUT:Duration=Duration::from_secs(30);
+/// 所有正在重组的数据报最多占用的内存（字节），超过时淘汰最早的数据报
+pub const MAX_REASSEMBLY_MEMORY:usize=4*1024*1024;
+/// 每个数据报最多接受的分片数
+pub const MAX_FRAGMENTS_PER_DATAGRAM:usize=64;
+/// IPv4数据报的最大长度
+const MAX_DATAGRAM_LENGTH:usize=65535;
+
+/// 重组时区分数据报的键：只有源地址、目的地址、协议与标识都相同的分片才属于同一数据报（RFC 791）
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub struct ReassemblyKey{
+    pub source_ip:[u8;4],
+    pub destination_ip:[u8;4],
+    pub protocol:u8,
+    pub id:u16,
+}
+
+impl ReassemblyKey{
+    /// ### 功能
+    /// 从分组首部取出重组的键
+    pub fn from_packet(packet:&[u8])->Self{
+        ReassemblyKey{
+            source_ip:packet[12..16].try_into().unwrap(),
+            destination_ip:packet[16..20].try_into().unwrap(),
+            protocol:packet[9],
+            id:u16::from_be_bytes([packet[4],packet[5]]),
+        }
+    }
+}
+
+/// 分片被拒绝、数据报被丢弃的原因
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum ReassemblyError{
+    /// 分片与已收到的分片部分重叠（或重叠部分内容不同），整个数据报被丢弃
+    Overlap,
+    /// 分片数超过上限
+    TooManyFragments,
+    /// 分片超出数据报的最大长度或已知的总长度，或非最后分片的长度不是8的倍数
+    BadFragment,
+}
+
+/// 分片加入重组器的结果
+pub enum ReassemblyResult{
+    /// 数据报已经收齐，为重组后的完整分组（含首部）
+    Complete(Vec<u8>),
+    /// 还需要等待其他分片
+    Incomplete,
+    /// 分片被拒绝，相应的数据报已被丢弃
+    Dropped(ReassemblyError),
+}
+
+/// 一个正在重组的数据报
+struct Datagram{
+    key:ReassemblyKey,
+    /// 收到第一个分片的时间
+    arrival:Instant,
+    /// 已收到的分片，按偏移排序的(起始字节，数据)，区间互不重叠
+    fragments:Vec<(usize,Vec<u8>)>,
+    /// 收到最后一个分片（MF=0）后确定的数据总长度
+    total_length:Option<usize>,
+    /// 偏移为0的分片的首部，重组后的分组沿用此首部
+    first_header:Option<Vec<u8>>,
+    /// 偏移为0的分片的首部加数据的前8字节，超时时用于ICMP超时报文
+    first_fragment:Option<Vec<u8>>,
+    /// 占用的内存
+    memory:usize,
+}
+
+impl Datagram{
+    /// ### 功能
+    /// 所有分片是否已经收齐
+    fn is_complete(&self)->bool{
+        let total_length=match self.total_length {
+            Some(total_length)=>total_length,
+            None=>return false,
+        };
+        if self.first_header.is_none(){
+            return false;
+        }
+        let mut next=0;
+        for (start,data) in &self.fragments{
+            if *start!=next{
+                return false;
+            }
+            next=start+data.len();
+        }
+        next==total_length
+    }
+    /// ### 功能
+    /// 把已经收齐的分片拼接为完整分组，首部取自第一个分片，并清除分片标志
+    fn assemble(self)->Vec<u8>{
+        let mut packet=self.first_header.unwrap();
+        let header_len=packet.len();
+        for (_,data) in &self.fragments{
+            packet.extend_from_slice(data);
+        }
+        let total_length=packet.len() as u16;
+        packet[2..4].copy_from_slice(&total_length.to_be_bytes());
+        let flags=u16::from_be_bytes([packet[6],packet[7]]) & !(FLAG_MF | FRAGMENT_OFFSET_MASK);
+        packet[6..8].copy_from_slice(&flags.to_be_bytes());
+        update_check_sum(&mut packet[0..header_len]);
+        packet
+    }
+}
+
+/// ## IP分片重组器
+/// 以(源地址，目的地址，协议，标识)区分数据报，按区间保存分片：
+/// - 与已有分片完全相同的重复分片被忽略；部分重叠或内容不同的分片会使整个数据报被丢弃（仿照RFC 5722），以防御teardrop等攻击
+/// - 每个数据报最多接受MAX_FRAGMENTS_PER_DATAGRAM个分片
+/// - 总内存超过MAX_REASSEMBLY_MEMORY时淘汰最早的数据报
+/// - 超过REASSEMBLY_TIMEOUT仍未收齐的数据报被丢弃
+pub struct Reassembler{
+    /// 按到达顺序排列的数据报，最早的在前
+    datagrams:VecDeque<Datagram>,
+    /// 所有数据报占用的内存
+    memory:usize,
+    /// 重组成功的数据报数
+    pub reassembled:u64,
+    /// 超时丢弃的数据报数
+    pub timeouts:u64,
+    /// 因内存不足被淘汰的数据报数
+    pub evictions:u64,
+    /// 因重叠被丢弃的数据报数
+    pub overlaps:u64,
+    /// 因分片过多或分片非法被丢弃的数据报数
+    pub bad_fragments:u64,
+}
+
+impl Reassembler{
+    /// ### 功能
+    /// 新建重组器
+    pub fn new()->Reassembler{
+        Reassembler{
+            datagrams:VecDeque::new(),
+            memory:0,
+            reassembled:0,
+            timeouts:0,
+            evictions:0,
+            overlaps:0,
+            bad_fragments:0,
+        }
+    }
+    /// ### 功能
+    /// 加入一个分片。分组应当已经通过首部校验，且已去掉链路层的填充
+    /// ### 返回值
+    /// 数据报收齐时返回重组后的完整分组
+    pub fn insert(&mut self,packet:&[u8])->ReassemblyResult{
+        let key=ReassemblyKey::from_packet(packet);
+        let header_len=header_length(packet);
+        let flags_and_fragment_offset=u16::from_be_bytes([packet[6],packet[7]]);
+        let start=((flags_and_fragment_offset & FRAGMENT_OFFSET_MASK) as usize)*8;
+        let more_fragments=flags_and_fragment_offset & FLAG_MF!=0;
+        let data=&packet[header_len..];
+        let end=start+data.len();
+
+        let index=match self.datagrams.iter().position(|datagram|datagram.key==key) {
+            Some(index)=>index,
+            None=>{
+                self.datagrams.push_back(Datagram{
+                    key,
+                    arrival:Instant::now(),
+                    fragments:Vec::new(),
+                    total_length:None,
+                    first_header:None,
+                    first_fragment:None,
+                    memory:0,
+                });
+                self.datagrams.len()-1
+            }
+        };
+
+        match Self::add_fragment(&mut self.datagrams[index],packet,start,end,more_fragments) {
+            Ok(true)=>{
+                let added=header_len+data.len();
+                self.datagrams[index].memory+=added;
+                self.memory+=added;
+            }
+            //重复分片
+            Ok(false)=>{}
+            Err(error)=>{
+                match error {
+                    ReassemblyError::Overlap=>self.overlaps+=1,
+                    _=>self.bad_fragments+=1,
+                }
+                self.remove(index);
+                return ReassemblyResult::Dropped(error);
+            }
+        }
+
+        if self.datagrams[index].is_complete(){
+            let datagram=self.remove(index);
+            self.reassembled+=1;
+            return ReassemblyResult::Complete(datagram.assemble());
+        }
+
+        //内存超过上限时淘汰最早的数据报（不产生ICMP报文）
+        while self.memory>MAX_REASSEMBLY_MEMORY{
+            let evicted=self.remove(0);
+            self.evictions+=1;
+            println!("重组内存不足，淘汰来自{}的数据报（标识{}）",format_ip(evicted.key.source_ip),evicted.key.id);
+        }
+        ReassemblyResult::Incomplete
+    }
+    /// ### 功能
+    /// 丢弃超时的数据报
+    /// ### 返回值
+    /// 超时且收到过第一个分片的数据报的首个分片（首部加数据前8字节），用于回送ICMP超时报文（code=1）
+    pub fn expire(&mut self)->Vec<Vec<u8>>{
+        let mut first_fragments=Vec::new();
+        while let Some(datagram)=self.datagrams.front(){
+            if datagram.arrival.elapsed()<REASSEMBLY_TIMEOUT{
+                break;
+            }
+            let datagram=self.remove(0);
+            self.timeouts+=1;
+            println!("重组超时，丢弃来自{}的数据报（标识{}）",format_ip(datagram.key.source_ip),datagram.key.id);
+            if let Some(first_fragment)=datagram.first_fragment{
+                first_fragments.push(first_fragment);
+            }
+        }
+        first_fragments
+    }
+    /// ### 功能
+    /// 打印统计
+    pub fn print(&self){
+        println!("正在重组{}个数据报，占用{}字节；重组成功{}，超时{}，淘汰{}，重叠{}，非法分片{}",
+            self.datagrams.len(),self.memory,self.reassembled,self.timeouts,self.evictions,self.overlaps,self.bad_fragments);
+    }
+
+    /// ### 功能
+    /// 把分片加入数据报，检查分片数、长度与重叠
+    /// ### 返回值
+    /// Result，成功时为是否加入了新的分片（完全相同的重复分片不加入）
+    fn add_fragment(datagram:&mut Datagram,packet:&[u8],start:usize,end:usize,more_fragments:bool)->Result<bool,ReassemblyError>{
+        let header_len=header_length(packet);
+        let data=&packet[header_len..];
+        if end>MAX_DATAGRAM_LENGTH-header_len{
+            return Err(ReassemblyError::BadFragment);
+        }
+        //除最后一个分片外，数据长度必须是8的倍数
+        if more_fragments && !data.len().is_multiple_of(8){
+            return Err(ReassemblyError::BadFragment);
+        }
+        if more_fragments && data.is_empty(){
+            return Err(ReassemblyError::BadFragment);
+        }
+        match datagram.total_length {
+            Some(total_length)=>{
+                if end>total_length || (!more_fragments && end!=total_length){
+                    return Err(ReassemblyError::BadFragment);
+                }
+            }
+            None=>{
+                if !more_fragments{
+                    //最后一个分片之前不能已经收到更靠后的数据
+                    if datagram.fragments.iter().any(|(old_start,old_data)|old_start+old_data.len()>end){
+                        return Err(ReassemblyError::BadFragment);
+                    }
+                    datagram.total_length=Some(end);
+                }
+            }
+        }
+
+        //查找插入位置并检查重叠
+        let position=datagram.fragments.partition_point(|(old_start,_)|*old_start<start);
+        if let Some((old_start,old_data))=datagram.fragments.get(position){
+            if *old_start==start && old_data.as_slice()==data{
+                //完全相同的重复分片
+                return Ok(false);
+            }
+            if *old_start<end{
+                return Err(ReassemblyError::Overlap);
+            }
+        }
+        if position>0{
+            let (old_start,old_data)=&datagram.fragments[position-1];
+            if old_start+old_data.len()>start{
+                return Err(ReassemblyError::Overlap);
+            }
+        }
+
+        if datagram.fragments.len()>=MAX_FRAGMENTS_PER_DATAGRAM{
+            return Err(ReassemblyError::TooManyFragments);
+        }
+        if start==0{
+            datagram.first_header=Some(packet[0..header_len].to_vec());
+            datagram.first_fragment=Some(packet[0..(header_len+8).min(packet.len())].to_vec());
+        }
+        datagram.fragments.insert(position,(start,data.to_vec()));
+        Ok(true)
+    }
+    /// ### 功能
+    /// 移除一个数据报并归还其内存
+    fn remove(&mut self,index:usize)->Datagram{
+        let datagram=self.datagrams.remove(index).unwrap();
+        self.memory-=datagram.memory;
+        datagram
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_layer::ip::header::{build_header,MIN_HEADER_LENGTH};
+    use crate::tools::checksum::internet_checksum;
+
+    /// ### 功能
+    /// 生成标识为id、偏移为offset字节的分片
+    fn fragment(id:u16,protocol:u8,offset:usize,more_fragments:bool,data:&[u8])->Vec<u8>{
+        let flags=(offset/8) as u16 | if more_fragments {FLAG_MF} else {0};
+        let mut packet=build_header(0,(MIN_HEADER_LENGTH+data.len()) as u16,id,flags,64,protocol,[10,0,0,1],[10,0,0,2],&[]);
+        packet.extend_from_slice(data);
+        packet
+    }
+
+    #[test]
+    fn out_of_order_fragments_are_reassembled(){
+        let mut reassembler=Reassembler::new();
+        let data:Vec<u8>=(0..40).collect();
+        assert!(matches!(reassembler.insert(&fragment(1,17,16,false,&data[16..])),ReassemblyResult::Incomplete));
+        assert!(matches!(reassembler.insert(&fragment(1,17,0,true,&data[0..8])),ReassemblyResult::Incomplete));
+        let packet=match reassembler.insert(&fragment(1,17,8,true,&data[8..16])) {
+            ReassemblyResult::Complete(packet)=>packet,
+            _=>panic!("数据报应当已经收齐"),
+        };
+        assert_eq!(&packet[MIN_HEADER_LENGTH..],&data[..]);
+        assert_eq!(u16::from_be_bytes([packet[2],packet[3]]) as usize,MIN_HEADER_LENGTH+data.len());
+        assert_eq!(u16::from_be_bytes([packet[6],packet[7]]) & (FLAG_MF | FRAGMENT_OFFSET_MASK),0);
+        assert_eq!(internet_checksum(&packet[0..MIN_HEADER_LENGTH]),0);
+        assert_eq!(reassembler.reassembled,1);
+        assert_eq!(reassembler.memory,0);
+    }
+
+    #[test]
+    fn fragments_with_different_keys_are_kept_apart(){
+        let mut reassembler=Reassembler::new();
+        assert!(matches!(reassembler.insert(&fragment(1,17,0,true,&[0;8])),ReassemblyResult::Incomplete));
+        //协议不同，不属于同一数据报
+        assert!(matches!(reassembler.insert(&fragment(1,6,8,false,&[0;8])),ReassemblyResult::Incomplete));
+        assert!(matches!(reassembler.insert(&fragment(1,17,8,false,&[0;8])),ReassemblyResult::Complete(_)));
+    }
+
+    #[test]
+    fn duplicate_fragment_is_ignored(){
+        let mut reassembler=Reassembler::new();
+        let first=fragment(2,17,0,true,&[1;8]);
+        assert!(matches!(reassembler.insert(&first),ReassemblyResult::Incomplete));
+        let memory=reassembler.memory;
+        assert!(matches!(reassembler.insert(&first),ReassemblyResult::Incomplete));
+        assert_eq!(reassembler.memory,memory);
+        assert!(matches!(reassembler.insert(&fragment(2,17,8,false,&[2;8])),ReassemblyResult::Complete(_)));
+        assert_eq!(reassembler.overlaps,0);
+    }
+
+    #[test]
+    fn overlapping_fragment_drops_datagram(){
+        let mut reassembler=Reassembler::new();
+        assert!(matches!(reassembler.insert(&fragment(3,17,0,true,&[1;16])),ReassemblyResult::Incomplete));
+        assert!(matches!(reassembler.insert(&fragment(3,17,8,false,&[2;16])),ReassemblyResult::Dropped(ReassemblyError::Overlap)));
+        assert_eq!(reassembler.overlaps,1);
+        assert_eq!(reassembler.memory,0);
+        //数据报已被丢弃，之后的分片开始新的重组
+        assert!(matches!(reassembler.insert(&fragment(3,17,16,false,&[3;8])),ReassemblyResult::Incomplete));
+    }
+
+    #[test]
+    fn same_offset_with_different_data_is_overlap(){
+        let mut reassembler=Reassembler::new();
+        assert!(matches!(reassembler.insert(&fragment(4,17,0,true,&[1;8])),ReassemblyResult::Incomplete));
+        assert!(matches!(reassembler.insert(&fragment(4,17,0,true,&[9;8])),ReassemblyResult::Dropped(ReassemblyError::Overlap)));
+    }
+
+    #[test]
+    fn bad_fragments_are_rejected(){
+        let mut reassembler=Reassembler::new();
+        //非最后分片的长度不是8的倍数
+        assert!(matches!(reassembler.insert(&fragment(5,17,0,true,&[0;7])),ReassemblyResult::Dropped(ReassemblyError::BadFragment)));
+        //超出最后分片确定的总长度
+        assert!(matches!(reassembler.insert(&fragment(6,17,8,false,&[0;8])),ReassemblyResult::Incomplete));
+        assert!(matches!(reassembler.insert(&fragment(6,17,16,true,&[0;8])),ReassemblyResult::Dropped(ReassemblyError::BadFragment)));
+        //超出数据报的最大长度
+        assert!(matches!(reassembler.insert(&fragment(7,17,65528,false,&[0;16])),ReassemblyResult::Dropped(ReassemblyError::BadFragment)));
+        assert_eq!(reassembler.bad_fragments,3);
+    }
+
+    #[test]
+    fn fragment_limit_drops_datagram(){
+        let mut reassembler=Reassembler::new();
+        for i in 0..MAX_FRAGMENTS_PER_DATAGRAM{
+            assert!(matches!(reassembler.insert(&fragment(8,17,i*8,true,&[0;8])),ReassemblyResult::Incomplete));
+        }
+        let result=reassembler.insert(&fragment(8,17,MAX_FRAGMENTS_PER_DATAGRAM*8,true,&[0;8]));
+        assert!(matches!(result,ReassemblyResult::Dropped(ReassemblyError::TooManyFragments)));
+        assert_eq!(reassembler.memory,0);
+    }
+
+    #[test]
+    fn oldest_datagram_is_evicted_when_memory_is_full(){
+        let mut reassembler=Reassembler::new();
+        let data=[0;1480];
+        let per_datagram=MIN_HEADER_LENGTH+data.len();
+        let count=MAX_REASSEMBLY_MEMORY/per_datagram+1;
+        for id in 0..count{
+            assert!(matches!(reassembler.insert(&fragment(id as u16,17,0,true,&data)),ReassemblyResult::Incomplete));
+        }
+        assert_eq!(reassembler.evictions,1);
+        assert!(reassembler.memory<=MAX_REASSEMBLY_MEMORY);
+        //最早的数据报已被淘汰，它的最后分片不能完成重组；较新的数据报不受影响
+        assert!(matches!(reassembler.insert(&fragment(0,17,1480,false,&[0;8])),ReassemblyResult::Incomplete));
+        assert!(matches!(reassembler.insert(&fragment(2,17,1480,false,&[0;8])),ReassemblyResult::Complete(_)));
+    }
+}
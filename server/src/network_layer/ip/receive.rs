@@ -1,78 +1,69 @@
 use std::sync::{Arc,Mutex};
 use std::thread::yield_now;
-use std::io::Write;
-use std::fs::File;
 use std::collections::VecDeque;
 use lazy_static::*;
 
-use crate::network_layer::icmp::receive::IcmpReceiveQueue;
+use crate::data_link_layer::interface::InterfaceTable;
+use crate::network_layer::filter::conntrack::ConnectionTable;
+use crate::network_layer::filter::rule::{FilterAction,FilterPacket,Hook};
+use crate::network_layer::filter::table::{reject,FilterTable};
+use crate::network_layer::icmp::send::{send_error,ICMP_DESTINATION_UNREACHABLE,ICMP_TIME_EXCEEDED};
+use crate::network_layer::igmp::group::MulticastGroupTable;
+use crate::tools::address::{format_ip,is_multicast_ip};
+use crate::tools::global_variables::*;
+
+use super::forward::IpForwardQueue;
+use super::header::{header_length,parse_header_options,replace_header_options,update_check_sum,FLAG_MF,FRAGMENT_OFFSET_MASK,MIN_HEADER_LENGTH};
+use super::loopback::{is_loopback_ip,LOOPBACK_INTERFACE,LOOPBACK_MTU};
+use super::nat::NatTable;
+use super::option::IpOption;
+use super::protocol::{IpDatagram,ProtocolRegistry};
+use super::reassembly::{ReassemblyResult,REASSEMBLER};
+use super::route::RoutingTable;
+use super::rpf::{check_martian,check_reverse_path,RpfMode};
+use super::send::IpSendQueue;
+use super::validate::{validate_header,IpDropReason,IP_RECEIVE_STATISTICS};
 
 lazy_static!{
     ///静态变量--IP的接收队列
     pub static ref IP_RECEIVE_QUEUE:Arc<Mutex<IpReceiveQueue>> = Arc::new(Mutex::new(IpReceiveQueue::new()));
 }
 
-///最大分片长度
-const DATA_SLICE_LENTH:usize=1400;
-/// 上层协议字段-TCP
-const TCP_PROTOCOL:u8 = 6;
-/// 上层协议字段-UDP
-const UDP_PROTOCOL :u8= 17;
-/// 上层协议字段-ICMPV4
-const ICMPV4_PROTOCOL:u8=1;
-/// 上层协议字段-IGMPV4
-const IGMPV4_PROTOCOL:u8=2;
-
-
+/// IP首部中接收时用到的字段
 struct IpHeader{
-    /// 默认IP版本：IPV4，头部长度：单位为4字节，最长60字节
-    version_and_hdrlen:u8,
-    /// 服务类型
-	type_of_service:u8,
-    /// 总长度
-	total_length:u16,
     /// 标识，表明不同分片属于同一数据报
 	id:u16,			
     /// 标志与片偏移
 	flags_and_fragment_offset:u16,
-    /// 生存时间
-	time_to_live:u8,
     /// （上层）协议（TCP = 6,UDP = 17，ICMPV4=1, IGMPV4=2）
 	upper_protocol_type:u8,
-    /// 首部检验和
-	check_sum:u16,
-    /// 源IP地址
-	source_ip:u32,   
-    /// 目的IP地址
-    destination_ip:u32,
-    /// 40字节的可选部分
-	optional:[u8;40],
+    /// 选项，长度由首部长度决定
+	options:Vec<IpOption>,
 }
 
 impl IpHeader{
     /// ### 功能
-    /// 从一个Vec<u8>的前几位构造一个ip头
-    pub fn from_u8(buffer:& Vec<u8>)->Self{
+    /// 从一个Vec<u8>的前几位构造一个ip头，首部长度由IHL字段确定。
+    /// 分组应当已经通过validate_header的校验
+    /// ### 返回值
+    /// Option，分组比首部短或选项格式错误时返回None
+    pub fn from_u8(buffer:&[u8])->Option<Self>{
+        if buffer.len()<MIN_HEADER_LENGTH || header_length(buffer)<MIN_HEADER_LENGTH || buffer.len()<header_length(buffer){
+            return None;
+        }
         let result:IpHeader=IpHeader{
-            version_and_hdrlen:buffer[0],
-            type_of_service:buffer[1],
-            total_length:(buffer[2]as u16)<<8| buffer[3] as u16,
             id:(buffer[4]as u16)<<8| buffer[5] as u16,			
             flags_and_fragment_offset:(buffer[6]as u16)<<8| buffer[7] as u16,
-            time_to_live:buffer[8],
             upper_protocol_type:buffer[9],
-            check_sum:(buffer[10] as u16)<<8| buffer[11] as u16,
-            source_ip:(buffer[12] as u32)<<24| (buffer[13] as u32)<<16|(buffer[14] as u32)<<8 | buffer[15] as u32,  
-            destination_ip:(buffer[16] as u32)<<24| (buffer[17] as u32)<<16|(buffer[18] as u32)<<8 | buffer[19] as u32,
-            optional:buffer[20..60].to_vec().clone().try_into().unwrap(),
+            options:parse_header_options(buffer)?,
         };
-        result
+        Some(result)
     }
 }
 
-///接收队列，下层协议交付时写入此结构
+///接收队列，下层协议交付时写入此结构，元素为(收到该分组的接口，分组)
 pub struct IpReceiveQueue(
-    VecDeque<Vec<u8>>
+    VecDeque<(usize,Vec<u8>)>
 );
 
 impl IpReceiveQueue{
@@ -84,17 +75,18 @@ impl IpReceiveQueue{
 
     /// ### 功能
     /// data_link层向其中写入数据。
-    /// 交付的数据应该在一定长度之间，该函数会检查。
-    pub fn add_data(&mut self,buffer: &Vec<u8>) -> bool{
-        if buffer.len()>1500 || buffer.len()<46{
+    /// 交付的数据应该在一定长度之间，该函数会检查：回环接口最长为回环接口的MTU，其他接口为1500
+    pub fn add_data(&mut self,interface:usize,buffer: &[u8]) -> bool{
+        let max_length=if interface==LOOPBACK_INTERFACE {LOOPBACK_MTU} else {1500};
+        if buffer.len()>max_length || buffer.len()<MIN_HEADER_LENGTH{
             return false;
         }
-        self.0.push_back(buffer.clone());
+        self.0.push_back((interface,buffer.to_vec()));
         true
     }
     /// ### 功能
     /// 获取第一个数据
-    pub fn get_data(&mut self)-> Option<Vec<u8>>{
+    pub fn get_data(&mut self)-> Option<(usize,Vec<u8>)>{
         self.0.pop_front()
     }
     
@@ -106,190 +98,178 @@ impl IpReceiveQueue{
 }
 
 
-/// 一个队列，其中的元素是id对应的各数据报的缓冲区
-/// ### 数据结构
-/// 每一个元素包括四部分：数据报id，数据队列，一个用于指示已经接收多少字节的的指针，一个(分片起始位置，分片长度)队列，数据应有的总长度
-struct ReceiveDataQueue(
-    Vec<(u16,[u8;65536],u32,Vec<(u32,u32)>,u32)>
-);
-impl ReceiveDataQueue {
-    /// ### 功能
-    /// 根据id找到对应的缓冲区
-    pub fn find(&mut self,id:u16)->Option<&mut (u16,[u8;65536],u32,Vec<(u32,u32)>,u32)>{
-        for i in &mut self.0{
-            if i.0==id{
-                return Some(i);
-            }
-        }
+/// ### 功能
+/// 目的地址为本机而源路由选项尚未走完时，用源路由中的下一个地址替换目的地址，
+/// 并在该位置记录本机的地址
+/// ### 返回值
+/// Option，需要继续转发时返回修改后的分组
+fn next_source_route_hop(
+    shared_interface_table:&Arc<Mutex<InterfaceTable>>,
+    interface:usize,
+    hdr:&IpHeader,
+    packet:&[u8]
+)->Option<Vec<u8>>{
+    let local_ip=shared_interface_table.lock().unwrap().get(interface)?.ip;
+    let mut options=hdr.options.clone();
+    let next_hop=options.iter_mut().find_map(|option|option.next_source_route_hop(local_ip))?;
+    let mut packet=packet.to_vec();
+    packet[16..20].copy_from_slice(&next_hop);
+    if !replace_header_options(&mut packet,&options){
         return None;
     }
-    /// ###功能
-    /// 根据id，找到对应的该id的缓冲区，并插入数据到指定位置
-    /// ### 返回值
-    /// 是否完成一个数据报
-    pub fn insert_data(&mut self,id:u16,data:Vec<u8>,offset:u16,len:u16,MF:bool,DF:bool)->bool{
-        let element=self.find(id).unwrap();
-
-        //单位换算为字节
-        let offset=offset*8;
-        //插入数据
-        element.1[offset as usize..(offset+len) as usize].copy_from_slice(data.as_slice());
-
-        //插入下标组
-        let mut flag=false;
-        for i in &element.3{
-            //检查是否存在该分片
-            if i.0==offset as u32{
-                flag=true;
-                break;
-            }
-        }
-        if flag==false{
-            //不存在该分片
-            element.3.push((offset.into(),len.into()));
-        }
-        //维护指针，每次都重新计算
-        while find_receive_data_queue_len(&element.3,element.2).is_some() {
-            element.2+=find_receive_data_queue_len(&element.3,element.2).unwrap();
-        }
-        //维护该数据报应有的数据长度
-        if (DF==true)|(DF==false&&MF==false){
-            element.4=(offset+len).into();
-        }
-        element.2==element.4
-    }
-    /// ###功能
-    /// 创建一个与id对应的缓冲区，用于接收IP分组
-    /// ### 返回值
-    /// 是否完成一个数据报
-    pub fn create_new(&mut self,id:u16,data:Vec<u8>,offset:u16,len:u16,MF:bool,DF:bool)->bool{
-        let mut element:  (u16, [u8; 65536], u32, Vec<(u32, u32)>,u32)=(
-            0,
-            [0;65536],
-            0,
-            Vec::new(),
-            65535
-        );
-        element.0=id;
-        //插入数据
-        element.1[offset as usize..(offset+len) as usize].copy_from_slice(data.as_slice());
-
-        //插入下标组
-        //一定不存在该分片
-        element.3.push((offset.into(),len.into()));
-        
-        //维护指针
-        while find_receive_data_queue_len(&element.3,element.2).is_some() {
-            element.2+=find_receive_data_queue_len(&element.3,element.2).unwrap();
-        }
-        //维护该数据报应有的数据长度
-        if (DF==true)|(DF==false&&MF==false){
-            element.4=(offset+len).into();
-        }
-        let flag=element.2==element.4;//返回值。如果当前长度等于总长度，则代表数据报已经接收完整。
-        self.0.push(element);
-        flag
-    }
-
-    /// ###功能
-    /// 删除一个与id对应的缓冲区
-    /// ### 返回值
-    /// 是否删除成功
-    pub fn delete_element(&mut self,id:u16)-> bool{
-        let mut index=0;
-        for i in &mut self.0{
-            if i.0==id{
-                self.0.remove(index);
-                return true;
-            }
-            index=index+1;
-        }
-        false
-    }
-}
-
-/// ###功能
-/// 从(分片起始位置，分片长度)队列中找到起始位置对应的分片长度
-pub fn find_receive_data_queue_len(vec:&Vec<(u32,u32)>,current_ptr:u32)->Option<u32>{
-    for i in vec{
-        if i.0==current_ptr{
-            return Some(i.1);
-        }
-    }
-    None
+    update_check_sum(&mut packet);
+    Some(packet)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn receive(
+    shared_interface_table:Arc<Mutex<InterfaceTable>>,
+    shared_routing_table:Arc<Mutex<RoutingTable>>,
     shared_ip_receive_queue:Arc<Mutex<IpReceiveQueue>>,
-    shared_icmp_receive_queue:Arc<Mutex<IcmpReceiveQueue>>) {
-    let mut receive_data_queue:ReceiveDataQueue=ReceiveDataQueue(Vec::new());
+    shared_ip_forward_queue:Arc<Mutex<IpForwardQueue>>,
+    shared_ip_send_queue:Arc<Mutex<IpSendQueue>>,
+    shared_multicast_group_table:Arc<Mutex<MulticastGroupTable>>,
+    shared_nat_table:Arc<Mutex<NatTable>>,
+    shared_filter_table:Arc<Mutex<FilterTable>>,
+    shared_connection_table:Arc<Mutex<ConnectionTable>>,
+    shared_protocol_registry:Arc<Mutex<ProtocolRegistry>>) {
+    let rpf_mode=RpfMode::from_name(REVERSE_PATH_FILTER).unwrap_or_else(||{
+        println!("反向路径检查方式{}无效，使用loose",REVERSE_PATH_FILTER);
+        RpfMode::Loose
+    });
     loop{
+        //丢弃重组超时的数据报，code=1代表分片重组超时
+        let first_fragments=REASSEMBLER.lock().unwrap().expire();
+        for first_fragment in first_fragments{
+            send_error(&shared_ip_send_queue,ICMP_TIME_EXCEEDED,1,0,&first_fragment);
+        }
+
         // 队列为空则直接跳过
         if shared_ip_receive_queue.lock().unwrap().is_empty(){
             yield_now();
             //因为下面代码在else块里，所以无需continue;
         }
         else {
-        // 对于每一个分组，根据id判断其所属的数据报。
-        // 如果为新的，则新建一个缓冲区存放
-        // 如果为旧的，跟已有的拼接，如果拼接为完整，则写入。
+        // 对于每一个分组，不是分片则直接交付；
+        // 是分片则交给重组器，与同一数据报的其他分片拼接，拼接为完整后交付。
             // 获取并解析
-            let data_from_data_link_layer=shared_ip_receive_queue.lock().unwrap().get_data().unwrap();
-            if data_from_data_link_layer.len()<60{
-                //小于60，肯定不是一个IP数据分组
-                yield_now();
+            let (interface,mut data_from_data_link_layer)=shared_ip_receive_queue.lock().unwrap().get_data().unwrap();
+            IP_RECEIVE_STATISTICS.lock().unwrap().received+=1;
+
+            //校验首部，并去掉链路层的填充
+            let total_length=match validate_header(&data_from_data_link_layer) {
+                Ok(total_length)=>total_length,
+                Err(reason)=>{
+                    IP_RECEIVE_STATISTICS.lock().unwrap().record_drop(reason,&data_from_data_link_layer);
+                    continue;
+                }
+            };
+            data_from_data_link_layer.truncate(total_length);
+
+            //丢弃火星地址，并按源地址做反向路径检查，防止伪造源地址
+            let source_ip:[u8;4]=data_from_data_link_layer[12..16].try_into().unwrap();
+            let destination_ip:[u8;4]=data_from_data_link_layer[16..20].try_into().unwrap();
+            if let Err(reason)=check_martian(&shared_interface_table.lock().unwrap(),interface,source_ip,destination_ip){
+                IP_RECEIVE_STATISTICS.lock().unwrap().record_drop(reason,&data_from_data_link_layer);
+                continue;
+            }
+            if !check_reverse_path(&shared_routing_table.lock().unwrap(),rpf_mode,interface,source_ip){
+                IP_RECEIVE_STATISTICS.lock().unwrap().record_drop(IpDropReason::ReversePath,&data_from_data_link_layer);
                 continue;
             }
-            let hdr=IpHeader::from_u8( & data_from_data_link_layer);
 
-            //查询是否在接收这个id
-            let flag_exists:bool=receive_data_queue.find(hdr.id).is_some();
-            
+            //路由器模式下反向转换NAT返回的分组，转换后目的地址为内部主机，随后被转发
+            if IP_FORWARDING{
+                shared_nat_table.lock().unwrap().translate_inbound(&mut data_from_data_link_layer);
+            }
 
-            let complete_flag;
-            if flag_exists{
-                //如果是已经接收过这个分组
-                complete_flag=receive_data_queue.insert_data(
-                    hdr.id,
-                    data_from_data_link_layer[60..data_from_data_link_layer.len()].to_vec(), 
-                    hdr.flags_and_fragment_offset & 0b0001_1111_1111_1111, 
-                    hdr.total_length-60,
-                    (hdr.flags_and_fragment_offset&(1<<13))==(1<<13),
-                    (hdr.flags_and_fragment_offset&(1<<14))==(1<<14)
-                );
+            //目的地址不是本机：路由器模式下交给转发，否则丢弃。NAT可能改写了目的地址
+            let destination_ip:[u8;4]=data_from_data_link_layer[16..20].try_into().unwrap();
+            //组播只接收本接口加入的组，不转发
+            if is_multicast_ip(destination_ip) && !shared_multicast_group_table.lock().unwrap().is_member(interface,destination_ip){
+                IP_RECEIVE_STATISTICS.lock().unwrap().record_drop(IpDropReason::NotForUs,&data_from_data_link_layer);
+                continue;
             }
-            else {
-                //如果是新的分组
-                complete_flag=receive_data_queue.create_new(
-                    hdr.id,
-                    data_from_data_link_layer[60..data_from_data_link_layer.len()].to_vec(), 
-                    hdr.flags_and_fragment_offset & 0b0001_1111_1111_1111, 
-                    hdr.total_length-60,
-                    (hdr.flags_and_fragment_offset&(1<<13))==(1<<13),
-                    (hdr.flags_and_fragment_offset&(1<<14))==(1<<14)
-                );
-                //如果是新分组，那么肯定是没有间隙的数据
-                
+            let for_us={
+                let interface_table=shared_interface_table.lock().unwrap();
+                is_loopback_ip(destination_ip) || is_multicast_ip(destination_ip)
+                    || interface_table.is_local_ip(destination_ip) || interface_table.is_broadcast_ip(destination_ip)
+            };
+            if !for_us{
+                if IP_FORWARDING{
+                    IP_RECEIVE_STATISTICS.lock().unwrap().accepted+=1;
+                    shared_ip_forward_queue.lock().unwrap().add_data(interface,data_from_data_link_layer);
+                }
+                else{
+                    IP_RECEIVE_STATISTICS.lock().unwrap().record_drop(IpDropReason::NotForUs,&data_from_data_link_layer);
+                }
+                continue;
             }
-            if complete_flag{//如果接收完该分组后数据报完整，则写入到文件中，或者交付给其他协议
-                let element=receive_data_queue.find(hdr.id).unwrap();
-                let data=&element.1[0..element.4 as usize];
-                let id=element.0;
-                if hdr.upper_protocol_type==UDP_PROTOCOL{
-                    //打开文件
-                    let file_path=String::from("receive.data");
-                    let mut file=File::create(file_path).unwrap();
-                    //写入文件
-                    file.write_all(data).unwrap();
-                    
+
+            //过滤：input挂载点。先只查询连接状态，通过后才跟踪，被丢弃的分组不建立连接；
+            //转发的分组在转发时跟踪
+            let state=shared_connection_table.lock().unwrap().classify(&data_from_data_link_layer);
+            let action=shared_filter_table.lock().unwrap().check(Hook::Input,&FilterPacket{
+                in_interface:Some(interface),
+                out_interface:None,
+                frame_header:None,
+                packet:Some(&data_from_data_link_layer),
+                state:Some(state),
+                length:data_from_data_link_layer.len(),
+            });
+            match action {
+                FilterAction::Accept=>{
+                    shared_connection_table.lock().unwrap().track(&data_from_data_link_layer);
                 }
-                else if hdr.upper_protocol_type==ICMPV4_PROTOCOL{
-                    //写入ICMPv4
-                    shared_icmp_receive_queue.lock().unwrap().add_data(data.to_vec());
+                FilterAction::Drop=>continue,
+                FilterAction::Reject=>{
+                    if !is_multicast_ip(destination_ip) && !shared_interface_table.lock().unwrap().is_broadcast_ip(destination_ip){
+                        reject(&shared_ip_send_queue,&data_from_data_link_layer);
+                    }
+                    continue;
+                }
+            }
+
+            let hdr=match IpHeader::from_u8( & data_from_data_link_layer) {
+                Some(hdr)=>hdr,
+                None=>{
+                    IP_RECEIVE_STATISTICS.lock().unwrap().record_drop(IpDropReason::BadOptions,&data_from_data_link_layer);
+                    continue;
                 }
+            };
+            IP_RECEIVE_STATISTICS.lock().unwrap().accepted+=1;
 
-                //将已经完成的缓冲区从receive_data_queue中删除
-                receive_data_queue.delete_element(id);
+            //源路由尚未走完：取出下一个地址作为目的地址，继续转发
+            if IP_FORWARDING{
+                if let Some(packet)=next_source_route_hop(&shared_interface_table,interface,&hdr,&data_from_data_link_layer){
+                    shared_ip_forward_queue.lock().unwrap().add_data(interface,packet);
+                    continue;
+                }
+            }
+
+            //不是分片（MF=0且偏移为0）则直接交付，否则交给重组器
+            let packet=if hdr.flags_and_fragment_offset & (FLAG_MF | FRAGMENT_OFFSET_MASK)==0 {
+                data_from_data_link_layer
+            }
+            else{
+                let result=REASSEMBLER.lock().unwrap().insert(&data_from_data_link_layer);
+                match result {
+                    ReassemblyResult::Complete(packet)=>packet,
+                    ReassemblyResult::Incomplete=>continue,
+                    ReassemblyResult::Dropped(error)=>{
+                        println!("丢弃来自{}的数据报（标识{}）：{:?}",format_ip(source_ip),hdr.id,error);
+                        continue;
+                    }
+                }
+            };
+            //数据报完整，交付给注册的上层协议；协议没有注册时回送ICMP协议不可达（code=2），广播与组播不回送
+            let datagram=IpDatagram{interface,packet};
+            let delivered=shared_protocol_registry.lock().unwrap().deliver(&datagram);
+            if !delivered{
+                println!("没有注册上层协议{}，丢弃来自{}的数据报",hdr.upper_protocol_type,format_ip(datagram.source_ip()));
+                if !is_multicast_ip(destination_ip) && !shared_interface_table.lock().unwrap().is_broadcast_ip(destination_ip){
+                    send_error(&shared_ip_send_queue,ICMP_DESTINATION_UNREACHABLE,2,0,&datagram.packet);
+                }
             }
         }
     }
@@ -0,0 +1,160 @@
+use crate::data_link_layer::interface::InterfaceTable;
+use crate::tools::address::is_multicast_ip;
+
+use super::loopback::{is_loopback_ip,LOOPBACK_INTERFACE};
+use super::route::RoutingTable;
+use super::validate::IpDropReason;
+
+/// 反向路径检查（RFC 3704）的方式
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum RpfMode{
+    /// 不检查
+    Off,
+    /// 宽松：存在到源地址的路由即可（默认路由也算）
+    Loose,
+    /// 严格：到源地址的路由必须从收到分组的接口发出
+    Strict,
+}
+
+impl RpfMode{
+    /// ### 功能
+    /// 由配置中的名称解析检查方式
+    pub fn from_name(name:&str)->Option<RpfMode>{
+        match name.to_ascii_lowercase().as_str() {
+            "off"=>Some(RpfMode::Off),
+            "loose"=>Some(RpfMode::Loose),
+            "strict"=>Some(RpfMode::Strict),
+            _=>None,
+        }
+    }
+}
+
+/// ### 功能
+/// 地址是否属于0.0.0.0/8（"本网络"，不能出现在网络上的分组中）
+fn is_this_network_ip(ip:[u8;4])->bool{
+    ip[0]==0
+}
+
+/// ### 功能
+/// 地址是否属于E类保留地址240.0.0.0/4（受限广播255.255.255.255除外）
+fn is_reserved_ip(ip:[u8;4])->bool{
+    ip[0]>=240 && ip!=[255;4]
+}
+
+/// ### 功能
+/// 检查从interface收到的分组的源地址与目的地址，丢弃不可能合法出现的"火星"地址（RFC 1812 5.3.7）：
+/// - 源地址或目的地址属于0.0.0.0/8、E类地址，或从回环以外的接口收到的127.0.0.0/8
+/// - 源地址为广播（受限广播或本机某个子网的广播）或组播地址
+/// - 从回环以外的接口收到以本机地址为源地址的分组
+/// ### 返回值
+/// Result，是火星地址时为丢弃原因
+pub fn check_martian(interface_table:&InterfaceTable,interface:usize,source_ip:[u8;4],destination_ip:[u8;4])->Result<(),IpDropReason>{
+    if is_this_network_ip(destination_ip) || is_reserved_ip(destination_ip)
+        || (is_loopback_ip(destination_ip) && interface!=LOOPBACK_INTERFACE){
+        return Err(IpDropReason::MartianDestination);
+    }
+    if interface==LOOPBACK_INTERFACE{
+        return Ok(());
+    }
+    if is_this_network_ip(source_ip) || is_reserved_ip(source_ip) || is_loopback_ip(source_ip)
+        || is_multicast_ip(source_ip) || interface_table.is_broadcast_ip(source_ip) || interface_table.is_local_ip(source_ip){
+        return Err(IpDropReason::MartianSource);
+    }
+    Ok(())
+}
+
+/// ### 功能
+/// 反向路径检查：按mode检查到源地址的路由，从回环接口收到的分组不检查
+/// ### 返回值
+/// 是否通过
+pub fn check_reverse_path(routing_table:&RoutingTable,mode:RpfMode,interface:usize,source_ip:[u8;4])->bool{
+    if interface==LOOPBACK_INTERFACE{
+        return true;
+    }
+    match mode {
+        RpfMode::Off=>true,
+        RpfMode::Loose=>routing_table.lookup(source_ip).is_some(),
+        RpfMode::Strict=>routing_table.lookup(source_ip).is_some_and(|route|route.interface==interface),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_layer::ip::route::{RouteEntry,RouteType};
+    use crate::tools::global_variables::LOCAL_IP;
+
+    /// 本机10.10.8.0/21在接口0上直连，192.168.1.0/24在接口1上直连，默认路由经接口0
+    fn routing_table()->RoutingTable{
+        let mut routing_table=RoutingTable::new();
+        routing_table.add_connected_routes(&InterfaceTable::new());
+        routing_table.add_route(RouteEntry{
+            destination:[192,168,1,0],
+            netmask:[255,255,255,0],
+            gateway:None,
+            interface:1,
+            route_type:RouteType::Connected,
+            metric:0,
+        });
+        routing_table
+    }
+
+    #[test]
+    fn martian_destination(){
+        let interface_table=InterfaceTable::new();
+        for destination_ip in [[0,1,2,3],[240,0,0,1],[127,0,0,1]]{
+            assert_eq!(check_martian(&interface_table,0,[10,10,10,9],destination_ip),Err(IpDropReason::MartianDestination));
+        }
+        assert_eq!(check_martian(&interface_table,0,[10,10,10,9],[255,255,255,255]),Ok(()));
+        assert_eq!(check_martian(&interface_table,LOOPBACK_INTERFACE,[127,0,0,1],[127,0,0,1]),Ok(()));
+    }
+
+    #[test]
+    fn martian_source(){
+        let interface_table=InterfaceTable::new();
+        let broadcast_ip=[10,10,15,255];
+        for source_ip in [[0,0,0,0],[240,0,0,1],[127,0,0,1],[224,0,0,1],[255,255,255,255],broadcast_ip,LOCAL_IP]{
+            assert_eq!(check_martian(&interface_table,0,source_ip,LOCAL_IP),Err(IpDropReason::MartianSource));
+        }
+        assert_eq!(check_martian(&interface_table,0,[10,10,10,9],LOCAL_IP),Ok(()));
+        //回环接口上本机地址作为源地址是正常的
+        assert_eq!(check_martian(&interface_table,LOOPBACK_INTERFACE,LOCAL_IP,LOCAL_IP),Ok(()));
+    }
+
+    #[test]
+    fn mode_from_name(){
+        assert_eq!(RpfMode::from_name("off"),Some(RpfMode::Off));
+        assert_eq!(RpfMode::from_name("Loose"),Some(RpfMode::Loose));
+        assert_eq!(RpfMode::from_name("STRICT"),Some(RpfMode::Strict));
+        assert_eq!(RpfMode::from_name("feasible"),None);
+    }
+
+    #[test]
+    fn reverse_path_off(){
+        let routing_table=RoutingTable::new();
+        assert!(check_reverse_path(&routing_table,RpfMode::Off,0,[8,8,8,8]));
+    }
+
+    #[test]
+    fn reverse_path_loose(){
+        let mut routing_table=routing_table();
+        //没有默认路由时，没有路由的源地址不通过
+        assert!(!check_reverse_path(&routing_table,RpfMode::Loose,0,[8,8,8,8]));
+        //有路由即可，不论出接口
+        assert!(check_reverse_path(&routing_table,RpfMode::Loose,0,[192,168,1,7]));
+        assert!(routing_table.set_default_route([10,10,10,1]));
+        assert!(check_reverse_path(&routing_table,RpfMode::Loose,1,[8,8,8,8]));
+    }
+
+    #[test]
+    fn reverse_path_strict(){
+        let routing_table=routing_table();
+        assert!(check_reverse_path(&routing_table,RpfMode::Strict,1,[192,168,1,7]));
+        assert!(check_reverse_path(&routing_table,RpfMode::Strict,0,[10,10,10,9]));
+        //到源地址的路由从其他接口发出
+        assert!(!check_reverse_path(&routing_table,RpfMode::Strict,0,[192,168,1,7]));
+        assert!(!check_reverse_path(&routing_table,RpfMode::Strict,1,[8,8,8,8]));
+        //回环接口收到的分组总是通过
+        assert!(check_reverse_path(&routing_table,RpfMode::Strict,LOOPBACK_INTERFACE,[8,8,8,8]));
+    }
+}
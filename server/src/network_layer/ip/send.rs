@@ -0,0 +1,398 @@
+use std::collections::{HashMap,VecDeque};
+use std::sync::{Arc,Mutex};
+use std::sync::mpsc::Sender;
+use std::thread::yield_now;
+use std::time::{Duration,Instant};
+use lazy_static::*;
+
+use crate::data_link_layer::ethernet_v2::send::Eth2SendQueue;
+use crate::data_link_layer::interface::InterfaceTable;
+use crate::network_layer::arp::cache_table::ArpCacheTable;
+use crate::network_layer::filter::conntrack::ConnectionTable;
+use crate::network_layer::filter::rule::{FilterAction,FilterPacket,Hook};
+use crate::network_layer::filter::table::FilterTable;
+use crate::tools::address::{format_ip,is_multicast_ip,multicast_mac};
+use crate::tools::global_variables::PATH_MTU_DISCOVERY;
+
+use super::header::{build_header,fragment,FLAG_DF,MIN_HEADER_LENGTH};
+use super::id::IdGenerator;
+use super::loopback::{is_loopback_ip,LOOPBACK_INTERFACE,LOOPBACK_IP,LOOPBACK_MTU};
+use super::pmtu::PathMtuCache;
+use super::option::{encode_options,IpOption};
+use super::receive::IpReceiveQueue;
+use super::route::RoutingTable;
+use super::tunnel::Tunnel;
+
+lazy_static!{
+    ///静态变量--IP发送队列，本机产生的数据报（如ICMP差错报文）由此发送
+    pub static ref IP_SEND_QUEUE:Arc<Mutex<IpSendQueue>> = Arc::new(Mutex::new(IpSendQueue::new()));
+    ///静态变量--IP输出队列，已经封装好、确定了出接口与下一跳的分组（如转发的分组）由此发送
+    pub static ref IP_OUTPUT_QUEUE:Arc<Mutex<IpOutputQueue>> = Arc::new(Mutex::new(IpOutputQueue::new()));
+}
+
+/// 本机产生的数据报的默认生存时间
+pub const DEFAULT_TIME_TO_LIVE:u8=64;
+/// DSCP-CS6（网络控制），用于ICMP差错报文、IGMP等控制报文（RFC 4594）
+pub const DSCP_CS6:u8=48;
+/// 等待下一跳ARP解析的最长时间，超时则丢弃分组
+const ARP_RESOLVE_TIMEOUT:Duration=Duration::from_secs(3);
+
+/// IP发送队列的元素。由new生成默认值，上层协议可以再修改其中的字段
+pub struct IpSendQueueElement{
+    /// 目的IP地址
+    pub destination_ip:[u8;4],
+    /// 源地址提示，必须是本机某个接口的地址；为None时使用出接口的地址
+    pub source_ip:Option<[u8;4]>,
+    /// 生存时间，traceroute等探测可以设置较小的值
+    pub time_to_live:u8,
+    /// 服务类型，高6位为DSCP，低2位为ECN
+    pub type_of_service:u8,
+    /// 是否禁止分片（DF）：Some(true)时超过MTU的数据报发送失败；Some(false)时不带DF，
+    /// 不参与路径MTU发现；None时由路径MTU发现决定
+    pub dont_fragment:Option<bool>,
+    /// 上层协议
+    pub protocol_type:u8,
+    /// 上层数据
+    pub data:Vec<u8>,
+    /// IP首部的选项
+    pub options:Vec<IpOption>,
+    /// 发送结果的通知通道：写入输出队列后通知Ok，失败时通知错误原因
+    pub result_sender:Option<Sender<Result<(),IpSendError>>>,
+}
+
+impl IpSendQueueElement{
+    /// ### 功能
+    /// 生成发往destination_ip的数据报，其余字段取默认值：
+    /// 源地址为出接口地址，TTL为64，服务类型为0，DF由路径MTU发现决定，不带选项，不通知结果
+    pub fn new(destination_ip:[u8;4],protocol_type:u8,data:Vec<u8>)->Self{
+        IpSendQueueElement{
+            destination_ip,
+            source_ip:None,
+            time_to_live:DEFAULT_TIME_TO_LIVE,
+            type_of_service:0,
+            dont_fragment:None,
+            protocol_type,
+            data,
+            options:Vec::new(),
+            result_sender:None,
+        }
+    }
+    /// ### 功能
+    /// 设置DSCP（服务类型的高6位），保留ECN
+    pub fn set_dscp(&mut self,dscp:u8){
+        self.type_of_service=(dscp<<2) | (self.type_of_service & 0b11);
+    }
+    /// ### 功能
+    /// 通过通知通道报告发送结果，上层已不再等待时忽略
+    fn report(&self,result:Result<(),IpSendError>){
+        if let Some(sender)=&self.result_sender{
+            let _=sender.send(result);
+        }
+    }
+}
+
+/// IP发送失败的原因
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum IpSendError{
+    /// 没有到目的地址的路由
+    NoRoute,
+    /// 数据报超过MTU，但DF置位不允许分片
+    FragmentationNeeded{mtu:usize},
+    /// 选项超过40字节
+    OptionsTooLong,
+    /// 被过滤规则丢弃或拒绝
+    Filtered,
+    /// 回环接口的接收队列拒绝了数据报
+    LoopbackRejected,
+}
+
+impl std::fmt::Display for IpSendError{
+    fn fmt(&self,f:&mut std::fmt::Formatter<'_>)->std::fmt::Result{
+        match self {
+            IpSendError::NoRoute=>write!(f,"没有路由"),
+            IpSendError::FragmentationNeeded{mtu}=>write!(f,"数据报超过MTU {}，且不允许分片",mtu),
+            IpSendError::OptionsTooLong=>write!(f,"IP选项超过40字节"),
+            IpSendError::Filtered=>write!(f,"被过滤规则禁止"),
+            IpSendError::LoopbackRejected=>write!(f,"回环接口的接收队列拒绝了数据报"),
+        }
+    }
+}
+
+///IP发送队列，由上层协议写入
+pub struct IpSendQueue(
+    VecDeque<IpSendQueueElement>
+);
+
+impl IpSendQueue{
+    /// 生成发送队列
+    pub fn new() -> Self{
+        let new_send_queue=VecDeque::new();
+        IpSendQueue(new_send_queue)
+    }
+    /// 由上层协议写入一个设置好的元素（源地址、TTL、服务类型、DF、选项、结果通知等）
+    pub fn add_element(&mut self,element:IpSendQueueElement) -> bool{
+        self.0.push_back(element);
+        true
+    }
+    /// 获取队列数据
+    pub fn get_data(&mut self)-> Option<IpSendQueueElement>{
+        self.0.pop_front()
+    }
+}
+
+/// IP输出队列的元素
+pub struct IpOutputQueueElement{
+    /// 出接口
+    interface:usize,
+    /// 下一跳IP地址
+    next_hop:[u8;4],
+    /// 完整的IP分组
+    packet:Vec<u8>
+}
+
+///IP输出队列
+pub struct IpOutputQueue(
+    VecDeque<IpOutputQueueElement>
+);
+
+impl IpOutputQueue{
+    /// 生成输出队列
+    pub fn new() -> Self{
+        let new_output_queue=VecDeque::new();
+        IpOutputQueue(new_output_queue)
+    }
+    /// 写入一个已经封装好的分组
+    pub fn add_data(&mut self,interface:usize,next_hop:[u8;4],packet:Vec<u8>) -> bool{
+        self.0.push_back(IpOutputQueueElement{
+            interface,
+            next_hop,
+            packet
+        });
+        true
+    }
+    /// 获取队列数据
+    pub fn get_data(&mut self)-> Option<IpOutputQueueElement>{
+        self.0.pop_front()
+    }
+}
+
+/// 封装好的数据报：(出接口，下一跳，依次发送的分组)
+type BuiltPackets=(usize,[u8;4],Vec<Vec<u8>>);
+
+/// ### 功能
+/// 为本机产生的数据报查找路由、封装首部，并按出接口的MTU与路径MTU中较小者分片。
+/// 开启路径MTU发现时，不超过该MTU的数据报带DF标志发出（RFC 1191），调用者指定了DF时以调用者为准
+/// ### 返回值
+/// Result，成功时为(出接口，下一跳，依次发送的分组)
+fn build_packets(
+    shared_interface_table:&Arc<Mutex<InterfaceTable>>,
+    shared_routing_table:&Arc<Mutex<RoutingTable>>,
+    shared_path_mtu_cache:&Arc<Mutex<PathMtuCache>>,
+    shared_filter_table:&Arc<Mutex<FilterTable>>,
+    shared_connection_table:&Arc<Mutex<ConnectionTable>>,
+    id_generator:&mut IdGenerator,
+    element:&IpSendQueueElement
+)->Result<BuiltPackets,IpSendError>{
+    //目的地址是本机或回环网络时使用回环接口，不查找路由
+    let (interface,next_hop,interface_ip,interface_mtu)=if is_loopback_ip(element.destination_ip) {
+        (LOOPBACK_INTERFACE,element.destination_ip,LOOPBACK_IP,LOOPBACK_MTU)
+    }
+    else if shared_interface_table.lock().unwrap().is_local_ip(element.destination_ip) {
+        (LOOPBACK_INTERFACE,element.destination_ip,element.destination_ip,LOOPBACK_MTU)
+    }
+    else if is_multicast_ip(element.destination_ip) {
+        //组播：从源地址所在的接口发出，没有源地址时使用第一个接口；下一跳即组地址
+        let interface_table=shared_interface_table.lock().unwrap();
+        let interface=element.source_ip
+            .and_then(|source_ip|interface_table.interfaces().iter().find(|interface|interface.has_ip(source_ip)))
+            .or_else(||interface_table.get(0))
+            .ok_or(IpSendError::NoRoute)?;
+        (interface.index,element.destination_ip,interface.ip,interface.mtu)
+    }
+    else{
+        let route=shared_routing_table.lock().unwrap().lookup(element.destination_ip).ok_or(IpSendError::NoRoute)?;
+        let interface=match shared_interface_table.lock().unwrap().get(route.interface) {
+            Some(interface)=>interface.clone(),
+            None=>return Err(IpSendError::NoRoute),
+        };
+        let next_hop=route.gateway.unwrap_or(element.destination_ip);
+        (interface.index,next_hop,interface.select_source(element.destination_ip,next_hop),interface.mtu)
+    };
+    let options=encode_options(&element.options).ok_or(IpSendError::OptionsTooLong)?;
+
+    //源地址提示不是本机地址时忽略，没有提示时使用按出接口选择的地址
+    let source_ip=match element.source_ip {
+        Some(source_ip) if is_loopback_ip(source_ip) || shared_interface_table.lock().unwrap().is_local_ip(source_ip)=>source_ip,
+        _=>interface_ip,
+    };
+
+    let mtu=match shared_path_mtu_cache.lock().unwrap().get(element.destination_ip) {
+        Some(path_mtu)=>path_mtu.min(interface_mtu),
+        None=>interface_mtu,
+    };
+    let total_length=MIN_HEADER_LENGTH+options.len()+element.data.len();
+    //超过MTU的数据报按MTU分片后发出，分片不带DF
+    let dont_fragment=element.dont_fragment.unwrap_or(PATH_MTU_DISCOVERY && total_length<=mtu);
+
+    let id=id_generator.next_id(element.destination_ip);
+    let mut packet=build_header(
+        element.type_of_service,
+        total_length as u16,
+        id,
+        if dont_fragment {FLAG_DF} else {0},
+        element.time_to_live,
+        element.protocol_type,
+        source_ip,
+        element.destination_ip,
+        &options
+    );
+    packet.extend_from_slice(&element.data);
+
+    //过滤：output挂载点，在分片之前检查完整的数据报。通过后才跟踪，被丢弃的分组不建立连接
+    let state=shared_connection_table.lock().unwrap().classify(&packet);
+    let action=shared_filter_table.lock().unwrap().check(Hook::Output,&FilterPacket{
+        in_interface:None,
+        out_interface:Some(interface),
+        frame_header:None,
+        packet:Some(&packet),
+        state:Some(state),
+        length:packet.len(),
+    });
+    if action!=FilterAction::Accept{
+        return Err(IpSendError::Filtered);
+    }
+    shared_connection_table.lock().unwrap().track(&packet);
+
+    let fragments=fragment(&packet,mtu).ok_or(IpSendError::FragmentationNeeded{mtu})?;
+    Ok((interface,next_hop,fragments))
+}
+
+/// ### 功能
+/// 隧道接口：把分组封装为发往隧道对端的外层数据报写入发送队列，由物理接口发出。
+/// 外层数据报复制内层的服务类型与DF标志（RFC 2003）
+fn encapsulate(
+    shared_routing_table:&Arc<Mutex<RoutingTable>>,
+    shared_ip_send_queue:&Arc<Mutex<IpSendQueue>>,
+    sequence_numbers:&mut HashMap<usize,u32>,
+    interface:usize,
+    tunnel:&Tunnel,
+    packet:&[u8]
+){
+    //到对端的路由经过隧道本身时会无限封装
+    if shared_routing_table.lock().unwrap().lookup(tunnel.remote_ip).is_some_and(|route|route.interface==interface){
+        println!("到隧道{}对端{}的路由经过隧道本身，丢弃分组",tunnel.name,format_ip(tunnel.remote_ip));
+        return;
+    }
+    let sequence_number=sequence_numbers.entry(interface).or_insert(0);
+    let mut outer=IpSendQueueElement::new(tunnel.remote_ip,tunnel.protocol(),tunnel.encapsulate(packet,*sequence_number));
+    *sequence_number=sequence_number.wrapping_add(1);
+    outer.source_ip=Some(tunnel.local_ip);
+    outer.type_of_service=packet[1];
+    outer.dont_fragment=Some(u16::from_be_bytes([packet[6],packet[7]]) & FLAG_DF!=0);
+    shared_ip_send_queue.lock().unwrap().add_element(outer);
+}
+
+/// ### 功能
+/// IP发送：
+/// - 为本机产生的数据报查找路由、封装首部、按出接口的MTU分片，写入输出队列
+/// - 目的地址是本机或回环网络的分组经回环接口直接交给本机的IP接收
+/// - 从隧道接口发出的分组封装为发往隧道对端的数据报，重新写入发送队列
+/// - 为输出队列中的分组解析下一跳的MAC地址，交给数据链路层。
+///   下一跳尚未解析时暂存分组，超时仍未解析则丢弃
+#[allow(clippy::too_many_arguments)]
+pub fn send(
+    shared_interface_table:Arc<Mutex<InterfaceTable>>,
+    shared_routing_table:Arc<Mutex<RoutingTable>>,
+    shared_path_mtu_cache:Arc<Mutex<PathMtuCache>>,
+    shared_filter_table:Arc<Mutex<FilterTable>>,
+    shared_connection_table:Arc<Mutex<ConnectionTable>>,
+    shared_arp_cache_table:Arc<Mutex<ArpCacheTable>>,
+    shared_ethernet_v2_send_queue:Arc<Mutex<Eth2SendQueue>>,
+    shared_ip_send_queue:Arc<Mutex<IpSendQueue>>,
+    shared_ip_output_queue:Arc<Mutex<IpOutputQueue>>,
+    shared_ip_receive_queue:Arc<Mutex<IpReceiveQueue>>
+){
+    //等待ARP解析的分组：(开始等待的时间，分组)
+    let mut pending:Vec<(Instant,IpOutputQueueElement)>=Vec::new();
+    //本机产生的数据报的标识，按目的地址分别生成
+    let mut id_generator=IdGenerator::new();
+    //GRE隧道的发送序号，按接口区分
+    let mut tunnel_sequence_numbers:HashMap<usize,u32>=HashMap::new();
+    loop{
+        let element=shared_ip_send_queue.lock().unwrap().get_data();
+        if let Some(element)=element{
+            let result=build_packets(&shared_interface_table,&shared_routing_table,&shared_path_mtu_cache,&shared_filter_table,&shared_connection_table,&mut id_generator,&element);
+            match result {
+                Ok((LOOPBACK_INTERFACE,_,fragments))=>{
+                    //回环接口：不经过ARP与以太网，直接交给本机的IP接收
+                    let mut receive_queue=shared_ip_receive_queue.lock().unwrap();
+                    let delivered=fragments.iter().all(|fragment|receive_queue.add_data(LOOPBACK_INTERFACE,fragment));
+                    drop(receive_queue);
+                    if delivered{
+                        element.report(Ok(()));
+                    }
+                    else{
+                        println!("发送到{}的数据报失败：{}",format_ip(element.destination_ip),IpSendError::LoopbackRejected);
+                        element.report(Err(IpSendError::LoopbackRejected));
+                    }
+                }
+                Ok((interface,next_hop,fragments))=>{
+                    let mut output_queue=shared_ip_output_queue.lock().unwrap();
+                    for fragment in fragments{
+                        output_queue.add_data(interface,next_hop,fragment);
+                    }
+                    element.report(Ok(()));
+                }
+                Err(err)=>{
+                    println!("发送到{}的数据报失败：{}",format_ip(element.destination_ip),err);
+                    element.report(Err(err));
+                }
+            }
+        }
+
+        let element=shared_ip_output_queue.lock().unwrap().get_data();
+        if let Some(element)=element{
+            if let Some(tunnel)=shared_interface_table.lock().unwrap().get(element.interface).and_then(|interface|interface.tunnel.clone()){
+                encapsulate(&shared_routing_table,&shared_ip_send_queue,&mut tunnel_sequence_numbers,element.interface,&tunnel,&element.packet);
+            }
+            else{
+                pending.push((Instant::now(),element));
+            }
+        }
+
+        if pending.is_empty(){
+            yield_now();
+            continue;
+        }
+
+        //发送已经解析出下一跳MAC的分组
+        let now=Instant::now();
+        let mut arp_cache_table=shared_arp_cache_table.lock().unwrap();
+        pending.retain(|(time,element)|{
+            //组播地址直接映射为MAC地址，不需要ARP
+            let dest_mac=if is_multicast_ip(element.next_hop) {
+                Some(multicast_mac(element.next_hop))
+            }
+            else{
+                arp_cache_table.find_mac_from_ip(element.next_hop)
+            };
+            match dest_mac {
+                Some(dest_mac)=>{
+                    shared_ethernet_v2_send_queue.lock().unwrap().add_data_to(element.interface,dest_mac,0x0800,&element.packet);
+                    false
+                }
+                None=>{
+                    if now.duration_since(*time)>ARP_RESOLVE_TIMEOUT{
+                        println!("无法解析下一跳{}的MAC地址，丢弃分组",format_ip(element.next_hop));
+                        false
+                    }
+                    else{
+                        true
+                    }
+                }
+            }
+        });
+        drop(arp_cache_table);
+        yield_now();
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::sync::{Arc,Mutex};
+
+use crate::data_link_layer::interface::InterfaceTable;
+use crate::tools::address::*;
+use crate::tools::checksum::internet_checksum;
+use crate::tools::global_variables::DEFAULT_MTU;
+
+use super::header::{header_length,MIN_HEADER_LENGTH};
+use super::protocol::{IpDatagram,PROTOCOL_GRE,PROTOCOL_IPIP};
+use super::receive::IpReceiveQueue;
+
+/// GRE首部中的标志位：校验和、路由、密钥、序号（RFC 2784、RFC 2890）
+const GRE_FLAG_CHECKSUM:u16=0x8000;
+const GRE_FLAG_ROUTING:u16=0x4000;
+const GRE_FLAG_KEY:u16=0x2000;
+const GRE_FLAG_SEQUENCE:u16=0x1000;
+/// GRE首部中的版本号，只支持0
+const GRE_VERSION_MASK:u16=0x0007;
+/// GRE首部中载荷的协议类型：IPv4
+const GRE_PROTOCOL_IPV4:u16=0x0800;
+
+/// 隧道的封装方式
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum TunnelKind{
+    /// IP-in-IP（RFC 2003），协议号4
+    Ipip,
+    /// GRE（RFC 2784），协议号47，可选密钥与序号（RFC 2890）
+    Gre{key:Option<u32>,sequence:bool},
+}
+
+/// 隧道接口的参数
+#[derive(Clone)]
+pub struct Tunnel{
+    /// 隧道名称，仅用于显示
+    pub name:String,
+    pub kind:TunnelKind,
+    /// 外层分组的源地址，必须是本机某个物理接口的地址
+    pub local_ip:[u8;4],
+    /// 外层分组的目的地址，即隧道对端。到对端的路由不能经过隧道本身
+    pub remote_ip:[u8;4],
+}
+
+impl Tunnel{
+    /// ### 功能
+    /// 外层分组的协议字段
+    pub fn protocol(&self)->u8{
+        match self.kind {
+            TunnelKind::Ipip=>PROTOCOL_IPIP,
+            TunnelKind::Gre{..}=>PROTOCOL_GRE,
+        }
+    }
+    /// ### 功能
+    /// 封装增加的长度：外层IP首部与GRE首部
+    pub fn overhead(&self)->usize{
+        match self.kind {
+            TunnelKind::Ipip=>MIN_HEADER_LENGTH,
+            TunnelKind::Gre{key,sequence}=>{
+                let key_length=if key.is_some() {4} else {0};
+                let sequence_length=if sequence {4} else {0};
+                MIN_HEADER_LENGTH+4+key_length+sequence_length
+            }
+        }
+    }
+    /// ### 功能
+    /// 把内层分组封装为外层分组的数据。IPIP直接以内层分组为数据，GRE在前面加上GRE首部
+    pub fn encapsulate(&self,packet:&[u8],sequence_number:u32)->Vec<u8>{
+        match self.kind {
+            TunnelKind::Ipip=>packet.to_vec(),
+            TunnelKind::Gre{key,sequence}=>{
+                let mut flags=0;
+                if key.is_some(){
+                    flags|=GRE_FLAG_KEY;
+                }
+                if sequence{
+                    flags|=GRE_FLAG_SEQUENCE;
+                }
+                let mut data=Vec::with_capacity(12+packet.len());
+                data.extend_from_slice(&flags.to_be_bytes());
+                data.extend_from_slice(&GRE_PROTOCOL_IPV4.to_be_bytes());
+                if let Some(key)=key{
+                    data.extend_from_slice(&key.to_be_bytes());
+                }
+                if sequence{
+                    data.extend_from_slice(&sequence_number.to_be_bytes());
+                }
+                data.extend_from_slice(packet);
+                data
+            }
+        }
+    }
+}
+
+/// ### 功能
+/// 解析一行隧道配置，格式为
+/// "名称 ipip|gre 本端地址 对端地址 隧道地址/前缀长度 [key 值] [seq] [mtu 值]"，
+/// key与seq只用于GRE；省略MTU时为以太网MTU减去封装增加的长度
+/// ### 返回值
+/// Option，(隧道，隧道地址，掩码，MTU)
+pub fn parse_tunnel(fields:&[&str])->Option<(Tunnel,[u8;4],[u8;4],usize)>{
+    if fields.len()<5{
+        return None;
+    }
+    let mut key=None;
+    let mut sequence=false;
+    let mut mtu=None;
+    let mut rest=fields[5..].iter();
+    while let Some(field)=rest.next(){
+        match *field {
+            "key"=>key=Some(rest.next()?.parse().ok()?),
+            "seq"=>sequence=true,
+            "mtu"=>mtu=Some(rest.next()?.parse().ok().filter(|mtu|*mtu>=68)?),
+            _=>return None,
+        }
+    }
+    let kind=match fields[1] {
+        "ipip" if key.is_none() && !sequence=>TunnelKind::Ipip,
+        "gre"=>TunnelKind::Gre{key,sequence},
+        _=>return None,
+    };
+    let tunnel=Tunnel{
+        name:fields[0].to_string(),
+        kind,
+        local_ip:parse_ip(fields[2])?,
+        remote_ip:parse_ip(fields[3])?,
+    };
+    let (ip,prefix_len)=fields[4].split_once('/')?;
+    let prefix_len:u32=prefix_len.parse().ok().filter(|len|*len<=32)?;
+    let mtu=mtu.unwrap_or(DEFAULT_MTU-tunnel.overhead());
+    Some((tunnel,parse_ip(ip)?,netmask_from_prefix_len(prefix_len),mtu))
+}
+
+/// ## 隧道的解封装
+/// 注册为IPIP与GRE的处理函数：按外层分组的源、目的地址（以及GRE密钥）找到隧道接口，
+/// 把内层分组作为从该接口收到的分组交给IP接收
+pub struct TunnelReceiver{
+    shared_interface_table:Arc<Mutex<InterfaceTable>>,
+    shared_ip_receive_queue:Arc<Mutex<IpReceiveQueue>>,
+    /// 带序号的GRE隧道上一次收到的序号，按接口区分
+    last_sequence_numbers:HashMap<usize,u32>,
+}
+
+impl TunnelReceiver{
+    /// ### 功能
+    /// 新建解封装的处理者
+    pub fn new(shared_interface_table:Arc<Mutex<InterfaceTable>>,shared_ip_receive_queue:Arc<Mutex<IpReceiveQueue>>)->TunnelReceiver{
+        TunnelReceiver{
+            shared_interface_table,
+            shared_ip_receive_queue,
+            last_sequence_numbers:HashMap::new(),
+        }
+    }
+    /// ### 功能
+    /// 解封装收到的IPIP或GRE数据报。没有匹配的隧道、GRE首部不支持、
+    /// 或带序号的GRE分组不按顺序到达（RFC 2890）时丢弃
+    pub fn receive(&mut self,datagram:&IpDatagram){
+        let (key,sequence_number,inner)=match datagram.protocol_type() {
+            PROTOCOL_IPIP=>(None,None,datagram.data()),
+            PROTOCOL_GRE=>match parse_gre(datagram.data()) {
+                Some(gre)=>gre,
+                None=>return,
+            },
+            _=>return,
+        };
+        let interface=self.shared_interface_table.lock().unwrap().interfaces().iter().find(|interface|{
+            match &interface.tunnel {
+                Some(tunnel)=>{
+                    tunnel.local_ip==datagram.destination_ip() && tunnel.remote_ip==datagram.source_ip()
+                        && match tunnel.kind {
+                            TunnelKind::Ipip=>datagram.protocol_type()==PROTOCOL_IPIP,
+                            TunnelKind::Gre{key:tunnel_key,..}=>datagram.protocol_type()==PROTOCOL_GRE && tunnel_key==key,
+                        }
+                }
+                None=>false,
+            }
+        }).cloned();
+        let interface=match interface {
+            Some(interface)=>interface,
+            None=>{
+                println!("没有与{}→{}匹配的隧道，丢弃分组",format_ip(datagram.source_ip()),format_ip(datagram.destination_ip()));
+                return;
+            }
+        };
+        if let (Some(TunnelKind::Gre{sequence:true,..}),Some(sequence_number))=(interface.tunnel.as_ref().map(|tunnel|tunnel.kind),sequence_number){
+            //序号按32位回绕比较，不大于上一次收到的序号的分组丢弃
+            if let Some(last)=self.last_sequence_numbers.get(&interface.index){
+                if (sequence_number.wrapping_sub(*last) as i32)<=0{
+                    return;
+                }
+            }
+            self.last_sequence_numbers.insert(interface.index,sequence_number);
+        }
+        if inner.len()<MIN_HEADER_LENGTH || inner[0]>>4!=4 || header_length(inner)>inner.len(){
+            return;
+        }
+        self.shared_ip_receive_queue.lock().unwrap().add_data(interface.index,inner);
+    }
+}
+
+/// ### 功能
+/// 解析GRE首部，只支持版本0、载荷为IPv4、没有路由字段的分组；校验和存在时检查
+/// ### 返回值
+/// Option，(密钥，序号，载荷)
+fn parse_gre(data:&[u8])->Option<(Option<u32>,Option<u32>,&[u8])>{
+    if data.len()<4{
+        return None;
+    }
+    let flags=u16::from_be_bytes([data[0],data[1]]);
+    if flags & (GRE_FLAG_ROUTING|GRE_VERSION_MASK)!=0 || u16::from_be_bytes([data[2],data[3]])!=GRE_PROTOCOL_IPV4{
+        return None;
+    }
+    let mut offset=4;
+    if flags & GRE_FLAG_CHECKSUM!=0{
+        if data.len()<offset+4 || internet_checksum(data)!=0{
+            return None;
+        }
+        offset+=4;
+    }
+    let mut key=None;
+    if flags & GRE_FLAG_KEY!=0{
+        key=Some(u32::from_be_bytes(data.get(offset..offset+4)?.try_into().unwrap()));
+        offset+=4;
+    }
+    let mut sequence_number=None;
+    if flags & GRE_FLAG_SEQUENCE!=0{
+        sequence_number=Some(u32::from_be_bytes(data.get(offset..offset+4)?.try_into().unwrap()));
+        offset+=4;
+    }
+    Some((key,sequence_number,data.get(offset..)?))
+}
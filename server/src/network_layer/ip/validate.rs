@@ -0,0 +1,143 @@
+use std::sync::{Arc,Mutex};
+use lazy_static::*;
+
+use crate::tools::address::format_ip;
+use crate::tools::checksum::internet_checksum;
+
+use super::header::{header_length,MIN_HEADER_LENGTH};
+
+lazy_static!{
+    ///静态变量--IP接收的统计，记录各种原因丢弃的分组数
+    pub static ref IP_RECEIVE_STATISTICS:Arc<Mutex<IpReceiveStatistics>> = Arc::new(Mutex::new(IpReceiveStatistics::new()));
+}
+
+/// 分组被丢弃的原因
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum IpDropReason{
+    /// 分组比最小首部还短
+    TooShort,
+    /// 版本不是4
+    BadVersion,
+    /// 首部长度小于20字节或超出分组
+    BadHeaderLength,
+    /// 首部校验和错误
+    BadChecksum,
+    /// 总长度小于首部长度或大于实际收到的长度
+    BadLength,
+    /// 选项格式错误
+    BadOptions,
+    /// 目的地址不是本机，且不转发
+    NotForUs,
+    /// 源地址不可能合法出现（0/8、127/8、E类、广播、组播或本机地址）
+    MartianSource,
+    /// 目的地址不可能合法出现（0/8、127/8、E类）
+    MartianDestination,
+    /// 未通过反向路径检查
+    ReversePath,
+}
+
+/// ## IP接收的统计
+pub struct IpReceiveStatistics{
+    /// 收到的分组数
+    pub received:u64,
+    /// 通过校验的分组数
+    pub accepted:u64,
+    pub too_short:u64,
+    pub bad_version:u64,
+    pub bad_header_length:u64,
+    pub bad_checksum:u64,
+    pub bad_length:u64,
+    pub bad_options:u64,
+    pub not_for_us:u64,
+    pub martian_source:u64,
+    pub martian_destination:u64,
+    pub reverse_path:u64,
+}
+
+impl IpReceiveStatistics{
+    /// ### 功能
+    /// 新建全部为0的统计
+    pub fn new()->IpReceiveStatistics{
+        IpReceiveStatistics{
+            received:0,
+            accepted:0,
+            too_short:0,
+            bad_version:0,
+            bad_header_length:0,
+            bad_checksum:0,
+            bad_length:0,
+            bad_options:0,
+            not_for_us:0,
+            martian_source:0,
+            martian_destination:0,
+            reverse_path:0,
+        }
+    }
+    /// ### 功能
+    /// 记录一个被丢弃的分组，并打印原因
+    pub fn record_drop(&mut self,reason:IpDropReason,packet:&[u8]){
+        let counter=match reason {
+            IpDropReason::TooShort=>&mut self.too_short,
+            IpDropReason::BadVersion=>&mut self.bad_version,
+            IpDropReason::BadHeaderLength=>&mut self.bad_header_length,
+            IpDropReason::BadChecksum=>&mut self.bad_checksum,
+            IpDropReason::BadLength=>&mut self.bad_length,
+            IpDropReason::BadOptions=>&mut self.bad_options,
+            IpDropReason::NotForUs=>&mut self.not_for_us,
+            IpDropReason::MartianSource=>&mut self.martian_source,
+            IpDropReason::MartianDestination=>&mut self.martian_destination,
+            IpDropReason::ReversePath=>&mut self.reverse_path,
+        };
+        *counter+=1;
+        if packet.len()>=MIN_HEADER_LENGTH{
+            let source_ip:[u8;4]=packet[12..16].try_into().unwrap();
+            let destination_ip:[u8;4]=packet[16..20].try_into().unwrap();
+            println!("丢弃IP分组 {}->{}：{:?}（累计{}）",format_ip(source_ip),format_ip(destination_ip),reason,counter);
+        }
+        else{
+            println!("丢弃IP分组：{:?}（累计{}）",reason,counter);
+        }
+    }
+    /// ### 功能
+    /// 丢弃的分组总数
+    pub fn dropped(&self)->u64{
+        self.too_short+self.bad_version+self.bad_header_length+self.bad_checksum
+            +self.bad_length+self.bad_options+self.not_for_us
+            +self.martian_source+self.martian_destination+self.reverse_path
+    }
+    /// ### 功能
+    /// 打印统计
+    pub fn print(&self){
+        println!("收到{}个IP分组，接受{}个，丢弃{}个",self.received,self.accepted,self.dropped());
+        println!("  过短 {}  版本错误 {}  首部长度错误 {}  校验和错误 {}  总长度错误 {}  选项错误 {}  目的地址非本机 {}",
+            self.too_short,self.bad_version,self.bad_header_length,self.bad_checksum,
+            self.bad_length,self.bad_options,self.not_for_us);
+        println!("  火星源地址 {}  火星目的地址 {}  反向路径检查失败 {}",
+            self.martian_source,self.martian_destination,self.reverse_path);
+    }
+}
+
+/// ### 功能
+/// 校验收到的分组首部：版本、首部长度、首部校验和，以及总长度与实际收到的长度
+/// ### 返回值
+/// Result，通过时为首部中的总长度，其后的内容是链路层的填充；否则为丢弃原因
+pub fn validate_header(packet:&[u8])->Result<usize,IpDropReason>{
+    if packet.len()<MIN_HEADER_LENGTH{
+        return Err(IpDropReason::TooShort);
+    }
+    if packet[0]>>4!=4{
+        return Err(IpDropReason::BadVersion);
+    }
+    let header_len=header_length(packet);
+    if header_len<MIN_HEADER_LENGTH || header_len>packet.len(){
+        return Err(IpDropReason::BadHeaderLength);
+    }
+    if internet_checksum(&packet[0..header_len])!=0{
+        return Err(IpDropReason::BadChecksum);
+    }
+    let total_length=u16::from_be_bytes([packet[2],packet[3]]) as usize;
+    if total_length<header_len || total_length>packet.len(){
+        return Err(IpDropReason::BadLength);
+    }
+    Ok(total_length)
+}
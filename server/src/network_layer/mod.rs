@@ -1,3 +1,6 @@
 pub mod ip;
 pub mod arp;
-pub mod icmp;
\ No newline at end of file
+pub mod icmp;
+pub mod rarp;
+pub mod igmp;
+pub mod filter;
\ No newline at end of file
@@ -0,0 +1,2 @@
+pub mod receive;
+pub mod table;
\ No newline at end of file
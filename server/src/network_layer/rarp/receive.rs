@@ -0,0 +1,90 @@
+use std::collections::VecDeque;
+use std::sync::{Arc,Mutex};
+use std::thread::yield_now;
+use lazy_static::*;
+
+use crate::data_link_layer::ethernet_v2::send::Eth2SendQueue;
+use crate::data_link_layer::interface::InterfaceTable;
+use crate::network_layer::arp::send::build_arp_frame;
+use crate::tools::address::{format_ip,format_mac};
+
+use super::table::RarpTable;
+
+lazy_static!{
+    ///静态变量--RARP的接收队列
+    pub static ref RARP_RECEIVE_QUEUE:Arc<Mutex<RarpReceiveQueue>> = Arc::new(Mutex::new(RarpReceiveQueue::new()));
+}
+
+///RARP的接收队列，帧格式与ARP相同，元素为(收到该帧的接口，RARP帧)
+pub struct RarpReceiveQueue(
+    VecDeque<(usize,[u8;28])>
+);
+
+impl RarpReceiveQueue{
+    ///生成接收队列
+    pub fn new() -> Self{
+        let new_receive_queue=VecDeque::new();
+        RarpReceiveQueue(new_receive_queue)
+    }
+    /// datalink向其中写入数据。
+    pub fn add_data(&mut self,interface:usize,buffer: [u8;28]) -> bool{
+        self.0.push_back((interface,buffer));
+        true
+    }
+    /// 获取队列数据
+    pub fn get_data(&mut self)-> Option<(usize,[u8;28])>{
+        self.0.pop_front()
+    }
+
+    /// 队列是否为空
+    pub fn is_empty(&self)->bool{
+        self.0.is_empty()
+    }
+}
+
+///### 功能
+/// RARP服务器：处理收到的RARP请求，从映射表中查找请求者的IP地址并应答。
+/// 表中没有的MAC地址不予应答
+pub fn receive(
+    shared_interface_table:Arc<Mutex<InterfaceTable>>,
+    shared_rarp_table:Arc<Mutex<RarpTable>>,
+    shared_rarp_receive_queue:Arc<Mutex<RarpReceiveQueue>>,
+    shared_ethernet_v2_send_queue:Arc<Mutex<Eth2SendQueue>>
+){
+    loop{
+        let mut receive_queue=shared_rarp_receive_queue.lock().unwrap();
+        if receive_queue.is_empty(){
+            yield_now();
+            continue;
+        }
+
+        let (interface_index,rarp_frame)=receive_queue.get_data().unwrap();
+        let interface=match shared_interface_table.lock().unwrap().get(interface_index) {
+            Some(interface)=>interface.clone(),
+            None=>continue,
+        };
+
+        //只处理RARP请求报文（op=3），不是则直接丢弃
+        if rarp_frame[6..8]!=[0x00,0x03]{
+            continue;
+        }
+
+        //请求的是目的mac地址对应的ip
+        let target_mac:[u8;6]=rarp_frame[18..24].try_into().unwrap();
+        let target_ip=match shared_rarp_table.lock().unwrap().find_ip_from_mac(target_mac) {
+            Some(ip)=>ip,
+            None=>{
+                println!("RARP请求的MAC地址{}不在映射表中",format_mac(target_mac));
+                continue;
+            }
+        };
+        println!("RARP应答：{} 分配到 {}",format_mac(target_mac),format_ip(target_ip));
+
+        //封装为帧。op=4代表为RARP应答
+        let reply_frame=build_arp_frame(4,interface.mac,interface.ip,target_mac,target_ip);
+
+        //从收到请求的接口发送给请求者--写入到Ethernet-v2的发送队列里
+        let requester_mac:[u8;6]=rarp_frame[8..14].try_into().unwrap();
+        shared_ethernet_v2_send_queue.lock().unwrap().add_data_to(interface.index,requester_mac,0x8035,&Vec::from(reply_frame));
+    }
+}
@@ -0,0 +1,79 @@
+use lazy_static::*;
+use std::fs::File;
+use std::io::{BufRead,BufReader};
+use std::sync::{Arc,Mutex};
+
+use crate::tools::address::{parse_ip,parse_mac};
+
+lazy_static!{
+    //静态变量--RARP服务器使用的MAC→IP映射表
+    pub static ref RARP_TABLE:Arc<Mutex<RarpTable>> = Arc::new(Mutex::new(RarpTable::new()));
+}
+
+/// ## RARP映射表
+/// 记录每个MAC地址应当分配的IP地址
+pub struct RarpTable{
+    inner:Vec<([u8;6],[u8;4])>
+}
+
+impl RarpTable{
+    /// ### 功能
+    /// 新建一个空的映射表
+    pub fn new()->RarpTable{
+        RarpTable{
+            inner:Vec::new()
+        }
+    }
+    /// ### 功能
+    /// 从ethers格式的文件中加载映射。
+    /// 每行一个表项，格式为"MAC地址 IP地址"，以#开头的内容为注释
+    /// ### 返回值
+    /// 成功加载的表项数
+    pub fn load(&mut self,file_path:&str)->usize{
+        let file=match File::open(file_path) {
+            Ok(file)=>file,
+            Err(err)=>{
+                println!("读取RARP映射表失败！{file_path}:{err}");
+                return 0;
+            }
+        };
+        let mut count=0;
+        for (line_number,line) in BufReader::new(file).lines().enumerate(){
+            let line=match line {
+                Ok(line)=>line,
+                Err(_)=>break,
+            };
+            //去掉注释
+            let line=line.split('#').next().unwrap_or("");
+            let fields:Vec<&str>=line.split_whitespace().collect();
+            if fields.is_empty(){
+                continue;
+            }
+            match (fields.len(),parse_mac(fields[0]),fields.get(1).and_then(|field|parse_ip(field))) {
+                (2,Some(mac),Some(ip))=>{
+                    self.insert(mac,ip);
+                    count+=1;
+                }
+                _=>{
+                    println!("RARP映射表格式错误！{file_path}:{}",line_number+1);
+                }
+            }
+        }
+        count
+    }
+    /// ### 功能
+    /// 插入或更新一条映射
+    pub fn insert(&mut self,mac:[u8;6],ip:[u8;4]){
+        match self.inner.iter_mut().find(|(old_mac,_)|*old_mac==mac) {
+            Some(old)=>old.1=ip,
+            None=>self.inner.push((mac,ip)),
+        }
+    }
+    /// ### 功能
+    /// 根据MAC地址查找应分配的IP地址
+    /// ### 返回值
+    /// Option，找到则返回IP地址
+    pub fn find_ip_from_mac(&self,mac:[u8;6])->Option<[u8;4]>{
+        self.inner.iter().find(|(old_mac,_)|*old_mac==mac).map(|(_,ip)|*ip)
+    }
+}
@@ -0,0 +1,95 @@
+//! 地址的解析与格式化工具
+
+/// ### 功能
+/// 将点分十进制字符串（如"10.10.10.3"）解析为IPv4地址
+/// ### 返回值
+/// Option，格式正确则返回地址
+pub fn parse_ip(s:&str)->Option<[u8;4]>{
+    let mut ip:[u8;4]=[0;4];
+    let mut count=0;
+    for part in s.trim().split('.'){
+        if count>=4{
+            return None;
+        }
+        ip[count]=part.parse::<u8>().ok()?;
+        count+=1;
+    }
+    if count!=4{
+        return None;
+    }
+    Some(ip)
+}
+
+/// ### 功能
+/// 将形如"14:5A:FC:15:1A:9D"或"14-5A-FC-15-1A-9D"的字符串解析为MAC地址
+/// ### 返回值
+/// Option，格式正确则返回地址
+pub fn parse_mac(s:&str)->Option<[u8;6]>{
+    let mut mac:[u8;6]=[0;6];
+    let mut count=0;
+    for part in s.trim().split([':','-']){
+        if count>=6 || part.is_empty() || part.len()>2{
+            return None;
+        }
+        mac[count]=u8::from_str_radix(part,16).ok()?;
+        count+=1;
+    }
+    if count!=6{
+        return None;
+    }
+    Some(mac)
+}
+
+/// ### 功能
+/// 将IPv4地址格式化为点分十进制
+pub fn format_ip(ip:[u8;4])->String{
+    format!("{}.{}.{}.{}",ip[0],ip[1],ip[2],ip[3])
+}
+
+/// ### 功能
+/// 将MAC地址格式化为冒号分隔的十六进制
+pub fn format_mac(mac:[u8;6])->String{
+    format!("{:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",mac[0],mac[1],mac[2],mac[3],mac[4],mac[5])
+}
+
+/// ### 功能
+/// 由前缀长度生成子网掩码
+pub fn netmask_from_prefix_len(prefix_len:u32)->[u8;4]{
+    if prefix_len==0{
+        return [0;4];
+    }
+    (u32::MAX << (32-prefix_len.min(32))).to_be_bytes()
+}
+
+/// ### 功能
+/// 解析"网络/前缀长度"，省略前缀长度时为单个地址
+/// ### 返回值
+/// Option，(网络，掩码)
+pub fn parse_prefix(s:&str)->Option<([u8;4],[u8;4])>{
+    match s.split_once('/') {
+        Some((network,prefix_len))=>{
+            let prefix_len:u32=prefix_len.parse().ok().filter(|len|*len<=32)?;
+            Some((parse_ip(network)?,netmask_from_prefix_len(prefix_len)))
+        }
+        None=>Some((parse_ip(s)?,[255;4])),
+    }
+}
+
+/// ### 功能
+/// 判断两个IPv4地址在给定掩码下是否属于同一子网
+pub fn is_same_subnet(a:[u8;4],b:[u8;4],netmask:[u8;4])->bool{
+    let netmask=u32::from_be_bytes(netmask);
+    (u32::from_be_bytes(a) & netmask)==(u32::from_be_bytes(b) & netmask)
+}
+
+/// ### 功能
+/// IP地址是否为组播地址（224.0.0.0/4）
+pub fn is_multicast_ip(ip:[u8;4])->bool{
+    ip[0]>>4==0xE
+}
+
+/// ### 功能
+/// 组播地址对应的以太网MAC地址：01:00:5E加上组地址的低23位（RFC 1112）
+pub fn multicast_mac(group:[u8;4])->[u8;6]{
+    [0x01,0x00,0x5E,group[1]&0x7F,group[2],group[3]]
+}
@@ -0,0 +1,26 @@
+/// ### 功能
+/// 计算互联网校验和（RFC 1071），IP首部、ICMP等协议均使用此算法。
+/// 数据长度为奇数时，末尾补一个0字节
+/// ### 返回值
+/// 16bit的校验和（已取反）。对带有正确校验和的数据计算，结果为0
+pub fn internet_checksum(data:&[u8])->u16{
+    let mut sum:u32=0;
+    for chunk in data.chunks(2){
+        let word=if chunk.len()==2 {
+            (chunk[0] as u32)<<8 | chunk[1] as u32
+        }
+        else{
+            //如果最后剩了一字节，作为高8位
+            (chunk[0] as u32)<<8
+        };
+        sum+=word;
+    }
+
+    //压缩32位到16位
+    while sum>>16 >0
+    {
+        sum=(sum & 0xffff)+(sum>>16);
+    }
+
+    !(sum as u16)
+}
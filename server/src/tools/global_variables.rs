@@ -1,4 +1,4 @@
-/// 设置一些系统的常量
+//! 设置一些系统的常量
 
 /// 本机的MAC地址 随便写的
 pub const LOCAL_MAC:[u8;6]=[ 0x14, 0x5A, 0xFC, 0x15, 0x1A, 0x9D ];
@@ -9,10 +9,40 @@ pub const GATEWAY_IP:[u8;4]=[ 10, 10, 11, 1];
 /// 子网掩码
 pub const NETMASK:[u8;4]=[ 255, 255, 248, 0 ];
 /// DNS服务器的IP地址
+#[allow(dead_code)]
 pub const DNS_SERVER_IP:[u8;4]=[ 211, 137, 130, 3 ];
 /// DHCP服务器的IP地址
+#[allow(dead_code)]
 pub const DHCP_SERVER_IP:[u8;4]=[ 111, 20, 62, 57 ];
 /// 广播MAC地址，全1
 pub const BROADCAST_MAC:[u8;6] = [ 0xff, 0xff, 0xff, 0xff, 0xff, 0xff ];
+/// RARP服务器的MAC→IP映射表文件（ethers格式）
+pub const RARP_TABLE_PATH:&str="ethers";
 
 
+/// 网络接口的配置文件，不存在时只使用由上面常量确定的一个接口
+pub const INTERFACE_TABLE_PATH:&str="interfaces";
+/// 隧道接口（IPIP、GRE）的配置文件，不存在时不使用隧道
+pub const TUNNEL_TABLE_PATH:&str="tunnels";
+/// 静态路由的配置文件
+pub const ROUTE_TABLE_PATH:&str="routes";
+/// 启动时在第一个接口上加入的组播组
+pub const JOINED_MULTICAST_GROUPS:&[[u8;4]]=&[];
+/// 发送队列是否使用加权轮询，否则为严格优先级
+pub const WEIGHTED_ROUND_ROBIN:bool=false;
+/// 加权轮询时各优先级类别（网络控制、实时、保证转发、尽力而为）每轮最多发送的帧数
+pub const TRANSMIT_CLASS_WEIGHTS:[u32;4]=[8,4,2,1];
+/// 流量控制（发送整形、接收监管）的配置文件，不存在时不限速
+pub const TRAFFIC_CONTROL_PATH:&str="shaping";
+/// 以太网的默认MTU
+pub const DEFAULT_MTU:usize=1500;
+/// 是否进行路径MTU发现（RFC 1191）：本机发出的分组均带DF标志，并按ICMP需要分片报文调整分片大小
+pub const PATH_MTU_DISCOVERY:bool=true;
+/// 过滤规则的配置文件，不存在时全部放行
+pub const FILTER_RULES_PATH:&str="filter";
+/// 源地址转换（SNAT/伪装）规则的配置文件，只在路由器模式下使用
+pub const NAT_RULES_PATH:&str="nat";
+/// 接收时的反向路径检查（RFC 3704）：off、loose（存在到源地址的路由）或strict（路由的出接口即入接口）
+pub const REVERSE_PATH_FILTER:&str="loose";
+/// 是否转发目的地址不是本机的IP数据报（路由器模式）
+pub const IP_FORWARDING:bool=false;